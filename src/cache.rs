@@ -0,0 +1,226 @@
+//! Keeps the aliases generated from a configuration, so that shells that run `dalia aliases`
+//! every time they start don't have to read every directory a `[*]` line names again.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The file next to the configuration file that the generated aliases are cached in.
+pub const CACHE_FILE: &str = "aliases.cache";
+
+/// The first line of a cache file, which changes whenever the format does.
+const HEADER: &str = "dalia cache 2";
+
+/// The line that separates what the cached aliases were generated from and the aliases.
+const SEPARATOR: &str = "--";
+
+/// Cache is the file that holds the aliases generated from a configuration with a given set of
+/// arguments by this version of dalia. Cached aliases are only used while the configuration file
+/// and the paths its lines read have the same size and modification time, and the environment
+/// variables it depends on have the same values, as when they were generated.
+pub struct Cache {
+    path: PathBuf,
+    config: PathBuf,
+    key: String,
+}
+
+impl Cache {
+    /// Returns the cache for aliases generated from the configuration at `config` with `args`.
+    pub fn new(config: &Path, args: &[String]) -> Self {
+        Cache {
+            path: config.with_file_name(CACHE_FILE),
+            config: config.to_path_buf(),
+            key: format!("{} {:?} {:?}", env!("CARGO_PKG_VERSION"), config, args),
+        }
+    }
+
+    /// Returns the cached aliases, or `None` when there aren't any or anything they were
+    /// generated from has changed since.
+    pub fn load(&self) -> Option<String> {
+        let contents = fs::read_to_string(&self.path).ok()?;
+        let (inputs, aliases) = contents.split_once(&format!("\n{}\n", SEPARATOR))?;
+        let mut lines = inputs.lines();
+        if lines.next()? != HEADER || lines.next()? != self.key {
+            return None;
+        }
+        for line in lines {
+            if let Some(variable) = line.strip_prefix('$') {
+                if variable != describe_variable(variable.split('=').next()?) {
+                    return None;
+                }
+                continue;
+            }
+            let (recorded, path) = line.split_once('\t')?;
+            if recorded != stamp(Path::new(path)) {
+                return None;
+            }
+        }
+        Some(aliases.to_string())
+    }
+
+    /// Caches `aliases`, which were generated from the configuration file along with the
+    /// directories and files in `paths` and the environment variables named in `variables`.
+    pub fn store(&self, paths: &[PathBuf], variables: &[String], aliases: &str) -> io::Result<()> {
+        let mut contents = format!("{}\n{}\n", HEADER, self.key);
+        for name in variables {
+            let variable = describe_variable(name);
+            if variable.contains('\n') {
+                return Ok(());
+            }
+            contents.push_str(&format!("${}\n", variable));
+        }
+        for path in std::iter::once(&self.config).chain(paths) {
+            let path = path.display().to_string();
+            // A path on more than one line can't be read back, so it's safer not to cache.
+            if path.contains('\n') {
+                return Ok(());
+            }
+            contents.push_str(&format!("{}\t{}\n", stamp(Path::new(&path)), path));
+        }
+        contents.push_str(SEPARATOR);
+        contents.push('\n');
+        contents.push_str(aliases);
+        fs::write(&self.path, contents)
+    }
+}
+
+/// Describes the value of the environment variable `name` as `name=value`, or just `name` when
+/// it isn't set.
+fn describe_variable(name: &str) -> String {
+    match env::var_os(name) {
+        Some(value) => format!("{}={}", name, value.to_string_lossy()),
+        None => name.to_string(),
+    }
+}
+
+/// Describes the size and modification time of the file or directory at `path`, which change
+/// whenever its contents do, or `missing` when it doesn't exist.
+fn stamp(path: &Path) -> String {
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_nanos());
+            format!("{}:{}", metadata.len(), modified)
+        }
+        Err(_) => "missing".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_load_returns_stored_aliases() -> io::Result<()> {
+        let temp = temp_testdir::TempDir::default();
+        let config = PathBuf::from(temp.as_ref()).join("config");
+        write(&config, "[work]/some/work/path\n")?;
+
+        let cache = Cache::new(&config, &args(&["--shell", "zsh"]));
+        assert_eq!(None, cache.load());
+        cache.store(&[], &[], "alias work='cd \"/some/work/path\"'\n")?;
+        assert_eq!(
+            Some("alias work='cd \"/some/work/path\"'\n".to_string()),
+            cache.load()
+        );
+        assert_eq!(None, Cache::new(&config, &args(&[])).load());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_misses_when_the_configuration_changes() -> io::Result<()> {
+        let temp = temp_testdir::TempDir::default();
+        let config = PathBuf::from(temp.as_ref()).join("config");
+        write(&config, "[work]/some/work/path\n")?;
+
+        let cache = Cache::new(&config, &[]);
+        cache.store(&[], &[], "alias work='cd \"/some/work/path\"'\n")?;
+        write(&config, "[work]/some/work/path\n[play]/some/play\n")?;
+        assert_eq!(None, cache.load());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_misses_when_a_glob_directory_changes() -> io::Result<()> {
+        let temp = temp_testdir::TempDir::default();
+        let config = PathBuf::from(temp.as_ref()).join("config");
+        let projects = PathBuf::from(temp.as_ref()).join("projects");
+        fs::create_dir_all(projects.join("api"))?;
+        write(&config, format!("[*]{}\n", projects.display()))?;
+
+        let cache = Cache::new(&config, &[]);
+        cache.store(&[projects.clone(), projects.join(".daliaignore")], &[], "")?;
+        assert_eq!(Some(String::new()), cache.load());
+        write(projects.join(".daliaignore"), "api\n")?;
+        assert_eq!(None, cache.load());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_misses_when_a_missing_path_is_created() -> io::Result<()> {
+        let temp = temp_testdir::TempDir::default();
+        let config = PathBuf::from(temp.as_ref()).join("config");
+        let preferred = PathBuf::from(temp.as_ref()).join("a");
+        write(
+            &config,
+            format!("[work]{} | /some/b\n", preferred.display()),
+        )?;
+
+        let cache = Cache::new(&config, &[]);
+        cache.store(std::slice::from_ref(&preferred), &[], "")?;
+        assert_eq!(Some(String::new()), cache.load());
+        fs::create_dir(&preferred)?;
+        assert_eq!(None, cache.load());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_misses_when_a_variable_changes() -> io::Result<()> {
+        let temp = temp_testdir::TempDir::default();
+        let config = PathBuf::from(temp.as_ref()).join("config");
+        write(&config, "[*]$DALIA_CACHE_TEST_DIR\n")?;
+        let variables = ["DALIA_CACHE_TEST_DIR".to_string()];
+
+        env::set_var("DALIA_CACHE_TEST_DIR", "/some/work");
+        let cache = Cache::new(&config, &[]);
+        cache.store(&[], &variables, "")?;
+        let unchanged = cache.load();
+        env::set_var("DALIA_CACHE_TEST_DIR", "/some/play");
+        let changed = cache.load();
+        env::remove_var("DALIA_CACHE_TEST_DIR");
+        let unset = cache.load();
+
+        assert_eq!(Some(String::new()), unchanged);
+        assert_eq!(None, changed);
+        assert_eq!(None, unset);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_misses_after_an_upgrade() -> io::Result<()> {
+        let temp = temp_testdir::TempDir::default();
+        let config = PathBuf::from(temp.as_ref()).join("config");
+        write(&config, "[work]/some/work/path\n")?;
+
+        let cache = Cache::new(&config, &[]);
+        cache.store(&[], &[], "")?;
+        let cache_file = config.with_file_name(CACHE_FILE);
+        let stored = fs::read_to_string(&cache_file)?;
+        write(
+            &cache_file,
+            stored.replace(env!("CARGO_PKG_VERSION"), "0.0.1"),
+        )?;
+        assert_eq!(None, cache.load());
+        Ok(())
+    }
+}
@@ -1,37 +1,108 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{env, fs};
 
-use crate::parser::Parser;
+use crate::cache::Cache;
+use crate::error::{DaliaError, DaliaWarning};
+use crate::format::format_config;
+use crate::lexer::{is_alias_char, Lexer};
+use crate::parser::{
+    is_absolute_config_path, AliasEntry, AliasSource, EntryKind, Parser, ParserOptions,
+    DEFAULT_FILE_COMMAND,
+};
+use crate::report;
+use crate::shell::ShellFormat;
+use notify::{EventKind, RecursiveMode, Watcher};
 
 const DALIA_CONFIG_ENV_VAR: &str = "DALIA_CONFIG_PATH";
+const DALIA_CONFIG_FILE_ENV_VAR: &str = "DALIA_CONFIG_FILE";
+const DALIA_MAX_ALIASES_ENV_VAR: &str = "DALIA_MAX_ALIASES";
 const CONFIG_FILE: &str = "config";
-const DEFAULT_DALIA_CONFIG_PATH: &str = "~/.dalia";
+/// The configuration source that stands for standard input, as in `dalia aliases -`.
+const STDIN: &str = "-";
+/// What a configuration read from standard input is called in errors and warnings.
+const STDIN_NAME: &str = "<stdin>";
+const XDG_CONFIG_HOME_ENV_VAR: &str = "XDG_CONFIG_HOME";
+const DEFAULT_XDG_CONFIG_HOME: &str = "~/.config";
+const LEGACY_DALIA_CONFIG_PATH: &str = "~/.dalia";
 const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
-const USAGE: &str = r#"Usage: dalia <command> [arguments]
+const COMMAND_NAMES: [&str; 11] = [
+    "aliases",
+    "watch",
+    "list",
+    "stat",
+    "lint",
+    "format",
+    "config",
+    "init",
+    "completions",
+    "version",
+    "help",
+];
+/// Common commands that an alias with the same name would hide.
+const SHADOWED_COMMANDS: [&str; 32] = [
+    "cat", "cd", "chmod", "chown", "cp", "df", "du", "echo", "find", "git", "grep", "kill", "less",
+    "ln", "ls", "make", "man", "mkdir", "mv", "open", "ps", "pwd", "rm", "ssh", "sudo", "tar",
+    "test", "top", "touch", "vi", "vim", "which",
+];
+const USAGE: &str = r#"Usage: dalia [--quiet] <command> [arguments]
 
 Commands:
     aliases: Generates all shell aliases for each configured directory at DALIA_CONFIG_PATH
+    watch: Generates all shell aliases again whenever the configuration file changes
+    list: Prints the configured aliases along with their paths and descriptions
+    stat: Prints statistics about the configured aliases
+    lint: Reports style problems in the configuration file
+    format: Rewrites the configuration file in a consistent style, with its aliases sorted
+    config: Prints the location of the configuration file, or opens it in an editor
+    init: Prints the line that loads dalia's aliases in a shell's startup file
+    completions: Prints a shell completion script for dalia
     version: The current build version
     help: Prints this usage message
+
+Flags:
+    -q, --quiet: Suppresses warnings; errors and generated output are still printed
     
 Examples:
     $ dalia aliases
 
 Environment:
 DALIA_CONFIG_PATH
-    The location where dalia looks for alias configurations. This is set to $HOME/dalia by default.
-    Put the alias configurations in a file named `config` here. 
+    The location where dalia looks for alias configurations. This is set to $XDG_CONFIG_HOME/dalia
+    (or ~/.config/dalia) by default, or to ~/.dalia when only that directory exists. Put the alias
+    configurations in a file named `config` here.
+
+DALIA_CONFIG_FILE
+    The name of the configuration file inside DALIA_CONFIG_PATH, for when it isn't named `config`
+    (e.g. `config.work`). When set to a full path instead, it takes precedence over
+    DALIA_CONFIG_PATH.
+
+DALIA_MAX_ALIASES
+    The most aliases the configuration can create, 1000 by default. Expanding a `[*]` line past it
+    is an error.
     
 Use "dalia help <command> for more information about that command."#;
 
-const ALIASES_USAGE: &str = r#"Usage: dalia aliases
+const ALIASES_USAGE: &str = r#"Usage: dalia aliases [--preserve-case] [--strict] [--include-hidden] [--include-unreadable]
+       [--no-follow-symlinks] [--disambiguate] [--canonicalize] [--base <dir>] [--max-glob <n>]
+       [--file-command <cmd>] [--with-unalias] [--with-vars] [--sort[=path]] [--prefix <text>]
+       [--suffix <text>] [--shell <name>] [--no-shadow-check] [--output-path <file>] [--no-cache]
+       [--config <file> | -]
 
 Description:
     Aliases generates shell aliases for each directory listed in DALIA_CONFIG_PATH/config.
     The aliases are only for changing directories to the specified locations. No other types
     of aliases are supported.
     
-    Each alias outputted by this command is of the form `alias path="cd /some/path"`.
+    Each alias outputted by this command is of the form `alias path="cd /some/path"`. Each alias
+    name can only be defined once; configuring the same name for two different paths is an error,
+    except that a custom name always takes precedence over a directory with the same name from a
+    `[*]` line, wherever the two lines are, with a warning.
     
     The configuration file uses its own format to generate aliases. The simplest way to generate
     an alias to a directory is to provide its absolute path on disk. The generated alias will use
@@ -39,152 +110,3780 @@ Description:
     alias name can be customized as well, by prepending the absolute path with a custom name surrounded
     by square brackets (i.e. `[` and `]`). The casing of the custom name doesn't change, so if it's
     provided in titlecase, snakecase, or any other case, the alias will be created with that case in
-    tact.
+    tact. A custom name can also be written as `name = /some/absolute/path`.
+
+    A line can list several paths separated by `|`, e.g. `[work]/opt/work | ~/work`, to create the
+    alias for the first one that exists. The alias is skipped with a warning if none of them exist.
+    Write `\|` for a `|` that's part of a path.
+
+    Relative paths, like `projects/api` or `../shared`, are resolved against the directory that
+    contains the configuration file, or the directory given with --base.
+
+    Aliases change directories with `cd` by default. A custom name can be followed by a colon and
+    another command to use instead, so `[mydir:pushd]/some/path` creates `alias mydir='pushd "/some/path"'`.
+    A custom name can instead be followed by a template in curly braces, where `%p` stands for the
+    path, so `[gs]{cd %p && git status}/some/path` creates `alias gs='cd "/some/path" && git status'`.
+    A description in double quotes can follow the path, like `[proj]/some/path "my main project"`,
+    and is left out of the generated alias.
     
     This command also expands a single directory into multiple aliases when the configured line starts with
     an asterisk surrounded by square brackets (i.e. `[*]`), which tells the parser to traverse the immediate
     children of the given directory and create lowercase named aliases for only the items that are directories.
-    All children that are files are ignored.
+    All children that are files are ignored, and so are hidden directories unless --include-hidden is given
+    or the asterisk is followed by an `a` (i.e. `[*a]`).
+    An `f` after the asterisks (i.e. `[*f]`) creates aliases for files too, named after the file without
+    its extension, which open the file with `$EDITOR` instead of changing to it.
+    Two asterisks (i.e. `[**]`) expand every directory below the given one, at any depth, and a number
+    after them limits the depth, so `[**2]/code` creates aliases for `/code/org` and `/code/org/repo`.
+    Directories with the same name at different places in the tree, or from different `[*]` lines, are
+    reported as duplicate aliases unless --disambiguate is given.
+    A colon and a pattern after the asterisks only creates aliases for directories whose names match
+    it, where `*` matches any text and `?` matches a single character, e.g. `[*:proj-*]/code`.
+    Directories can be left out of the expansion, along with everything inside them, by listing their
+    names after the path with a leading `!`, e.g. `[**]/code !node_modules !target`.
+    They can also be listed inside the brackets, where they may contain the same wildcards as a
+    pattern, e.g. `[**!node_modules!*_old]/code`. More can be listed, one per line, in a
+    `.daliaignore` file in the expanded directory.
+    Text before the asterisks is added to the start of every generated name, and text after them
+    that starts with `-`, `_`, or `.` is added to the end, so `[w-*-dev]/dev` creates `w-acme-dev`.
+    File extensions in parentheses right after the asterisks, like `[*.(rs|py)]/code`, limit the
+    aliases to directories that directly contain a file with one of them.
+
+    A line starting with `!` removes an alias created by any other line, either by its name (e.g.
+    `!archive`) or by its path (e.g. `!/code/archive`). Exclusions apply once the whole configuration
+    has been read, so they can appear anywhere, and ones that don't match an alias are warned about.
+
+    A line can be limited to one operating system by starting it with `@` and the operating system's
+    name (e.g. `@macos` or `@linux`). Lines guarded for another operating system are skipped entirely,
+    so the same alias can be configured once per operating system.
+
+    Paths that share a long prefix can use a variable. A line like `$work = /some/long/path` defines
+    the variable `work`, and later lines can reference it as `$work` (e.g. `[api]$work/api`). Variables
+    must be defined before they're used and can't be redefined. References to names that aren't
+    defined in the configuration but are set in the environment (e.g. `$HOME`) are left for the shell.
+
+    A custom name's path can start from the path of another alias with `@` and its name, e.g.
+    `[work]@base/work`. The other alias can be defined anywhere in the configuration, including by
+    another reference, but references that lead back to themselves are an error.
+
+    The first line of the configuration can be a `version = 1` header naming the version of the
+    configuration format it's written in. Configurations without one are read as version 1, and ones
+    newer than this version of dalia understands are read as well as possible, with a warning.
+
+    A `[section: name]` line on its own adds the section's name and a `-` to the start of every alias
+    on the lines after it, up to the next section, so `/repos/frontend` after `[section: work]`
+    creates `alias work-frontend='cd "/repos/frontend"'`. A `[section:]` line ends the section.
+
+    A line containing `@case preserve` keeps the original casing of alias names derived from the paths
+    on the lines after it, just like the --preserve-case flag, and `@case lower` switches back to
+    lowercase names.
+
+Flags:
+    --preserve-case
+        Keeps the original casing of alias names derived from paths, including those created by
+        directory expansion, instead of lowercasing them. Custom names are never changed.
+
+    --strict
+        Treats aliases whose names differ only in case (e.g. `Docs` and `docs`), and `[*]` lines
+        that create more aliases than --max-glob allows, as an error instead of a warning.
+
+    --include-hidden
+        Creates aliases for hidden directories, whose names start with a `.`, when expanding a
+        `[*]` line. They're skipped by default.
+
+    --include-unreadable
+        Creates aliases for directories from a `[*]` line that the current user can't enter, for
+        aliases that will be used by a different user. They're skipped with a warning by default.
+
+    --no-follow-symlinks
+        Skips symbolic links when expanding a `[*]` line. By default, a symbolic link to a directory
+        gets an alias that points at the link, but the directory it links to isn't expanded.
+
+    --disambiguate
+        Renames every directory from a `[*]` line that would get the same alias as another one to
+        include the name of its parent directory, so `/work/notes` and `/personal/notes` become
+        `work-notes` and `personal-notes` instead of being reported as duplicates. Custom names
+        are never renamed.
+
+    --canonicalize
+        Resolves symbolic links and `..` in paths that exist before checking whether two paths
+        configured for the same alias are the same directory. Paths that only differ by trailing
+        slashes are always the same path, and paths that only differ by `~` or environment
+        variables are always treated as the same directory, with a warning.
+
+    --base <dir>
+        Resolves relative paths, like `projects/api` or `../shared`, against the given directory
+        instead of the directory containing the configuration file.
+
+    --max-glob <n>
+        Warns about any `[*]` line that creates more than n aliases, 200 by default, which usually
+        means it points at the wrong directory.
+
+    --file-command <cmd>
+        Sets the command that aliases for files from a `[*f]` line run, instead of `$EDITOR`.
+
+    --with-unalias
+        Removes every alias before defining it (with `unalias`, or `functions --erase` in fish),
+        so that sourcing the output again replaces whatever had the same name.
+
+    --with-vars
+        Also exports a variable with each alias's path, named after the alias in uppercase, e.g.
+        `export DIR_PROJ_API=~/"code/api"` for `proj.api`. When aliases like `proj.api` and
+        `proj-api` would get the same variable, only the first of them by name gets it, with a
+        warning.
+
+    --sort, --sort=path
+        Writes the aliases sorted by name, ignoring case, or by the path they point at.
+
+    --prefix <text>, --suffix <text>
+        Adds text to the start or end of every alias name, e.g. `--prefix proj-` turns `api` into
+        `proj-api`, to keep dalia's aliases apart from others. The text may only contain letters,
+        digits, `_`, and `-`.
+
+    --shell <name>
+        Writes the aliases in the syntax of the given shell: bash (the default), zsh, posix, fish,
+        nushell (or nu), powershell, elvish, tcsh (or csh), or cmd. Fish aliases are written as
+        functions, nushell aliases as `def --env` commands, and powershell aliases as functions
+        that run `Set-Location`, e.g.
+        `function docs { Set-Location 'C:\Users\me\Documents' }`. Aliases whose names
+        PowerShell can't call, like ones that start with a digit, are written with a warning.
+        Elvish aliases are written as functions added to the REPL, e.g.
+        `edit:add-var docs~ { cd ~/'My Documents' }`, to load with
+        `eval (dalia aliases --shell elvish | slurp)` in rc.elv. Tcsh aliases have no `=`, e.g.
+        `alias docs 'cd ~/"My Documents"'`. Cmd aliases are doskey macros that run `cd /d`, written
+        as a file for `doskey /macrofile=`, e.g. `docs=cd /d "C:\Users\me\Documents"`. Aliases
+        whose names aren't plain ASCII are left out of it with a warning, and --with-vars can't be
+        used with it.
+
+    --no-shadow-check
+        Stops warning about aliases named after common commands and shell builtins, like `cd` or
+        `ls`, which hide them once the aliases are sourced.
+
+    --output-path <file>
+        Writes the aliases to the given file instead of stdout, replacing it all at once so that a
+        shell sourcing it never sees half of it, e.g. `--output-path ~/.dalia_aliases`.
+
+    --config <file>, -
+        Reads the configuration from the given file instead of the usual one, or from standard
+        input when it's `-`, e.g. `cat fragments/*.conf | dalia aliases -`. Relative paths in a
+        configuration from standard input are resolved against the current directory unless
+        --base is given. Only the aliases from the usual configuration file are cached.
+
+    --no-cache
+        Generates the aliases again even if the configuration file, the directories its `[*]` and
+        `[**]` lines read, the paths its `|` lines check for, and the environment variables those
+        use haven't changed since they were last generated with the same flags and version of
+        dalia, which reuses the aliases cached in aliases.cache next to the configuration file.
 
 Examples:
     Simple path
-    /some/path => alias path='cd /some/path'
+    /some/path => alias path='cd "/some/path"'
     
     Custom name
-    [my-path]/some/path => alias my-path='cd /some/path'
-    [MyPath]/some/path => alias MyPath='cd /some/path'
+    [my-path]/some/path => alias my-path='cd "/some/path"'
+    [MyPath]/some/path => alias MyPath='cd "/some/path"'
     
     Directory Expansion
     [*]/some/path =>
-        alias one='cd /some/path/one'
-        alias two='cd /some/path/two'
-        alias three='cd /some/path/three'
+        alias one='cd "/some/path/one"'
+        alias two='cd "/some/path/two"'
+        alias three='cd "/some/path/three"'
         
     when /some/path has contents /one, /two, file.txt, and /three."#;
 
-const VERSION_USAGE: &str = r#"Usage: dalia version
+const WATCH_USAGE: &str = r#"Usage: dalia watch [--preserve-case] [--strict] [--include-hidden] [--include-unreadable]
+       [--no-follow-symlinks] [--disambiguate] [--canonicalize] [--base <dir>] [--max-glob <n>]
+       [--file-command <cmd>] [--with-unalias] [--with-vars] [--sort[=path]] [--prefix <text>]
+       [--suffix <text>] [--shell <name>] [--no-shadow-check]
+
+Description:
+    Watch generates the same aliases as the aliases command, then keeps running and generates
+    them again every time the configuration file changes. Each set of aliases is prefixed with
+    `unalias -a &&` so that aliases removed from the configuration file are removed from the shell
+    too. Errors in the changed configuration file are reported and the previous aliases are kept,
+    and nothing is printed when a change leaves the aliases the same.
+    Press Ctrl-C to stop watching.
+
+    Watch accepts the same flags as the aliases command, except --output-path, --no-cache, and
+    --config.
+
+Examples:
+    $ dalia watch | source /dev/stdin"#;
+
+const LIST_USAGE: &str = r#"Usage: dalia list [--names-only | --paths-only]
+
+Description:
+    List prints every alias configured in DALIA_CONFIG_PATH/config, sorted by name, next to the
+    path it points at and its description, if it has one.
+
+Flags:
+    --names-only: Prints just the alias names, one per line, for scripts and shell completions
+    --paths-only: Prints just the paths, one per line, in the same order
+
+Examples:
+    $ dalia list
+    api   /Users/johnappleseed/code/api   the public API
+    docs  /Users/johnappleseed/Documents
+
+    $ dalia list --names-only
+    api
+    docs"#;
+
+const STAT_USAGE: &str = r#"Usage: dalia stat
+
+Description:
+    Stat prints statistics about the aliases configured in DALIA_CONFIG_PATH/config: how many
+    aliases there are in total, how many were named explicitly, derived from a path, or expanded
+    from a `[*]` line, and how many point at directories or files that don't exist. It also prints the
+    location of the configuration file and when it was last modified.
+
+Examples:
+    $ dalia stat
+    config:      /Users/johnappleseed/.config/dalia/config
+    modified:    2021-03-14 15:09:26 UTC
+    aliases:     5
+    explicit:    2
+    derived:     1
+    glob:        2
+    broken:      0"#;
+
+const LINT_USAGE: &str = r#"Usage: dalia lint [--fail-on-warning]
+
+Description:
+    Lint reports style problems in DALIA_CONFIG_PATH/config that don't stop aliases from being
+    generated, each at one of three levels:
+
+    info:    alias names with uppercase letters, unlike the lowercase names derived from paths
+    warning: aliases that hide a common command like `ls` or `cd`, and aliases for a path without
+             a directory name, like `/`
+    error:   aliases for a path that's still relative once it's resolved, e.g. because
+             DALIA_CONFIG_PATH is relative, which only works from one directory
+
+    Lint fails when it reports an error.
+
+Flags:
+    --fail-on-warning: Fails when lint reports a warning too
+
+Examples:
+    $ dalia lint
+    warning: alias 'ls' hides the ls command on line 3
+    info: alias 'Docs' has uppercase letters on line 7"#;
+
+const FORMAT_USAGE: &str = r#"Usage: dalia format [--check | --in-place]
+
+Description:
+    Format prints DALIA_CONFIG_PATH/config in a consistent style: one entry per line without
+    blank lines or whitespace around it, a blank line before each section, and the aliases
+    sorted by name. Lines that affect the ones after them, like `[section: name]`,
+    `$name = value`, and `@case`, stay where they are, and `[*]` and `!name` lines keep their
+    order after the aliases around them. Format fails instead of changing which aliases the
+    configuration creates.
+
+Flags:
+    --check: Prints nothing, and fails when the configuration file isn't formatted yet
+    --in-place: Writes the formatted configuration back to the configuration file
+
+Examples:
+    $ dalia format --in-place"#;
+
+const CONFIG_USAGE: &str = r#"Usage: dalia config <path|edit>
 
 Description:
-    Version prints the current semantic version of the dalia executable."#;
+    Config path prints the absolute location of the configuration file that dalia reads, after
+    expanding DALIA_CONFIG_PATH and DALIA_CONFIG_FILE, whether or not the file exists yet.
 
-#[derive(Debug)]
-struct Configuration<'a> {
+    Config edit opens the configuration file with $EDITOR (or vi), creating it and the directory
+    that holds it first when they don't exist.
+
+Examples:
+    $ dalia config path
+    /home/me/.config/dalia/config
+
+    $ dalia config edit"#;
+
+const INIT_USAGE: &str = r#"Usage: dalia init <shell>
+
+Description:
+    Init prints the line to add to the given shell's startup file so that every new session loads
+    dalia's aliases, written in that shell's syntax. The supported shells are bash, zsh, fish,
+    posix, and elvish.
+
+Examples:
+    bash
+    $ dalia init bash >> ~/.bashrc
+
+    zsh
+    $ dalia init zsh >> ~/.zshrc
+
+    fish
+    $ dalia init fish >> ~/.config/fish/config.fish
+
+    elvish
+    $ dalia init elvish >> ~/.config/elvish/rc.elv"#;
+
+const COMPLETIONS_USAGE: &str = r#"Usage: dalia completions <shell>
+
+Description:
+    Completions prints a script that teaches the given shell how to complete dalia's commands
+    and their arguments. The supported shells are bash, zsh, and fish.
+
+Examples:
+    bash
+    $ eval "$(dalia completions bash)"
+
+    zsh
+    $ eval "$(dalia completions zsh)"
+
+    fish
+    $ dalia completions fish | source"#;
+
+const VERSION_USAGE: &str = r#"Usage: dalia version [--json]
+
+Description:
+    Version prints the current semantic version of the dalia executable.
+
+Flags:
+    --json: Prints the version and build profile as a JSON object instead
+
+Examples:
+    $ dalia version --json
+    {"version":"1.2.0","profile":"release"}"#;
+
+/// Configuration is a parsed configuration file along with how its aliases should be written.
+#[derive(Clone, Debug)]
+pub struct Configuration<'a> {
     path: String,
     parser: Parser<'a>,
+    shell: ShellFormat,
 }
 
 impl<'a> Configuration<'a> {
-    fn new() -> Result<Configuration<'a>, &'static str> {
-        let path = env::var(DALIA_CONFIG_ENV_VAR)
-            .unwrap_or_else(|_| shellexpand::tilde(DEFAULT_DALIA_CONFIG_PATH).to_string());
+    /// Returns a builder for a configuration that doesn't depend on the process environment.
+    pub fn builder() -> ConfigurationBuilder {
+        ConfigurationBuilder::default()
+    }
+
+    fn new(options: ParserOptions) -> Result<Configuration<'a>, String> {
+        let path = config_file_path();
+        let options = with_default_base(options, &path);
+        Configuration::from_path(path, options)
+    }
 
-        let path = format!("{}{}{}", path, std::path::MAIN_SEPARATOR, CONFIG_FILE);
-        let contents = fs::read_to_string(&path).unwrap_or_default();
-        if contents.is_empty() {
-            return Err("configuration file is empty; add a few paths to $DALIA_CONFIG_PATH/config and try again.");
+    fn from_path(path: String, options: ParserOptions) -> Result<Configuration<'a>, String> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(missing_config(&path)),
+            Err(e) => {
+                return Err(format!(
+                    "couldn't read the configuration file {}: {}",
+                    path, e
+                ))
+            }
+        };
+        Configuration::from_contents(path, &contents, options)
+    }
+
+    /// Reads a configuration from `r`, like standard input when it's given as `-`, naming it
+    /// `<stdin>` in errors and warnings.
+    fn from_reader(r: impl io::Read, options: ParserOptions) -> Result<Configuration<'a>, String> {
+        let path = STDIN_NAME.to_string();
+        match io::read_to_string(r) {
+            Ok(contents) => Configuration::from_contents(path, &contents, options),
+            Err(e) => Err(format!(
+                "couldn't read the configuration from {}: {}",
+                path, e
+            )),
         }
+    }
 
-        let parser = Parser::new(&contents);
+    fn from_contents(
+        path: String,
+        contents: &str,
+        options: ParserOptions,
+    ) -> Result<Configuration<'a>, String> {
+        let parser = match Parser::try_new_with_options(contents, options) {
+            Ok(parser) => parser,
+            Err(DaliaError::EmptyInput) => {
+                return Err(format!(
+                    "configuration file {} is empty; add a few paths to it and try again.",
+                    path
+                ))
+            }
+            Err(e) => return Err(DaliaError::InConfig(PathBuf::from(&path), Box::new(e)).into()),
+        };
+
+        Ok(Configuration {
+            path,
+            parser,
+            shell: ShellFormat::Bash,
+        })
+    }
+
+    /// The location of the configuration file.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The shell the aliases are written for.
+    pub fn shell(&self) -> ShellFormat {
+        self.shell
+    }
 
-        Ok(Configuration { path, parser })
+    /// Writes an alias definition for every configured alias to `out`.
+    pub fn write_aliases(&self, out: &mut impl Write) -> Result<(), String> {
+        let output = AliasOutput {
+            shell: self.shell,
+            ..Default::default()
+        };
+        generate_aliases(self, &output, out)
     }
 
-    fn aliases(&self) -> HashMap<String, String> {
+    pub fn aliases(&self) -> HashMap<String, String> {
         self.parser.aliases()
     }
 
-    fn process_input(&mut self) -> Result<(), String> {
-        self.parser.process_input()
+    pub fn sources(&self) -> HashMap<String, AliasSource> {
+        self.parser.sources()
+    }
+
+    pub fn entries(&self) -> Vec<AliasEntry> {
+        self.parser.entries()
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        self.parser.warnings()
+    }
+
+    pub fn typed_warnings(&self) -> &[DaliaWarning] {
+        self.parser.typed_warnings()
+    }
+
+    /// Returns the version from the configuration's `version = N` header, or None without one,
+    /// which means version 1.
+    pub fn format_version(&self) -> Option<u32> {
+        self.parser.format_version()
+    }
+
+    /// Parses the configuration, naming the configuration file in any error along with the
+    /// position of syntax errors.
+    fn process_input(&mut self) -> Result<(), DaliaError> {
+        self.parser
+            .parse()
+            .and_then(|_| self.parser.resolve())
+            .map_err(|e| {
+                let e = match self.parser.error_position() {
+                    Some((line, column)) => DaliaError::Located(line, column, Box::new(e)),
+                    None => e,
+                };
+                DaliaError::InConfig(PathBuf::from(&self.path), Box::new(e))
+            })
+    }
+
+    /// Parses the configuration like process_input, describing any error for the terminal with
+    /// the line of the configuration it was found on.
+    fn process_input_for_terminal(&mut self) -> Result<(), String> {
+        self.process_input()
+            .map_err(|e| report::render(&e, self.parser.source(), report::color_enabled()))
+    }
+}
+
+/// Two configurations are equal when they create the same aliases, running the same commands
+/// with the same paths, wherever those aliases come from in the configuration file.
+impl PartialEq for Configuration<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        // Entries are sorted by alias, so the order of the lines doesn't matter.
+        let (ours, theirs) = (self.entries(), other.entries());
+        ours.iter()
+            .map(alias_content)
+            .eq(theirs.iter().map(alias_content))
+    }
+}
+
+impl Eq for Configuration<'_> {}
+
+impl Hash for Configuration<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Combining each alias's own hash with XOR gives the same result in any order.
+        let combined = self.entries().iter().fold(0, |combined, entry| {
+            let mut hasher = DefaultHasher::new();
+            alias_content(entry).hash(&mut hasher);
+            combined ^ hasher.finish()
+        });
+        state.write_u64(combined);
+    }
+}
+
+/// Returns the parts of an entry that end up in its generated alias.
+fn alias_content(entry: &AliasEntry) -> (&str, &str, &str) {
+    (&entry.alias, &entry.path, &entry.command)
+}
+
+/// ConfigurationBuilder creates a Configuration from explicit inputs instead of the environment
+/// variables the dalia command reads.
+#[derive(Clone, Debug)]
+pub struct ConfigurationBuilder {
+    config_path: Option<PathBuf>,
+    options: ParserOptions,
+    shell: ShellFormat,
+}
+
+impl Default for ConfigurationBuilder {
+    fn default() -> Self {
+        Self {
+            config_path: None,
+            options: ParserOptions::default(),
+            shell: ShellFormat::Bash,
+        }
+    }
+}
+
+impl ConfigurationBuilder {
+    /// Sets the configuration file to read. Without one, the file is found the same way the dalia
+    /// command finds it.
+    pub fn config_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.config_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn options(mut self, options: ParserOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn shell(mut self, shell: ShellFormat) -> Self {
+        self.shell = shell;
+        self
+    }
+
+    /// Reads and parses the configuration file.
+    pub fn build<'a>(self) -> Result<Configuration<'a>, DaliaError> {
+        let path = match self.config_path {
+            Some(path) => path.display().to_string(),
+            None => config_file_path(),
+        };
+        let mut config = Configuration::from_path(path, self.options)
+            .map_err(|e| DaliaError::ParseError(e.to_string()))?;
+        config.shell = self.shell;
+        config.process_input()?;
+        Ok(config)
+    }
+}
+
+/// Returns the location of the configuration file. DALIA_CONFIG_FILE is either a full path, which
+/// takes precedence over DALIA_CONFIG_PATH, or a bare filename that replaces `config` inside the
+/// DALIA_CONFIG_PATH directory.
+fn config_file_path() -> String {
+    let file = env::var(DALIA_CONFIG_FILE_ENV_VAR).ok();
+    if let Some(file) = &file {
+        if file.chars().any(std::path::is_separator) || file.starts_with('~') {
+            return shellexpand::tilde(file).to_string();
+        }
+    }
+    let path = env::var(DALIA_CONFIG_ENV_VAR)
+        .map(|path| shellexpand::tilde(&path).to_string())
+        .unwrap_or_else(|_| default_config_dir().display().to_string());
+    let name = file
+        .filter(|f| !f.is_empty())
+        .unwrap_or_else(|| CONFIG_FILE.to_string());
+    format!("{}{}{}", path, std::path::MAIN_SEPARATOR, name)
+}
+
+/// Returns the location of the configuration file as an absolute path, resolving a relative
+/// DALIA_CONFIG_PATH or DALIA_CONFIG_FILE against the current directory.
+fn absolute_config_file_path() -> Result<PathBuf, String> {
+    let path = PathBuf::from(config_file_path());
+    if path.is_absolute() {
+        return Ok(path);
+    }
+    env::current_dir()
+        .map(|dir| dir.join(path))
+        .map_err(|e| e.to_string())
+}
+
+/// Opens the configuration file with $EDITOR, falling back to vi, after creating it if it's
+/// missing.
+fn edit_config() -> Result<(), String> {
+    let path = absolute_config_file_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    if !path.exists() {
+        fs::File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let editor = env::var("EDITOR")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .map_err(|e| format!("couldn't run {}: {}", program, e))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", program, status));
+    }
+    Ok(())
+}
+
+/// FormatMode is what the format command does with the formatted configuration.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FormatMode {
+    /// Prints it.
+    Print,
+    /// Fails if it's different from the configuration file.
+    Check,
+    /// Writes it back to the configuration file.
+    InPlace,
+}
+
+/// Formats the configuration file at `path` as the format command does, after checking that the
+/// formatted configuration creates the same aliases as the file.
+fn format_config_file(path: &str, mode: FormatMode, out: &mut impl Write) -> Result<(), String> {
+    let options = with_default_base(parser_options(&[])?, path);
+    let mut config = Configuration::from_path(path.to_string(), options.clone())?;
+    config.process_input_for_terminal()?;
+    let contents = config.parser.source();
+    let formatted = format_config(contents);
+
+    let mut reformatted = Configuration::from_contents(path.to_string(), &formatted, options)?;
+    if reformatted.process_input().is_err() || reformatted != config {
+        return Err(format!(
+            "can't format {} without changing its aliases",
+            path
+        ));
+    }
+    match mode {
+        FormatMode::Print => out
+            .write_all(formatted.as_bytes())
+            .map_err(|e| e.to_string()),
+        FormatMode::Check if formatted == contents => Ok(()),
+        FormatMode::Check => Err(format!(
+            "{} isn't formatted; run `dalia format --in-place` to format it",
+            path
+        )),
+        FormatMode::InPlace if formatted == contents => Ok(()),
+        FormatMode::InPlace => write_atomically(Path::new(path), &formatted),
+    }
+}
+
+/// Prints every token the lexer finds in the configuration file at `path`, one per line.
+fn print_tokens(path: &str, out: &mut impl Write) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let c = match contents.chars().next() {
+        Some(c) => c,
+        None => return Err(format!("{} is empty", path)),
+    };
+    let tokens = Lexer::new(&contents, 0, c)
+        .all_tokens()
+        .map_err(|e| e.to_string())?;
+    for token in tokens {
+        writeln!(out, "{}", token).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Explains that the configuration file at `path` doesn't exist, and whether its directory
+/// doesn't either.
+fn missing_config(path: &str) -> String {
+    match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => format!(
+            "configuration directory {} doesn't exist; create it with a file named {} that lists a few paths, or run `dalia config edit`.",
+            dir.display(),
+            Path::new(path).file_name().map_or(CONFIG_FILE.into(), |n| n.to_string_lossy())
+        ),
+        _ => format!(
+            "configuration file {} doesn't exist; create it with a few paths, or run `dalia config edit`.",
+            path
+        ),
+    }
+}
+
+/// Returns the directory dalia looks in when DALIA_CONFIG_PATH isn't set: `dalia` inside
+/// `$XDG_CONFIG_HOME` (or `~/.config`), unless only the older `~/.dalia` directory exists.
+fn default_config_dir() -> PathBuf {
+    let xdg_config_home = env::var(XDG_CONFIG_HOME_ENV_VAR)
+        .ok()
+        .filter(|home| !home.is_empty())
+        .unwrap_or_else(|| DEFAULT_XDG_CONFIG_HOME.to_string());
+    let xdg = PathBuf::from(shellexpand::tilde(&xdg_config_home).as_ref()).join("dalia");
+    let legacy = PathBuf::from(shellexpand::tilde(LEGACY_DALIA_CONFIG_PATH).as_ref());
+    if !xdg.is_dir() && legacy.is_dir() {
+        return legacy;
+    }
+    xdg
+}
+
+/// Statistics summarizes a processed configuration for the `stat` command.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct Statistics {
+    total: usize,
+    explicit: usize,
+    derived: usize,
+    glob: usize,
+    broken: usize,
+}
+
+impl Statistics {
+    fn new(config: &Configuration) -> Self {
+        let aliases = config.aliases();
+        let mut stats = Statistics {
+            total: aliases.len(),
+            ..Default::default()
+        };
+        for source in config.sources().values() {
+            match source {
+                AliasSource::Explicit => stats.explicit += 1,
+                AliasSource::PathDerived => stats.derived += 1,
+                AliasSource::GlobExpanded => stats.glob += 1,
+            }
+        }
+        stats.broken = config
+            .entries()
+            .iter()
+            .filter(|entry| {
+                let path = shellexpand::tilde(&entry.path);
+                let path = Path::new(path.as_ref());
+                match entry.kind {
+                    EntryKind::Directory => !path.is_dir(),
+                    EntryKind::File => !path.is_file(),
+                }
+            })
+            .count();
+        stats
+    }
+}
+
+/// LintLevel is how serious a problem reported by the `lint` command is.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum LintLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LintLevel::Info => "info",
+            LintLevel::Warning => "warning",
+            LintLevel::Error => "error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// LintWarning is a single style problem with one of the configured aliases.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LintWarning {
+    pub level: LintLevel,
+    pub alias: String,
+    pub message: String,
+    /// The configuration line that created the alias.
+    pub line: usize,
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} on line {}", self.level, self.message, self.line)
+    }
+}
+
+/// Verbosity controls whether informational messages are written to standard error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+}
+
+impl Verbosity {
+    /// Separates the global verbosity flags from the rest of the arguments.
+    fn from_args(args: &[String]) -> (Verbosity, Vec<String>) {
+        let mut verbosity = Verbosity::Normal;
+        let mut rest = Vec::with_capacity(args.len());
+        for arg in args {
+            match arg.as_str() {
+                "-q" | "--quiet" => verbosity = Verbosity::Quiet,
+                _ => rest.push(arg.to_owned()),
+            }
+        }
+        (verbosity, rest)
+    }
+}
+
+/// AliasOutput controls how generated aliases are written: the shell syntax they use, a prefix
+/// and suffix added to every alias name without changing the parsed configuration, whether
+/// each alias is removed before it's defined, whether a variable with each path is exported
+/// too, the order they're written in, the file they're written to instead of stdout, and whether
+/// aliases that hide common commands are warned about.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct AliasOutput {
+    prefix: String,
+    suffix: String,
+    shell: ShellFormat,
+    unalias: bool,
+    vars: bool,
+    sort: Option<SortKey>,
+    path: Option<PathBuf>,
+    shadow_check: bool,
+}
+
+/// SortKey is what `--sort` orders the generated aliases by.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SortKey {
+    /// The alias name, ignoring case.
+    Alias,
+    /// The path the alias points at, and then the alias name.
+    Path,
+}
+
+impl Default for AliasOutput {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            suffix: String::new(),
+            shell: ShellFormat::Bash,
+            unalias: false,
+            vars: false,
+            sort: None,
+            path: None,
+            shadow_check: true,
+        }
+    }
+}
+
+impl AliasOutput {
+    /// Separates the `--prefix <name>`, `--suffix <name>`, `--shell <name>`, `--with-unalias`,
+    /// `--with-vars`, `--sort[=alias|path]`, `--output-path <file>`, and `--no-shadow-check` flags
+    /// from the rest of the arguments.
+    fn from_args(args: &[String]) -> Result<(AliasOutput, Vec<String>), String> {
+        let mut output = AliasOutput::default();
+        let mut rest = Vec::with_capacity(args.len());
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--with-unalias" {
+                output.unalias = true;
+                continue;
+            }
+            if arg == "--with-vars" {
+                output.vars = true;
+                continue;
+            }
+            if arg == "--no-shadow-check" {
+                output.shadow_check = false;
+                continue;
+            }
+            if arg == "--sort" || arg.starts_with("--sort=") {
+                output.sort = match arg.as_str() {
+                    "--sort" | "--sort=alias" => Some(SortKey::Alias),
+                    "--sort=path" => Some(SortKey::Path),
+                    _ => {
+                        return Err(format!(
+                            "invalid value for --sort: {}; expected alias or path",
+                            &arg["--sort=".len()..]
+                        ))
+                    }
+                };
+                continue;
+            }
+            if !matches!(
+                arg.as_str(),
+                "--prefix" | "--suffix" | "--shell" | "--output-path"
+            ) {
+                rest.push(arg.to_owned());
+                continue;
+            }
+            let value = match args.next() {
+                Some(v) => v,
+                None => return Err(format!("missing value for {}", arg)),
+            };
+            match arg.as_str() {
+                "--shell" => output.shell = value.parse()?,
+                "--output-path" if value.is_empty() => {
+                    return Err(format!("invalid value for {}: {}", arg, value))
+                }
+                "--output-path" => {
+                    output.path = Some(PathBuf::from(shellexpand::tilde(value).as_ref()))
+                }
+                _ if value.is_empty() || !value.chars().all(is_alias_char) => {
+                    return Err(format!("invalid value for {}: {}", arg, value))
+                }
+                "--prefix" => output.prefix = value.to_owned(),
+                _ => output.suffix = value.to_owned(),
+            }
+        }
+        if output.vars && output.shell == ShellFormat::Cmd {
+            return Err("--with-vars can't be used with --shell cmd".to_string());
+        }
+        Ok((output, rest))
+    }
+
+    fn apply(&self, alias: &str) -> String {
+        format!("{}{}{}", self.prefix, alias, self.suffix)
     }
 }
 
 pub enum Command {
     Aliases,
+    Watch,
+    List,
+    Stat,
+    Lint,
+    Format,
+    Config,
+    Init,
+    Completions,
     Version,
     Help,
+    /// Prints the lexer's tokens for the configuration file. It's left out of the usage message.
+    Debug,
 }
 
 impl Command {
     pub fn run(args: Vec<String>) -> Result<(), String> {
-        if args.is_empty() || args.len() > 3 {
+        Command::run_with_writer(&args, &mut io::stdout())
+    }
+
+    /// Runs the command named in `args`, writing everything it outputs to `out`.
+    pub fn run_with_writer(args: &[String], out: &mut impl Write) -> Result<(), String> {
+        Command::run_with_writers(args, out, &mut io::stderr())
+    }
+
+    /// Runs the command named in `args`, writing its output to `out` and any warnings to `err`.
+    pub fn run_with_writers(
+        args: &[String],
+        out: &mut impl Write,
+        err: &mut impl Write,
+    ) -> Result<(), String> {
+        let (verbosity, args) = Verbosity::from_args(args);
+        if args.is_empty() {
             return Err("wrong number of arguments provided.".to_string());
         } else if args.len() == 1 {
-            print_usage();
-            return Ok(());
+            return print_usage(out);
         }
 
-        let cmd = args.get(1).unwrap();
+        let cmd = &args[1];
+        let rest = &args[2..];
         match Command::from_str(cmd) {
-            Some(Command::Aliases) => generate_aliases(),
+            Some(Command::Aliases) => {
+                let (output, flags) = AliasOutput::from_args(rest)?;
+                let (source, flags) = config_source(&flags)?;
+                let (no_cache, flags): (Vec<String>, Vec<String>) =
+                    flags.into_iter().partition(|flag| flag == "--no-cache");
+                let options = parser_options(&flags)?;
+                let key: Vec<String> = rest
+                    .iter()
+                    .filter(|a| *a != "--no-cache")
+                    .cloned()
+                    .collect();
+                // Only the usual configuration file is cached, so that a file given with --config,
+                // which could be one of several in the same directory, doesn't leave a cache next to
+                // it. Standard input couldn't be checked for changes anyway.
+                let stdin = source.as_deref() == Some(STDIN);
+                let cached = source.is_none();
+                let path = source.unwrap_or_else(config_file_path);
+                let cache = Cache::new(Path::new(&path), &key);
+                if cached && no_cache.is_empty() {
+                    if let Some(aliases) = cache.load() {
+                        return write_aliases(&aliases, &output, out);
+                    }
+                }
+
+                let mut config = if stdin {
+                    let mut options = options;
+                    if options.base.is_none() {
+                        options.base = env::current_dir().ok();
+                    }
+                    Configuration::from_reader(io::stdin().lock(), options)?
+                } else {
+                    let options = with_default_base(options, &path);
+                    Configuration::from_path(path, options)?
+                };
+                config.process_input_for_terminal()?;
+                report_warnings(&config, verbosity, err)?;
+                let names_warned = report_name_warnings(&config, &output, verbosity, err)?;
+                let aliases = format_aliases(&config, &output);
+                // Aliases that came with warnings aren't cached, so the warnings are repeated
+                // until they're fixed. The cache only saves time, so failing to write it is fine.
+                if cached && config.warnings().is_empty() && !names_warned {
+                    let mut paths = config.parser.glob_paths();
+                    paths.extend(config.parser.fallback_paths());
+                    let mut variables = config.parser.environment_variables();
+                    variables.push(DALIA_MAX_ALIASES_ENV_VAR.to_string());
+                    let _ = cache.store(&paths, &variables, &aliases);
+                }
+                write_aliases(&aliases, &output, out)
+            }
+            Some(Command::Watch) => {
+                let (output, flags) = AliasOutput::from_args(rest)?;
+                if output.path.is_some() {
+                    return Err("--output-path can't be used with watch".to_string());
+                }
+                watch(&parser_options(&flags)?, &output, verbosity, out, err)
+            }
+            Some(Command::List) => {
+                expect_arguments(rest, 1)?;
+                let format = match rest.first().map(String::as_str) {
+                    None => ListFormat::Table,
+                    Some("--names-only") => ListFormat::Names,
+                    Some("--paths-only") => ListFormat::Paths,
+                    Some(flag) => return Err(format!("unknown flag: {}", flag)),
+                };
+                let mut config = Configuration::new(parser_options(&[])?)?;
+                config.process_input_for_terminal()?;
+                report_warnings(&config, verbosity, err)?;
+                print_list(&config, format, out)
+            }
+            Some(Command::Stat) => {
+                expect_arguments(rest, 0)?;
+                print_stat(out)
+            }
+            Some(Command::Lint) => {
+                expect_arguments(rest, 1)?;
+                let fail_level = match rest.first().map(String::as_str) {
+                    None => LintLevel::Error,
+                    Some("--fail-on-warning") => LintLevel::Warning,
+                    Some(flag) => return Err(format!("unknown flag: {}", flag)),
+                };
+                let mut config = Configuration::new(parser_options(&[])?)?;
+                config.process_input_for_terminal()?;
+                report_warnings(&config, verbosity, err)?;
+                print_lint(&lint_config(&config), fail_level, out)
+            }
+            Some(Command::Format) => {
+                expect_arguments(rest, 1)?;
+                let mode = match rest.first().map(String::as_str) {
+                    None => FormatMode::Print,
+                    Some("--check") => FormatMode::Check,
+                    Some("--in-place") => FormatMode::InPlace,
+                    Some(flag) => return Err(format!("unknown flag: {}", flag)),
+                };
+                format_config_file(&config_file_path(), mode, out)
+            }
+            Some(Command::Config) => {
+                expect_arguments(rest, 1)?;
+                match rest.first().map(String::as_str) {
+                    Some("path") => writeln!(out, "{}", absolute_config_file_path()?.display())
+                        .map_err(|e| e.to_string()),
+                    Some("edit") => edit_config(),
+                    Some(other) => Err(format!(
+                        "unknown config command: {}; expected path or edit.",
+                        other
+                    )),
+                    None => Err("missing config command; expected path or edit.".to_string()),
+                }
+            }
+            Some(Command::Init) => {
+                expect_arguments(rest, 1)?;
+                match rest.first() {
+                    Some(shell) => {
+                        write!(out, "{}", init_script(shell.parse()?)?).map_err(|e| e.to_string())
+                    }
+                    None => Err(
+                        "missing shell; expected one of bash, zsh, fish, posix, or elvish."
+                            .to_string(),
+                    ),
+                }
+            }
+            Some(Command::Completions) => {
+                expect_arguments(rest, 1)?;
+                match rest.first() {
+                    Some(shell) => write!(out, "{}", completion_script(shell.parse()?)?)
+                        .map_err(|e| e.to_string()),
+                    None => Err("missing shell; expected one of bash, zsh, or fish.".to_string()),
+                }
+            }
             Some(Command::Version) => {
-                print_version();
-                Ok(())
+                expect_arguments(rest, 1)?;
+                match rest.first().map(String::as_str) {
+                    None => print_version(out),
+                    Some("--json") => print_version_json(out),
+                    Some(flag) if flag.starts_with('-') => Err(format!("unknown flag: {}", flag)),
+                    Some(_) => expect_arguments(rest, 0),
+                }
             }
             Some(Command::Help) => {
-                if args.len() == 3 {
-                    return print_help(args[2].as_str());
-                } else {
-                    print_usage();
+                expect_arguments(rest, 1)?;
+                match rest.first() {
+                    Some(command) => print_help(command, out),
+                    None => print_usage(out),
                 }
-                Ok(())
             }
-            None => {
-                return Err(format!("unknown command: {}", cmd));
+            Some(Command::Debug) => {
+                expect_arguments(rest, 1)?;
+                match rest.first().map(String::as_str) {
+                    Some("--tokens") => print_tokens(&config_file_path(), out),
+                    Some(flag) => Err(format!("unknown flag: {}", flag)),
+                    None => Err("missing flag; expected --tokens.".to_string()),
+                }
             }
+            None => Err(format!("unknown command: {}", cmd)),
         }
     }
 
     fn from_str(value: &str) -> Option<Command> {
         match value {
             "aliases" => Some(Command::Aliases),
+            "watch" => Some(Command::Watch),
+            "list" => Some(Command::List),
+            "stat" => Some(Command::Stat),
+            "lint" => Some(Command::Lint),
+            "format" => Some(Command::Format),
+            "config" => Some(Command::Config),
+            "init" => Some(Command::Init),
+            "completions" => Some(Command::Completions),
             "version" => Some(Command::Version),
             "help" => Some(Command::Help),
+            "debug" => Some(Command::Debug),
             _ => None,
         }
     }
 }
 
-fn print_help(value: &str) -> Result<(), String> {
-    match Command::from_str(value) {
-        Some(Command::Aliases) => print_alias_usage(),
-        Some(Command::Version) => print_version_usage(),
-        Some(Command::Help) => print_usage(),
-        None => {
-            return Err(format!("unknown command: {}", value));
-        }
+/// Fails when more than `max` arguments follow a command.
+fn expect_arguments(args: &[String], max: usize) -> Result<(), String> {
+    if args.len() > max {
+        return Err("wrong number of arguments provided.".to_string());
     }
     Ok(())
 }
 
-fn generate_aliases() -> Result<(), String> {
-    let mut config = Configuration::new()?;
-    config.process_input()?;
-
-    let aliases: Vec<String> = config
-        .aliases()
-        .iter()
-        .map(|(alias, path)| format!("alias {}='cd {}'\n", alias, path))
-        .collect();
-
-    aliases.iter().for_each(|alias| print!("{}", alias));
-
-    Ok(())
-}
-
-fn print_usage() {
-    println!("{}", USAGE)
-}
-
-fn print_alias_usage() {
-    println!("{}", ALIASES_USAGE)
+fn print_help(value: &str, out: &mut impl Write) -> Result<(), String> {
+    match Command::from_str(value) {
+        Some(Command::Aliases) => print_alias_usage(out),
+        Some(Command::Watch) => print_watch_usage(out),
+        Some(Command::List) => print_list_usage(out),
+        Some(Command::Stat) => print_stat_usage(out),
+        Some(Command::Lint) => print_lint_usage(out),
+        Some(Command::Format) => print_format_usage(out),
+        Some(Command::Config) => print_config_usage(out),
+        Some(Command::Init) => print_init_usage(out),
+        Some(Command::Completions) => print_completions_usage(out),
+        Some(Command::Version) => print_version_usage(out),
+        Some(Command::Help) => print_usage(out),
+        Some(Command::Debug) | None => Err(format!("unknown command: {}", value)),
+    }
 }
 
-fn print_version_usage() {
-    println!("{}", VERSION_USAGE)
+/// Parses the value of DALIA_MAX_ALIASES.
+fn parse_max_aliases(value: &str) -> Result<usize, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", DALIA_MAX_ALIASES_ENV_VAR, value))
 }
 
-fn print_version() {
+/// Builds the parser options selected by the given command line flags.
+fn parser_options(flags: &[String]) -> Result<ParserOptions, String> {
+    let mut options = ParserOptions::default();
+    if let Ok(max) = env::var(DALIA_MAX_ALIASES_ENV_VAR) {
+        options.max_aliases = parse_max_aliases(&max)?;
+    }
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--base" => match flags.next() {
+                Some(dir) => options.base = Some(PathBuf::from(shellexpand::tilde(dir).as_ref())),
+                None => return Err(format!("missing value for {}", flag)),
+            },
+            "--max-glob" => match flags.next() {
+                Some(max) => {
+                    options.max_glob_aliases = max
+                        .parse()
+                        .map_err(|_| format!("invalid value for {}: {}", flag, max))?
+                }
+                None => return Err(format!("missing value for {}", flag)),
+            },
+            "--file-command" => match flags.next() {
+                Some(command) => options.file_command = command.to_owned(),
+                None => return Err(format!("missing value for {}", flag)),
+            },
+            "--preserve-case" => options.preserve_case = true,
+            "--strict" => options.strict = true,
+            "--include-hidden" => options.include_hidden = true,
+            "--include-unreadable" => options.include_unreadable = true,
+            "--no-follow-symlinks" => options.follow_symlinks = false,
+            "--disambiguate" => options.disambiguate = true,
+            "--canonicalize" => options.canonicalize = true,
+            // An unexpanded `[*]` line isn't an alias, so there'd be nothing to write for it.
+            "--no-expand-glob" => {
+                return Err(
+                    "--no-expand-glob can't be used when generating aliases; it's only available \
+                     to library users through ParserOptions"
+                        .to_string(),
+                )
+            }
+            _ => return Err(format!("unknown flag: {}", flag)),
+        }
+    }
+    Ok(options)
+}
+
+/// Separates `--config <file>`, or `-` on its own, from the rest of the flags, returning the file
+/// to read the configuration from, or `-` for standard input.
+fn config_source(flags: &[String]) -> Result<(Option<String>, Vec<String>), String> {
+    let mut source = None;
+    let mut rest = Vec::with_capacity(flags.len());
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            STDIN => source = Some(STDIN.to_string()),
+            "--config" => match flags.next() {
+                Some(file) => source = Some(shellexpand::tilde(file).to_string()),
+                None => return Err(format!("missing value for {}", flag)),
+            },
+            _ => rest.push(flag.to_owned()),
+        }
+    }
+    Ok((source, rest))
+}
+
+/// Resolves relative paths against the directory containing the configuration file, unless the
+/// options already name a base directory.
+fn with_default_base(mut options: ParserOptions, config_path: &str) -> ParserOptions {
+    if options.base.is_none() {
+        options.base = Path::new(config_path).parent().map(Path::to_path_buf);
+    }
+    options
+}
+
+/// Writes the configuration's warnings to `err` unless they've been silenced.
+fn report_warnings(
+    config: &Configuration,
+    verbosity: Verbosity,
+    err: &mut impl Write,
+) -> Result<(), String> {
+    if verbosity == Verbosity::Quiet {
+        return Ok(());
+    }
+    for warning in config.warnings() {
+        writeln!(err, "dalia: {}: warning: {}", config.path, warning).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Writes a warning to `err` for every alias whose name can't be called in the shell `output`
+/// is for, or that hides a common command unless `output` skips that check, unless warnings
+/// have been silenced, and reports whether there were any.
+fn report_name_warnings(
+    config: &Configuration,
+    output: &AliasOutput,
+    verbosity: Verbosity,
+    err: &mut impl Write,
+) -> Result<bool, String> {
+    let mut warnings = Vec::new();
+    for entry in config.entries() {
+        let alias = output.apply(&entry.alias);
+        if output.shell == ShellFormat::Powershell && !is_powershell_function_name(&alias) {
+            warnings.push(format!("{} isn't a valid PowerShell function name", alias));
+        }
+        if output.shell == ShellFormat::Cmd && !is_doskey_macro_name(&alias) {
+            warnings.push(format!(
+                "skipping {}: doskey macro names may only contain ASCII letters, digits, '_', \
+                 '-', and '.'",
+                alias
+            ));
+        }
+        if output.shadow_check && SHADOWED_COMMANDS.contains(&alias.as_str()) {
+            warnings.push(format!(
+                "alias '{}' on line {} hides the {} command; consider renaming it",
+                alias, entry.line, alias
+            ));
+        }
+    }
+    if output.vars {
+        let owners = variable_owners(config, output);
+        for entry in config.entries() {
+            let alias = output.apply(&entry.alias);
+            let name = variable_name(&alias);
+            if owners[&name] != alias {
+                warnings.push(format!(
+                    "aliases '{}' and '{}' would both export {}; only exporting it for '{}'",
+                    owners[&name], alias, name, owners[&name]
+                ));
+            }
+        }
+    }
+    if verbosity != Verbosity::Quiet {
+        for warning in &warnings {
+            writeln!(err, "dalia: {}: warning: {}", config.path, warning)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(!warnings.is_empty())
+}
+
+/// Reports whether PowerShell reads `name` as a command when it's typed, which takes a letter or
+/// `_` first, since names like `1kb` read as numbers and ones like `.api` as dot-sourcing.
+fn is_powershell_function_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Reports whether `name` can be a doskey macro name. Macro files are read in the console's code
+/// page, so only ASCII names are safe.
+fn is_doskey_macro_name(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn generate_aliases(
+    config: &Configuration,
+    output: &AliasOutput,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    write_aliases(&format_aliases(config, output), output, out)
+}
+
+/// Formats every alias in the configuration the way `output` asks for.
+fn format_aliases(config: &Configuration, output: &AliasOutput) -> String {
+    let mut entries = config.entries();
+    match output.sort {
+        Some(SortKey::Alias) => entries.sort_by_key(|e| e.alias.to_lowercase()),
+        Some(SortKey::Path) => entries.sort_by(|a, b| a.path.cmp(&b.path)),
+        None => {}
+    }
+    if output.shell == ShellFormat::Cmd {
+        entries.retain(|e| is_doskey_macro_name(&output.apply(&e.alias)));
+    }
+    let mut aliases: Vec<String> = Vec::with_capacity(entries.len());
+    if output.unalias {
+        aliases.extend(
+            entries
+                .iter()
+                .filter_map(|e| format_unalias(&output.apply(&e.alias), output.shell)),
+        );
+    }
+    aliases.extend(entries.iter().map(|e| {
+        let command = match output.shell {
+            ShellFormat::Powershell if e.command == "cd" => "Set-Location".to_string(),
+            // A variable alone isn't a command in PowerShell, so the call operator runs it.
+            ShellFormat::Powershell if e.command == DEFAULT_FILE_COMMAND => {
+                "& $env:EDITOR".to_string()
+            }
+            // Nushell reads environment variables from `$env`, and `^` runs the external
+            // command the variable names.
+            ShellFormat::Nushell if e.command == DEFAULT_FILE_COMMAND => "^$env.EDITOR".to_string(),
+            // Elvish only reads environment variables through the `E:` namespace.
+            ShellFormat::Elvish if e.command == DEFAULT_FILE_COMMAND => "$E:EDITOR".to_string(),
+            // Without /d, cmd's cd doesn't change to a directory on another drive.
+            ShellFormat::Cmd if e.command == "cd" => "cd /d".to_string(),
+            ShellFormat::Cmd if e.command == DEFAULT_FILE_COMMAND => "%EDITOR%".to_string(),
+            _ => e.command.clone(),
+        };
+        let quoted = AliasEntry {
+            path: output.shell.quote_path(&e.path),
+            command,
+            ..e.clone()
+        };
+        format_alias(&output.apply(&e.alias), &quoted.body(), output.shell)
+    }));
+    if output.vars {
+        let owners = variable_owners(config, output);
+        aliases.extend(entries.iter().filter_map(|e| {
+            let alias = output.apply(&e.alias);
+            let name = variable_name(&alias);
+            if owners[&name] != alias {
+                return None;
+            }
+            Some(format_variable(
+                &name,
+                &output.shell.quote_path(&e.path),
+                output.shell,
+            ))
+        }));
+    }
+
+    aliases.concat()
+}
+
+/// Writes formatted aliases to the file `output` names, or to `out` when it doesn't name one.
+fn write_aliases(aliases: &str, output: &AliasOutput, out: &mut impl Write) -> Result<(), String> {
+    match &output.path {
+        Some(path) => write_atomically(path, aliases),
+        None => out.write_all(aliases.as_bytes()).map_err(|e| e.to_string()),
+    }
+}
+
+/// Writes `contents` to a temporary file next to `path` and then renames it over `path`, so that
+/// anything reading `path` sees either the old contents or the new ones, never part of them.
+fn write_atomically(path: &Path, contents: &str) -> Result<(), String> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let describe = |e: io::Error| format!("couldn't write {}: {}", path.display(), e);
+    let mut file = tempfile::NamedTempFile::new_in(dir).map_err(describe)?;
+    file.write_all(contents.as_bytes()).map_err(describe)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.as_file()
+            .set_permissions(fs::Permissions::from_mode(0o644))
+            .map_err(describe)?;
+    }
+    file.persist(path).map_err(|e| describe(e.error))?;
+    Ok(())
+}
+
+/// Escapes an alias body for tcsh's single quotes: each `'` is written between two single-quoted
+/// strings, and each `!` that isn't already escaped gets a backslash, since history substitution
+/// happens even inside single quotes.
+fn escape_tcsh_body(body: &str) -> String {
+    let mut escaped = String::with_capacity(body.len());
+    let mut previous = None;
+    for c in body.chars() {
+        match c {
+            '\'' => escaped.push_str(r"'\''"),
+            '!' if previous != Some('\\') => escaped.push_str(r"\!"),
+            c => escaped.push(c),
+        }
+        previous = Some(c);
+    }
+    escaped
+}
+
+/// Formats the command that removes an alias in the given shell, so that defining it again
+/// replaces whatever had the name before. Nushell's `def`, PowerShell's `function`, Elvish's
+/// `edit:add-var`, and doskey macros already replace an existing command, so they don't need
+/// one.
+fn format_unalias(alias: &str, shell: ShellFormat) -> Option<String> {
+    match shell {
+        ShellFormat::Bash | ShellFormat::Zsh | ShellFormat::Posix => {
+            Some(format!("unalias {} 2>/dev/null\n", alias))
+        }
+        ShellFormat::Fish => Some(format!("functions --erase {}\n", alias)),
+        ShellFormat::Tcsh => Some(format!("unalias {}\n", alias)),
+        ShellFormat::Nushell | ShellFormat::Powershell | ShellFormat::Elvish | ShellFormat::Cmd => {
+            None
+        }
+    }
+}
+
+/// Formats a single alias that runs `body` in the given shell's syntax. Alias names only ever
+/// hold characters that are safe to write in every shell, so only the body needs escaping.
+fn format_alias(alias: &str, body: &str, shell: ShellFormat) -> String {
+    match shell {
+        ShellFormat::Bash | ShellFormat::Zsh | ShellFormat::Posix => {
+            // A single-quoted string can't hold a `'`, so each one closes the string, adds a
+            // double-quoted `'` and opens it again.
+            format!("alias {}='{}'\n", alias, body.replace('\'', "'\"'\"'"))
+        }
+        ShellFormat::Fish => format!("function {}\n  {}\nend\n", alias, body),
+        // Without --env, a custom command's directory change doesn't outlive the command.
+        ShellFormat::Nushell => format!("def --env {} [] {{ {} }}\n", alias, body),
+        // PowerShell aliases can't take arguments, so each one is a function instead.
+        ShellFormat::Powershell => format!("function {} {{ {} }}\n", alias, body),
+        // `eval` runs the code in a namespace of its own, so a plain `fn` wouldn't reach the
+        // REPL. `edit:add-var` defines the function there instead.
+        ShellFormat::Elvish => format!("edit:add-var {}~ {{ {} }}\n", alias, body),
+        // tcsh has no `=` between the name and the body, and no way to escape a `'` inside
+        // single quotes, so each one is written between two single-quoted strings instead.
+        ShellFormat::Tcsh => format!("alias {} '{}'\n", alias, escape_tcsh_body(body)),
+        // Doskey macros are written the way a file for `doskey /macrofile=` lists them.
+        ShellFormat::Cmd => format!("{}={}\n", alias, body),
+    }
+}
+
+/// Returns the name of the variable that holds an alias's path: the alias in uppercase after
+/// `DIR_`, with anything that can't be in a variable name replaced by `_`, so `proj.api` becomes
+/// `DIR_PROJ_API`.
+fn variable_name(alias: &str) -> String {
+    let name: String = alias
+        .chars()
+        .map(|c| match c.to_ascii_uppercase() {
+            c @ ('A'..='Z' | '0'..='9' | '_') => c,
+            _ => '_',
+        })
+        .collect();
+    format!("DIR_{}", name)
+}
+
+/// Returns the alias whose path each variable from `--with-vars` holds, by variable name. When
+/// more than one alias gets the same variable name, like `proj.api` and `proj-api`, the first of
+/// them by name keeps it.
+fn variable_owners(config: &Configuration, output: &AliasOutput) -> HashMap<String, String> {
+    let mut owners = HashMap::new();
+    for entry in config.entries() {
+        let alias = output.apply(&entry.alias);
+        owners.entry(variable_name(&alias)).or_insert(alias);
+    }
+    owners
+}
+
+/// Formats the command that exports a variable holding `path`, already quoted for the given
+/// shell.
+fn format_variable(name: &str, path: &str, shell: ShellFormat) -> String {
+    match shell {
+        ShellFormat::Bash | ShellFormat::Zsh | ShellFormat::Posix => {
+            format!("export {}={}\n", name, path)
+        }
+        ShellFormat::Fish => format!("set -gx {} {}\n", name, path),
+        ShellFormat::Nushell => format!("$env.{} = ({} | path expand)\n", name, path),
+        ShellFormat::Powershell => format!("$env:{} = {}\n", name, path),
+        ShellFormat::Elvish => format!("set-env {} {}\n", name, path),
+        ShellFormat::Tcsh => format!("setenv {} {}\n", name, path),
+        // A macro file can only hold macros, so --with-vars is rejected for cmd.
+        ShellFormat::Cmd => String::new(),
+    }
+}
+
+/// WatchEvent is sent to the watch loop when the configuration changes or the user interrupts it.
+enum WatchEvent {
+    Changed,
+    Interrupted,
+}
+
+/// Prints the configured aliases, then prints them again each time the configuration file
+/// changes until the process receives Ctrl-C.
+fn watch(
+    options: &ParserOptions,
+    output: &AliasOutput,
+    verbosity: Verbosity,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> Result<(), String> {
+    let path = config_file_path();
+    let options = &with_default_base(options.clone(), &path);
+    let mut emitted = None;
+    emit_watched_aliases(&path, options, output, verbosity, &mut emitted, out, err)?;
+
+    let (tx, rx) = mpsc::channel();
+    let interrupt = tx.clone();
+    ctrlc::set_handler(move || {
+        let _ = interrupt.send(WatchEvent::Interrupted);
+    })
+    .map_err(|e| e.to_string())?;
+
+    let file_name = Path::new(&path).file_name().map(|n| n.to_os_string());
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let modified = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+            if modified
+                && event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == file_name.as_deref())
+            {
+                let _ = tx.send(WatchEvent::Changed);
+            }
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    // Watching the directory rather than the file keeps working when editors replace the file.
+    let dir = Path::new(&path).parent().unwrap_or_else(|| Path::new("."));
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(|e| e.to_string())?;
+
+    while let Ok(event) = rx.recv() {
+        match event {
+            WatchEvent::Changed => {
+                let result =
+                    emit_watched_aliases(&path, options, output, verbosity, &mut emitted, out, err);
+                if let Err(e) = result {
+                    writeln!(err, "dalia: {}: {}", path, e).map_err(|e| e.to_string())?;
+                }
+            }
+            WatchEvent::Interrupted => break,
+        }
+    }
+    if verbosity == Verbosity::Normal {
+        writeln!(err, "dalia: stopped watching {}", path).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Parses the configuration file at `path` and prints its aliases, prefixed with `unalias -a &&`
+/// so that the shell drops the aliases printed before. Nothing is printed when the aliases hash
+/// the same as the ones last `emitted`, like after only a description changed.
+fn emit_watched_aliases(
+    path: &str,
+    options: &ParserOptions,
+    output: &AliasOutput,
+    verbosity: Verbosity,
+    emitted: &mut Option<u64>,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> Result<(), String> {
+    let mut config = Configuration::from_path(path.to_string(), options.clone())?;
+    config.process_input_for_terminal()?;
+    report_warnings(&config, verbosity, err)?;
+    report_name_warnings(&config, output, verbosity, err)?;
+
+    let mut hasher = DefaultHasher::new();
+    config.hash(&mut hasher);
+    if emitted.replace(hasher.finish()) == Some(hasher.finish()) {
+        return Ok(());
+    }
+
+    let mut aliases = Vec::new();
+    generate_aliases(&config, output, &mut aliases)?;
+    // Only shells with `alias` commands have an `unalias` to clear the previous ones with.
+    if matches!(
+        output.shell,
+        ShellFormat::Fish | ShellFormat::Nushell | ShellFormat::Powershell | ShellFormat::Elvish
+    ) {
+        out.write_all(&aliases).map_err(|e| e.to_string())?;
+    } else if output.shell == ShellFormat::Cmd {
+        // A macro file is loaded once, so there's no way to clear the macros it defined before.
+        out.write_all(&aliases).map_err(|e| e.to_string())?;
+    } else if output.shell == ShellFormat::Tcsh {
+        // tcsh has no `unalias -a`, but unalias takes a pattern that matches every alias.
+        writeln!(out, "unalias *").map_err(|e| e.to_string())?;
+        out.write_all(&aliases).map_err(|e| e.to_string())?;
+    } else if aliases.is_empty() {
+        writeln!(out, "unalias -a").map_err(|e| e.to_string())?;
+    } else {
+        write!(out, "unalias -a && ").map_err(|e| e.to_string())?;
+        out.write_all(&aliases).map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+/// ListFormat is what the `list` command prints for each alias.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ListFormat {
+    /// The alias, its path, and its description, lined up in columns.
+    Table,
+    /// Just the alias name.
+    Names,
+    /// Just the path.
+    Paths,
+}
+
+/// Prints every alias in the configuration, sorted by name, in the given format.
+fn print_list(
+    config: &Configuration,
+    format: ListFormat,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let entries = config.entries();
+    let alias_width = entries
+        .iter()
+        .map(|e| e.alias.chars().count())
+        .max()
+        .unwrap_or(0);
+    let path_width = entries
+        .iter()
+        .map(|e| e.path.chars().count())
+        .max()
+        .unwrap_or(0);
+    for entry in &entries {
+        let line = match (format, &entry.description) {
+            (ListFormat::Names, _) => entry.alias.to_owned(),
+            (ListFormat::Paths, _) => entry.path.to_owned(),
+            (ListFormat::Table, Some(description)) => format!(
+                "{:alias_width$}  {:path_width$}  {}",
+                entry.alias,
+                entry.path,
+                description,
+                alias_width = alias_width,
+                path_width = path_width
+            ),
+            (ListFormat::Table, None) => format!(
+                "{:alias_width$}  {}",
+                entry.alias,
+                entry.path,
+                alias_width = alias_width
+            ),
+        };
+        writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn print_stat(out: &mut impl Write) -> Result<(), String> {
+    let mut config = Configuration::new(parser_options(&[])?)?;
+    config.process_input_for_terminal()?;
+
+    let stats = Statistics::new(&config);
+    let modified = fs::metadata(&config.path)
+        .and_then(|m| m.modified())
+        .map(format_timestamp)
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    writeln!(out, "config:      {}", config.path)
+        .and_then(|_| writeln!(out, "modified:    {}", modified))
+        .and_then(|_| writeln!(out, "aliases:     {}", stats.total))
+        .and_then(|_| writeln!(out, "explicit:    {}", stats.explicit))
+        .and_then(|_| writeln!(out, "derived:     {}", stats.derived))
+        .and_then(|_| writeln!(out, "glob:        {}", stats.glob))
+        .and_then(|_| writeln!(out, "broken:      {}", stats.broken))
+        .map_err(|e| e.to_string())
+}
+
+/// Checks every configured alias for style problems, reporting them in alias order.
+pub fn lint_config(config: &Configuration) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+    for entry in config.entries() {
+        let mut report = |level, message| {
+            warnings.push(LintWarning {
+                level,
+                alias: entry.alias.to_owned(),
+                message,
+                line: entry.line,
+            })
+        };
+        if !is_absolute_config_path(&entry.path) {
+            report(
+                LintLevel::Error,
+                format!(
+                    "alias '{}' points at the relative path {}",
+                    entry.alias, entry.path
+                ),
+            );
+        } else if entry.path != "~" && Path::new(&entry.path).file_name().is_none() {
+            report(
+                LintLevel::Warning,
+                format!(
+                    "alias '{}' points at {}, which has no directory name",
+                    entry.alias, entry.path
+                ),
+            );
+        }
+        if SHADOWED_COMMANDS.contains(&entry.alias.as_str()) {
+            report(
+                LintLevel::Warning,
+                format!("alias '{}' hides the {} command", entry.alias, entry.alias),
+            );
+        }
+        if entry.alias.chars().any(char::is_uppercase) {
+            report(
+                LintLevel::Info,
+                format!("alias '{}' has uppercase letters", entry.alias),
+            );
+        }
+    }
+    warnings
+}
+
+/// Writes each lint warning to `out`, failing when any of them is at least as serious as
+/// `fail_level`.
+fn print_lint(
+    warnings: &[LintWarning],
+    fail_level: LintLevel,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    for warning in warnings {
+        writeln!(out, "{}", warning).map_err(|e| e.to_string())?;
+    }
+    let failures = warnings.iter().filter(|w| w.level >= fail_level).count();
+    match failures {
+        0 => Ok(()),
+        1 => Err("lint found 1 problem".to_string()),
+        n => Err(format!("lint found {} problems", n)),
+    }
+}
+
+/// Formats a timestamp as a UTC date and time, e.g. `2021-03-14 15:09:26 UTC`.
+fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Converts days since the epoch to a civil date; see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+/// Returns the words that can follow the given subcommand on the command line.
+fn command_arguments(command: &str) -> Vec<&'static str> {
+    match command {
+        "help" => COMMAND_NAMES.to_vec(),
+        "lint" => vec!["--fail-on-warning"],
+        "format" => vec!["--check", "--in-place"],
+        "list" => vec!["--names-only", "--paths-only"],
+        "config" => vec!["path", "edit"],
+        "aliases" | "watch" => [
+            "--preserve-case",
+            "--strict",
+            "--include-hidden",
+            "--include-unreadable",
+            "--no-follow-symlinks",
+            "--disambiguate",
+            "--canonicalize",
+            "--base",
+            "--max-glob",
+            "--file-command",
+            "--with-unalias",
+            "--with-vars",
+            "--sort",
+            "--prefix",
+            "--suffix",
+            "--shell",
+            "--no-shadow-check",
+        ]
+        .iter()
+        .copied()
+        .chain(if command == "aliases" {
+            vec!["--output-path", "--no-cache", "--config"]
+        } else {
+            Vec::new()
+        })
+        .collect(),
+        "version" => vec!["--json"],
+        "init" => ShellFormat::INIT.iter().map(ShellFormat::name).collect(),
+        "completions" => ShellFormat::COMPLETIONS
+            .iter()
+            .map(ShellFormat::name)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the line that loads dalia's aliases when it's added to the given shell's startup file.
+fn init_script(shell: ShellFormat) -> Result<String, String> {
+    match shell {
+        ShellFormat::Bash => Ok("eval \"$(dalia aliases)\"\n".to_string()),
+        ShellFormat::Zsh | ShellFormat::Posix => {
+            Ok(format!("eval \"$(dalia aliases --shell {})\"\n", shell))
+        }
+        ShellFormat::Fish => Ok("dalia aliases --shell fish | source\n".to_string()),
+        ShellFormat::Elvish => Ok("eval (dalia aliases --shell elvish | slurp)\n".to_string()),
+        // Nushell can only source files that exist before it starts running the startup file.
+        // tcsh's eval joins the lines of its input into one, so it can't define more than one
+        // alias at a time.
+        ShellFormat::Nushell | ShellFormat::Powershell | ShellFormat::Tcsh | ShellFormat::Cmd => {
+            Err(format!("init isn't available for {}", shell))
+        }
+    }
+}
+
+/// Generates a completion script for dalia's own subcommands in the given shell's syntax.
+fn completion_script(shell: ShellFormat) -> Result<String, String> {
+    let commands = COMMAND_NAMES.join(" ");
+    let with_arguments: Vec<(&str, String)> = COMMAND_NAMES
+        .iter()
+        .map(|c| (*c, command_arguments(c).join(" ")))
+        .filter(|(_, args)| !args.is_empty())
+        .collect();
+
+    let mut script = String::new();
+    match shell {
+        ShellFormat::Bash => {
+            script.push_str("_dalia() {\n");
+            script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+            script.push_str("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n");
+            script.push_str(&format!(
+                "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n",
+                commands
+            ));
+            script.push_str("        return\n");
+            script.push_str("    fi\n");
+            script.push_str("    case \"${COMP_WORDS[1]}\" in\n");
+            for (command, args) in &with_arguments {
+                script.push_str(&format!(
+                    "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;\n",
+                    command, args
+                ));
+            }
+            script.push_str("    esac\n");
+            script.push_str("}\n");
+            script.push_str("complete -F _dalia dalia\n");
+        }
+        ShellFormat::Zsh => {
+            script.push_str("#compdef dalia\n");
+            script.push_str("_dalia() {\n");
+            script.push_str("    if (( CURRENT == 2 )); then\n");
+            script.push_str(&format!("        compadd -- {}\n", commands));
+            script.push_str("        return\n");
+            script.push_str("    fi\n");
+            script.push_str("    case \"${words[2]}\" in\n");
+            for (command, args) in &with_arguments {
+                script.push_str(&format!("        {}) compadd -- {} ;;\n", command, args));
+            }
+            script.push_str("    esac\n");
+            script.push_str("}\n");
+            script.push_str("compdef _dalia dalia\n");
+        }
+        ShellFormat::Fish => {
+            script.push_str("complete -c dalia -f\n");
+            script.push_str(&format!(
+                "complete -c dalia -n '__fish_use_subcommand' -a '{}'\n",
+                commands
+            ));
+            for (command, args) in &with_arguments {
+                script.push_str(&format!(
+                    "complete -c dalia -n '__fish_seen_subcommand_from {}' -a '{}'\n",
+                    command, args
+                ));
+            }
+        }
+        ShellFormat::Nushell
+        | ShellFormat::Posix
+        | ShellFormat::Powershell
+        | ShellFormat::Elvish
+        | ShellFormat::Tcsh
+        | ShellFormat::Cmd => return Err(format!("completions aren't available for {}", shell)),
+    }
+    Ok(script)
+}
+
+fn print_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", USAGE).map_err(|e| e.to_string())
+}
+
+fn print_alias_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", ALIASES_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_list_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", LIST_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_stat_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", STAT_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_lint_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", LINT_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_format_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", FORMAT_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_watch_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", WATCH_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_config_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", CONFIG_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_init_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", INIT_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_completions_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", COMPLETIONS_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_version_usage(out: &mut impl Write) -> Result<(), String> {
+    writeln!(out, "{}", VERSION_USAGE).map_err(|e| e.to_string())
+}
+
+fn print_version(out: &mut impl Write) -> Result<(), String> {
     if let Some(v) = VERSION {
-        println!("dalia version {}", v)
+        writeln!(out, "dalia version {}", v).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Prints the version along with the build profile as a single line of JSON.
+fn print_version_json(out: &mut impl Write) -> Result<(), String> {
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    writeln!(
+        out,
+        "{{\"version\":\"{}\",\"profile\":\"{}\"}}",
+        VERSION.unwrap_or("unknown"),
+        profile
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir, create_dir_all, write};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Serializes tests that change environment variables read by the command.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    static_assertions::assert_impl_all!(Configuration<'static>: Clone, Send, Sync);
+
+    #[test]
+    fn test_cloned_configuration_is_independent() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let config_path = dir.join(CONFIG_FILE);
+        write(&config_path, "[work]/home/me/work\n").map_err(|e| e.to_string())?;
+
+        let config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        let mut clone = config.clone();
+        clone.process_input()?;
+        clone.path = "/elsewhere/config".into();
+
+        assert_eq!(1, clone.aliases().len());
+        assert!(config.aliases().is_empty());
+        assert_eq!(config_path.display().to_string(), config.path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_statistics_counts_alias_sources() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let glob_dir = dir.join("glob");
+        create_dir(&glob_dir).map_err(|e| e.to_string())?;
+        create_dir(glob_dir.join("one")).map_err(|e| e.to_string())?;
+        let explicit_dir = dir.join("explicit");
+        create_dir(&explicit_dir).map_err(|e| e.to_string())?;
+
+        let config_path = dir.join(CONFIG_FILE);
+        let contents = format!(
+            "[work]{}\n{}\n[*]{}\n",
+            explicit_dir.display(),
+            dir.join("missing").display(),
+            glob_dir.display()
+        );
+        write(&config_path, contents).map_err(|e| e.to_string())?;
+
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let stats = Statistics::new(&config);
+        assert_eq!(
+            Statistics {
+                total: 3,
+                explicit: 1,
+                derived: 1,
+                glob: 1,
+                broken: 1,
+            },
+            stats
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_config_reports_style_problems() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        let contents = "[ls]/home/me/code\n[root]/\n[Docs]/home/me/docs\nwork = code/work\n";
+        write(&config_path, contents).map_err(|e| e.to_string())?;
+        let config = Configuration::builder().config_path(&config_path).build()?;
+
+        let lint = |level, alias: &str, message: &str, line| LintWarning {
+            level,
+            alias: alias.to_string(),
+            message: message.to_string(),
+            line,
+        };
+        assert_eq!(
+            vec![
+                lint(
+                    LintLevel::Info,
+                    "Docs",
+                    "alias 'Docs' has uppercase letters",
+                    3
+                ),
+                lint(
+                    LintLevel::Warning,
+                    "ls",
+                    "alias 'ls' hides the ls command",
+                    1
+                ),
+                lint(
+                    LintLevel::Warning,
+                    "root",
+                    "alias 'root' points at /, which has no directory name",
+                    2
+                ),
+                lint(
+                    LintLevel::Error,
+                    "work",
+                    "alias 'work' points at the relative path code/work",
+                    4
+                ),
+            ],
+            lint_config(&config)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_fails_on_warnings_when_asked() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&file, "[ls]/home/me/code\n").map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> (Result<(), String>, String) {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let mut out = Vec::new();
+            let result = Command::run_with_writers(&args, &mut out, &mut Vec::new());
+            (result, String::from_utf8(out).unwrap())
+        };
+        let lenient = run(&["dalia", "lint"]);
+        let strict = run(&["dalia", "lint", "--fail-on-warning"]);
+        let unknown = run(&["dalia", "lint", "--nope"]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        let expected = "warning: alias 'ls' hides the ls command on line 1\n".to_string();
+        assert_eq!((Ok(()), expected.clone()), lenient);
+        assert_eq!((Err("lint found 1 problem".into()), expected), strict);
+        assert_eq!(Err("unknown flag: --nope".into()), unknown.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_prints_aliases() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(
+            &file,
+            "[web]/some/web\n[api]/some/code/api \"the public API\"\n[Docs]/docs\n",
+        )
+        .map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> Result<String, String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let mut out = Vec::new();
+            Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+        let table = run(&["dalia", "list"]);
+        let names = run(&["dalia", "list", "--names-only"]);
+        let paths = run(&["dalia", "list", "--paths-only"]);
+        let unknown = run(&["dalia", "list", "--json"]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        assert_eq!(
+            "Docs  /docs\napi   /some/code/api  the public API\nweb   /some/web\n",
+            table?
+        );
+        assert_eq!("Docs\napi\nweb\n", names?);
+        assert_eq!("/docs\n/some/code/api\n/some/web\n", paths?);
+        assert_eq!(Err("unknown flag: --json".to_string()), unknown);
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_file_env_var_takes_precedence() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let file = dir.join("dalia.conf");
+        write(&file, "[work]/some/work/path\n").map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_ENV_VAR, dir.join("elsewhere"));
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let config = Configuration::new(ParserOptions::default());
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+        env::remove_var(DALIA_CONFIG_ENV_VAR);
+
+        let mut config = config?;
+        assert_eq!(file.display().to_string(), config.path);
+        config.process_input()?;
+        assert_eq!("/some/work/path", config.aliases().get("work").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_file_env_var_overrides_file_name() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let file = dir.join("config.work");
+        write(&file, "[work]/some/work/path\n").map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_ENV_VAR, &dir);
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, "config.work");
+        let config = Configuration::new(ParserOptions::default());
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+        let default_path = config_file_path();
+        env::remove_var(DALIA_CONFIG_ENV_VAR);
+
+        let mut config = config?;
+        assert_eq!(file.display().to_string(), config.path);
+        config.process_input()?;
+        assert_eq!("/some/work/path", config.aliases().get("work").unwrap());
+        assert_eq!(dir.join(CONFIG_FILE).display().to_string(), default_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_path_prints_the_resolved_location() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let run = || -> Result<String, String> {
+            let args: Vec<String> = vec!["dalia".into(), "config".into(), "path".into()];
+            let mut out = Vec::new();
+            Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+
+        env::set_var(DALIA_CONFIG_ENV_VAR, &dir);
+        let in_dir = run();
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, dir.join("missing.conf"));
+        let full_path = run();
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+        env::remove_var(DALIA_CONFIG_ENV_VAR);
+
+        assert_eq!(format!("{}\n", dir.join(CONFIG_FILE).display()), in_dir?);
+        assert_eq!(
+            format!("{}\n", dir.join("missing.conf").display()),
+            full_path?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_expects_path_or_edit() {
+        let run = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            Command::run_with_writers(&args, &mut Vec::new(), &mut Vec::new())
+        };
+        assert_eq!(
+            Err("missing config command; expected path or edit.".to_string()),
+            run(&["dalia", "config"])
+        );
+        assert_eq!(
+            Err("unknown config command: show; expected path or edit.".to_string()),
+            run(&["dalia", "config", "show"])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_config_edit_creates_a_missing_file() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join("new").join(CONFIG_FILE);
+
+        let editor = env::var("EDITOR").ok();
+        env::set_var("EDITOR", "true");
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let result = edit_config();
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+        match editor {
+            Some(editor) => env::set_var("EDITOR", editor),
+            None => env::remove_var("EDITOR"),
+        }
+
+        result?;
+        assert!(file.is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_prints_tokens() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&file, "[work]/some/work/path\n").map_err(|e| e.to_string())?;
+
+        let mut out = Vec::new();
+        print_tokens(&file.display().to_string(), &mut out)?;
+        assert_eq!(
+            "<'[', LBRACK>\n<'work', ALIAS>\n<']', RBRACK>\n<'/some/work/path', PATH>\n<'<EOF>', <EOF>>\n",
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_emit_watched_aliases_reparses_the_same_file() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(
+            &config_path,
+            "[work]/some/work/path\n[play]/some/play/path\n",
+        )
+        .map_err(|e| e.to_string())?;
+        let path = config_path.display().to_string();
+        let options = ParserOptions::default();
+
+        let emit = || -> Result<Vec<String>, String> {
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            emit_watched_aliases(
+                &path,
+                &options,
+                &AliasOutput::default(),
+                Verbosity::Normal,
+                &mut None,
+                &mut out,
+                &mut err,
+            )?;
+            let out = String::from_utf8(out).unwrap();
+            assert!(out.starts_with("unalias -a && alias "), "{}", out);
+            let mut lines: Vec<String> = out
+                .trim_start_matches("unalias -a && ")
+                .lines()
+                .map(String::from)
+                .collect();
+            lines.sort();
+            Ok(lines)
+        };
+        let first = emit()?;
+        let second = emit()?;
+
+        assert_eq!(
+            vec![
+                "alias play='cd \"/some/play/path\"'",
+                "alias work='cd \"/some/work/path\"'"
+            ],
+            first
+        );
+        assert_eq!(first, second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_emit_watched_aliases_skips_unchanged_aliases() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        let path = config_path.display().to_string();
+        let mut emitted = None;
+        let mut emit = |contents: &str| -> Result<String, String> {
+            write(&config_path, contents).map_err(|e| e.to_string())?;
+            let mut out = Vec::new();
+            emit_watched_aliases(
+                &path,
+                &ParserOptions::default(),
+                &AliasOutput::default(),
+                Verbosity::Normal,
+                &mut emitted,
+                &mut out,
+                &mut Vec::new(),
+            )?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(
+            "unalias -a && alias work='cd \"/some/work/path\"'\n",
+            emit("[work]/some/work/path\n")?
+        );
+        assert_eq!("", emit("\n[work]/some/work/path \"my job\"\n")?);
+        assert_eq!(
+            "unalias -a && alias work='cd \"/some/other/path\"'\n",
+            emit("[work]/some/other/path\n")?
+        );
+        Ok(())
+    }
+
+    fn parsed(contents: &str) -> Result<Configuration<'static>, String> {
+        let mut config = Configuration::from_contents(
+            CONFIG_FILE.to_string(),
+            contents,
+            ParserOptions::default(),
+        )?;
+        config.process_input()?;
+        Ok(config)
+    }
+
+    fn hash_of(config: &Configuration) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        config.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_configurations_with_the_same_aliases_are_equal() -> Result<(), String> {
+        let config = parsed("[work]/some/work/path\n[play]/some/play/path\n")?;
+        let same = parsed("[work]/some/work/path\n[play]/some/play/path\n")?;
+        let reordered = parsed("[play]/some/play/path \"games\"\n\n[work]/some/work/path\n")?;
+        assert!(config == same);
+        assert!(config == reordered);
+        assert_eq!(hash_of(&config), hash_of(&same));
+        assert_eq!(hash_of(&config), hash_of(&reordered));
+        Ok(())
+    }
+
+    #[test]
+    fn test_configurations_with_different_aliases_are_not_equal() -> Result<(), String> {
+        let config = parsed("[work]/some/work/path\n[play]/some/play/path\n")?;
+        for other in [
+            "[work]/some/work/path\n",
+            "[work]/some/work/path\n[play]/some/other/path\n",
+            "[work]/some/work/path\n[games]/some/play/path\n",
+            "[work]/some/work/path\n[play]{code %p}/some/play/path\n",
+        ]
+        .iter()
+        {
+            let other = parsed(other)?;
+            assert!(config != other);
+            assert_ne!(hash_of(&config), hash_of(&other));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_reports_missing_glob_directory() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let file = dir.join(CONFIG_FILE);
+        let missing = dir.join("moved");
+        write(&file, format!("[*]{}\n", missing.display())).map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let args: Vec<String> = vec!["dalia".into(), "aliases".into()];
+        let result = Command::run_with_writers(&args, &mut Vec::new(), &mut Vec::new());
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        assert_eq!(
+            Err(format!(
+                "error in {}: couldn't expand {} on line 1: entity not found",
+                file.display(),
+                missing.display()
+            )),
+            result
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_aliases_uses_configured_command() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&config_path, "[mydir:pushd]/some/path\n").map_err(|e| e.to_string())?;
+
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+        let mut out: Vec<u8> = Vec::new();
+        generate_aliases(&config, &AliasOutput::default(), &mut out)?;
+
+        assert_eq!(
+            "alias mydir='pushd \"/some/path\"'\n",
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_with_prefix_and_suffix() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&file, "[api]/some/api\n/some/web\n").map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> Result<String, String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let mut out = Vec::new();
+            Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+        let prefixed = run(&["dalia", "aliases", "--prefix", "proj-"]);
+        let both = run(&[
+            "dalia", "aliases", "--strict", "--prefix", "p", "--suffix", "_s",
+        ]);
+        let invalid = run(&["dalia", "aliases", "--prefix", "a'b"]);
+        let missing = run(&["dalia", "aliases", "--suffix"]);
+        let shell = run(&["dalia", "aliases", "--shell", "xonsh"]);
+        let fish = run(&["dalia", "aliases", "--shell", "fish"]);
+        let unalias = run(&["dalia", "aliases", "--with-unalias"]);
+        let fish_unalias = run(&["dalia", "aliases", "--with-unalias", "--shell", "fish"]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        let prefixed = prefixed?;
+        assert_eq!(2, prefixed.lines().count());
+        for line in prefixed.lines() {
+            assert!(line.starts_with("alias proj-"), "{}", line);
+        }
+        assert_eq!(
+            "alias papi_s='cd \"/some/api\"'\nalias pweb_s='cd \"/some/web\"'\n",
+            both?
+        );
+        assert_eq!(Err("invalid value for --prefix: a'b".to_string()), invalid);
+        assert_eq!(Err("unknown shell: xonsh".to_string()), shell);
+        assert!(fish?.starts_with("function api\n  cd \"/some/api\"\nend\n"));
+        assert_eq!(Err("missing value for --suffix".to_string()), missing);
+        assert_eq!(
+            "unalias api 2>/dev/null\nunalias web 2>/dev/null\nalias api='cd \"/some/api\"'\nalias web='cd \"/some/web\"'\n",
+            unalias?
+        );
+        assert!(fish_unalias?
+            .starts_with("functions --erase api\nfunctions --erase web\nfunction api\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_config_dir_follows_xdg() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let xdg = PathBuf::from(temp.as_ref()).join("xdg_test");
+        create_dir(&xdg).map_err(|e| e.to_string())?;
+        create_dir(xdg.join("dalia")).map_err(|e| e.to_string())?;
+
+        let previous = env::var(XDG_CONFIG_HOME_ENV_VAR);
+        env::set_var(XDG_CONFIG_HOME_ENV_VAR, &xdg);
+        let dir = default_config_dir();
+        match previous {
+            Ok(v) => env::set_var(XDG_CONFIG_HOME_ENV_VAR, v),
+            Err(_) => env::remove_var(XDG_CONFIG_HOME_ENV_VAR),
+        }
+
+        assert_eq!(xdg.join("dalia"), dir);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_default_config_dir_falls_back_to_legacy_dir() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let home = PathBuf::from(temp.as_ref());
+        create_dir(home.join(".dalia")).map_err(|e| e.to_string())?;
+
+        let (previous_home, previous_xdg) = (env::var("HOME"), env::var(XDG_CONFIG_HOME_ENV_VAR));
+        env::set_var("HOME", &home);
+        env::remove_var(XDG_CONFIG_HOME_ENV_VAR);
+        let legacy = default_config_dir();
+        create_dir(home.join(".config")).map_err(|e| e.to_string())?;
+        create_dir(home.join(".config").join("dalia")).map_err(|e| e.to_string())?;
+        let xdg = default_config_dir();
+        if let Ok(v) = previous_home {
+            env::set_var("HOME", v);
+        }
+        if let Ok(v) = previous_xdg {
+            env::set_var(XDG_CONFIG_HOME_ENV_VAR, v);
+        }
+
+        assert_eq!(home.join(".dalia"), legacy);
+        assert_eq!(home.join(".config").join("dalia"), xdg);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_glob_base_expands_home_directory() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let home = PathBuf::from(temp.as_ref());
+        create_dir(home.join("sub")).map_err(|e| e.to_string())?;
+        create_dir(home.join("sub").join("api")).map_err(|e| e.to_string())?;
+        create_dir(home.join("sub").join("web")).map_err(|e| e.to_string())?;
+        let config_path = home.join(CONFIG_FILE);
+        write(&config_path, "[*]~/sub\n").map_err(|e| e.to_string())?;
+
+        let previous_home = env::var("HOME");
+        env::set_var("HOME", &home);
+        let config = Configuration::builder().config_path(&config_path).build();
+        if let Ok(v) = previous_home {
+            env::set_var("HOME", v);
+        }
+
+        let aliases = config?.aliases();
+        assert_eq!(2, aliases.len());
+        assert_eq!(
+            Some(&home.join("sub").join("api").display().to_string()),
+            aliases.get("api")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_configuration_builder() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join("aliases.conf");
+        write(&config_path, "[work]/some/work/path\n/some/Other\n").map_err(|e| e.to_string())?;
+
+        let config = Configuration::builder()
+            .config_path(&config_path)
+            .options(ParserOptions {
+                preserve_case: true,
+                ..Default::default()
+            })
+            .shell(ShellFormat::Zsh)
+            .build()?;
+        let mut out: Vec<u8> = Vec::new();
+        config.write_aliases(&mut out)?;
+
+        assert_eq!(config_path.display().to_string(), config.path());
+        assert_eq!(ShellFormat::Zsh, config.shell());
+        assert_eq!(
+            "alias Other='cd \"/some/Other\"'\nalias work='cd \"/some/work/path\"'\n",
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_configuration_builder_fails_with_missing_file() {
+        let temp = temp_testdir::TempDir::default();
+        let result = Configuration::builder()
+            .config_path(PathBuf::from(temp.as_ref()).join("missing"))
+            .build();
+        assert!(matches!(result, Err(DaliaError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_configuration_errors_name_the_file() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&config_path, "[work]/home/me/work\n[broken\n").map_err(|e| e.to_string())?;
+
+        let err = Configuration::builder()
+            .config_path(&config_path)
+            .build()
+            .unwrap_err();
+        assert!(matches!(&err, DaliaError::InConfig(path, _) if *path == config_path));
+        assert_eq!(
+            format!(
+                "parse error in {} at line 2: expecting RBRACK; found <'<EOF>', <EOF>>",
+                config_path.display()
+            ),
+            err.to_string()
+        );
+
+        write(
+            &config_path,
+            "[work]/home/me/work\n[my work]/home/me/work\n",
+        )
+        .map_err(|e| e.to_string())?;
+        let err = Configuration::builder()
+            .config_path(&config_path)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            format!(
+                "parse error in {} at line 2: alias names can't contain spaces: 'my work'",
+                config_path.display()
+            ),
+            err.to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_and_empty_config_files() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let load = |path: &Path| {
+            Configuration::from_path(path.display().to_string(), ParserOptions::default())
+                .map(|_| ())
+        };
+
+        let missing_dir = dir.join("dalia").join(CONFIG_FILE);
+        assert_eq!(
+            Err(format!(
+                "configuration directory {} doesn't exist; create it with a file named config that lists a few paths, or run `dalia config edit`.",
+                dir.join("dalia").display()
+            )),
+            load(&missing_dir)
+        );
+
+        let missing_file = dir.join(CONFIG_FILE);
+        assert_eq!(
+            Err(format!(
+                "configuration file {} doesn't exist; create it with a few paths, or run `dalia config edit`.",
+                missing_file.display()
+            )),
+            load(&missing_file)
+        );
+
+        write(&missing_file, "").map_err(|e| e.to_string())?;
+        assert_eq!(
+            Err(format!(
+                "configuration file {} is empty; add a few paths to it and try again.",
+                missing_file.display()
+            )),
+            load(&missing_file)
+        );
+
+        write(&missing_file, "  \n\t\n").map_err(|e| e.to_string())?;
+        assert!(load(&missing_file).unwrap_err().contains("is empty"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_aliases_writes_to_output() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&config_path, "[work]/some/work/path\n").map_err(|e| e.to_string())?;
+
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+        let mut out: Vec<u8> = Vec::new();
+        generate_aliases(&config, &AliasOutput::default(), &mut out)?;
+
+        assert_eq!(
+            "alias work='cd \"/some/work/path\"'\n",
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_writes_to_output_path() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        let output_path = PathBuf::from(temp.as_ref()).join("aliases.sh");
+        write(&file, "[work]/some/work/path\n").map_err(|e| e.to_string())?;
+        write(&output_path, "alias old='cd /old'\n").map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> Result<String, String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let mut out = Vec::new();
+            Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+        let output = output_path.display().to_string();
+        let aliases = run(&["dalia", "aliases", "--output-path", &output]);
+        let watch = run(&["dalia", "watch", "--output-path", &output]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        assert_eq!("", aliases?);
+        assert_eq!(
+            "alias work='cd \"/some/work/path\"'\n",
+            std::fs::read_to_string(&output_path).map_err(|e| e.to_string())?
+        );
+        assert_eq!(
+            Err("--output-path can't be used with watch".to_string()),
+            watch
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(&output_path).map_err(|e| e.to_string())?;
+            assert_eq!(0o644, metadata.permissions().mode() & 0o777);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_are_cached_until_the_configuration_changes() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        let cache_file = PathBuf::from(temp.as_ref()).join(crate::cache::CACHE_FILE);
+        write(&file, "[work]/some/work/path\n").map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> Result<String, String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let mut out = Vec::new();
+            Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+        let first = run(&["dalia", "aliases"]);
+        // Changing what's cached shows whether the next run reads from the cache.
+        let cached = std::fs::read_to_string(&cache_file).map_err(|e| e.to_string())?;
+        write(&cache_file, cached.replace("alias work=", "alias cached="))
+            .map_err(|e| e.to_string())?;
+        let second = run(&["dalia", "aliases"]);
+        let no_cache = run(&["dalia", "aliases", "--no-cache"]);
+        write(&cache_file, cached.replace("alias work=", "alias cached="))
+            .map_err(|e| e.to_string())?;
+        write(&file, "[work]/some/work/path\n[play]/some/play/path\n")
+            .map_err(|e| e.to_string())?;
+        let changed = run(&["dalia", "aliases"]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        assert_eq!("alias work='cd \"/some/work/path\"'\n", first?);
+        assert_eq!("alias cached='cd \"/some/work/path\"'\n", second?);
+        assert_eq!("alias work='cd \"/some/work/path\"'\n", no_cache?);
+        assert_eq!(
+            "alias play='cd \"/some/play/path\"'\nalias work='cd \"/some/work/path\"'\n",
+            changed?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_aliases_follow_fallback_paths() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let file = dir.join(CONFIG_FILE);
+        let config = format!("[work]{0}/a | {0}/b\n", dir.display());
+        write(&file, config).map_err(|e| e.to_string())?;
+        create_dir(dir.join("b")).map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = || -> Result<String, String> {
+            let args: Vec<String> = vec!["dalia".to_string(), "aliases".to_string()];
+            let mut out = Vec::new();
+            Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+        let before = run();
+        create_dir(dir.join("a")).map_err(|e| e.to_string())?;
+        let after = run();
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        assert_eq!(
+            format!("alias work='cd \"{}/b\"'\n", dir.display()),
+            before?
+        );
+        assert_eq!(format!("alias work='cd \"{}/a\"'\n", dir.display()), after?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_aliases_follow_nested_directories_of_recursive_globs() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let file = dir.join(CONFIG_FILE);
+        write(&file, format!("[**]{}/p\n", dir.display())).map_err(|e| e.to_string())?;
+        create_dir_all(dir.join("p/a")).map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = || -> Result<String, String> {
+            let args: Vec<String> = vec!["dalia".to_string(), "aliases".to_string()];
+            let mut out = Vec::new();
+            Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+        let before = run();
+        create_dir(dir.join("p/a/newchild")).map_err(|e| e.to_string())?;
+        let after = run();
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        assert_eq!(format!("alias a='cd \"{}/p/a\"'\n", dir.display()), before?);
+        assert_eq!(
+            format!(
+                "alias a='cd \"{0}/p/a\"'\nalias newchild='cd \"{0}/p/a/newchild\"'\n",
+                dir.display()
+            ),
+            after?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_from_a_given_configuration_file_are_not_cached() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let file = dir.join("work.conf");
+        write(&file, "[work]/some/work/path\n").map_err(|e| e.to_string())?;
+
+        let args: Vec<String> = vec!["dalia", "aliases", "--config", file.to_str().unwrap()]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let mut out = Vec::new();
+        Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+
+        assert_eq!(
+            "alias work='cd \"/some/work/path\"'\n",
+            String::from_utf8(out).unwrap()
+        );
+        assert!(!dir.join(crate::cache::CACHE_FILE).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_configuration_from_reader() -> Result<(), String> {
+        let input = "[work]/some/work/path\n/some/play\n";
+        let mut config = Configuration::from_reader(input.as_bytes(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let mut out = Vec::new();
+        generate_aliases(&config, &AliasOutput::default(), &mut out)?;
+        assert_eq!(
+            "alias play='cd \"/some/play\"'\nalias work='cd \"/some/work/path\"'\n",
+            String::from_utf8(out).unwrap()
+        );
+        assert_eq!(
+            Err(
+                "configuration file <stdin> is empty; add a few paths to it and try again."
+                    .to_string()
+            ),
+            Configuration::from_reader(&b"\n"[..], ParserOptions::default()).map(|_| ())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_source() -> Result<(), String> {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+        assert_eq!(
+            (Some("-".to_string()), args(&["--strict"])),
+            config_source(&args(&["-", "--strict"]))?
+        );
+        assert_eq!(
+            (Some("-".to_string()), Vec::new()),
+            config_source(&args(&["--config", "-"]))?
+        );
+        assert_eq!(
+            (Some("/etc/dalia".to_string()), Vec::new()),
+            config_source(&args(&["--config", "/etc/dalia"]))?
+        );
+        assert_eq!(
+            (None, args(&["--strict"])),
+            config_source(&args(&["--strict"]))?
+        );
+        assert_eq!(
+            Err("missing value for --config".to_string()),
+            config_source(&args(&["--config"]))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_reads_the_given_config_file() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join("fragment.conf");
+        write(&file, "[work]/some/work/path\n").map_err(|e| e.to_string())?;
+
+        let args: Vec<String> = ["dalia", "aliases", "--config", &file.display().to_string()]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let mut out = Vec::new();
+        Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+        assert_eq!(
+            "alias work='cd \"/some/work/path\"'\n",
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_path_needs_a_value() {
+        let args = vec!["--output-path".to_string()];
+        assert_eq!(
+            Err("missing value for --output-path".to_string()),
+            AliasOutput::from_args(&args)
+        );
+    }
+
+    #[test]
+    fn test_generate_aliases_sorted() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(
+            &config_path,
+            "[web]/b/web\n[Zed]/a/zed\n[api]/c/api\n[Beta]/b/beta\n",
+        )
+        .map_err(|e| e.to_string())?;
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let generate = |args: &[&str]| -> Result<Vec<String>, String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let (output, rest) = AliasOutput::from_args(&args)?;
+            assert!(rest.is_empty());
+            let mut out = Vec::new();
+            generate_aliases(&config, &output, &mut out)?;
+            Ok(String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .map(|l| l.split('=').next().unwrap().to_string())
+                .collect())
+        };
+        assert_eq!(
+            vec!["alias api", "alias Beta", "alias web", "alias Zed"],
+            generate(&["--sort"])?
+        );
+        assert_eq!(generate(&["--sort"])?, generate(&["--sort=alias"])?);
+        assert_eq!(
+            vec!["alias Zed", "alias Beta", "alias web", "alias api"],
+            generate(&["--sort=path"])?
+        );
+        assert_eq!(
+            vec![
+                "unalias p-Zed 2>/dev/null",
+                "unalias p-Beta 2>/dev/null",
+                "unalias p-web 2>/dev/null",
+                "unalias p-api 2>/dev/null",
+                "alias p-Zed",
+                "alias p-Beta",
+                "alias p-web",
+                "alias p-api",
+            ],
+            generate(&["--with-unalias", "--sort=path", "--prefix", "p-"])?
+        );
+        assert_eq!(
+            Err("invalid value for --sort: size; expected alias or path".to_string()),
+            generate(&["--sort=size"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_aliases_with_vars() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&config_path, "[proj.api]~/code/api\n[web]/some/web\n").map_err(|e| e.to_string())?;
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let generate = |args: &[&str]| -> Result<String, String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let (output, _) = AliasOutput::from_args(&args)?;
+            let mut out = Vec::new();
+            generate_aliases(&config, &output, &mut out)?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+        assert_eq!(
+            "alias proj.api='cd ~/\"code/api\"'\nalias web='cd \"/some/web\"'\nexport DIR_PROJ_API=~/\"code/api\"\nexport DIR_WEB=\"/some/web\"\n",
+            generate(&["--with-vars"])?
+        );
+        assert!(
+            generate(&["--with-vars", "--shell", "fish", "--prefix", "p-"])?.ends_with(
+                "set -gx DIR_P_PROJ_API ~/\"code/api\"\nset -gx DIR_P_WEB \"/some/web\"\n"
+            )
+        );
+        assert!(generate(&["--with-vars", "--shell", "nushell"])?
+            .ends_with("$env.DIR_WEB = (\"/some/web\" | path expand)\n"));
+        assert!(!generate(&[])?.contains("DIR_"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_with_vars_warns_about_variables_with_the_same_name() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&file, "[proj.api]/some/old-api\n[proj-api]/some/api\n")
+            .map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let args: Vec<String> = ["dalia", "aliases", "--with-vars", "--no-cache"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let result = Command::run_with_writers(&args, &mut out, &mut err);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        result?;
+        assert_eq!(
+            "alias proj-api='cd \"/some/api\"'\n\
+             alias proj.api='cd \"/some/old-api\"'\n\
+             export DIR_PROJ_API=\"/some/api\"\n",
+            String::from_utf8(out).unwrap()
+        );
+        assert_eq!(
+            format!(
+                "dalia: {}: warning: aliases 'proj-api' and 'proj.api' would both export \
+                 DIR_PROJ_API; only exporting it for 'proj-api'\n",
+                file.display()
+            ),
+            String::from_utf8(err).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_aliases_for_fish() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let projects = PathBuf::from(temp.as_ref()).join("projects");
+        create_dir_all(projects.join("api")).map_err(|e| e.to_string())?;
+        create_dir_all(projects.join("web")).map_err(|e| e.to_string())?;
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(
+            &config_path,
+            format!(
+                "[work]/some/work/path\n/some/Docs\n[*]{}\n",
+                projects.display()
+            ),
+        )
+        .map_err(|e| e.to_string())?;
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let output = AliasOutput {
+            shell: ShellFormat::Fish,
+            sort: Some(SortKey::Alias),
+            ..AliasOutput::default()
+        };
+        let mut out = Vec::new();
+        generate_aliases(&config, &output, &mut out)?;
+        assert_eq!(
+            format!(
+                "function api\n  cd \"{0}/api\"\nend\n\
+                 function docs\n  cd \"/some/Docs\"\nend\n\
+                 function web\n  cd \"{0}/web\"\nend\n\
+                 function work\n  cd \"/some/work/path\"\nend\n",
+                projects.display()
+            ),
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_for_powershell() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(
+            &file,
+            "[docs]C:\\Users\\me\\My Documents\n[here]/home/me/it's-here\n/some/2024\n",
+        )
+        .map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let args: Vec<String> = ["dalia", "aliases", "--shell", "powershell", "--with-vars"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let result = Command::run_with_writers(&args, &mut out, &mut err);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        result?;
+        assert_eq!(
+            "function 2024 { Set-Location '/some/2024' }\n\
+             function docs { Set-Location 'C:\\Users\\me\\My Documents' }\n\
+             function here { Set-Location '/home/me/it''s-here' }\n\
+             $env:DIR_2024 = '/some/2024'\n\
+             $env:DIR_DOCS = 'C:\\Users\\me\\My Documents'\n\
+             $env:DIR_HERE = '/home/me/it''s-here'\n",
+            String::from_utf8(out).unwrap()
+        );
+        assert_eq!(
+            format!(
+                "dalia: {}: warning: 2024 isn't a valid PowerShell function name\n",
+                file.display()
+            ),
+            String::from_utf8(err).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_file_aliases_for_powershell() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        let notes = PathBuf::from(temp.as_ref()).join("notes");
+        create_dir_all(&notes).map_err(|e| e.to_string())?;
+        write(notes.join("todo.md"), "").map_err(|e| e.to_string())?;
+        write(
+            &config_path,
+            format!("[code]$HOME/code\n[*f]{}\n", notes.display()),
+        )
+        .map_err(|e| e.to_string())?;
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let args = vec!["--shell".to_string(), "powershell".to_string()];
+        let (output, _) = AliasOutput::from_args(&args)?;
+        let mut out = Vec::new();
+        generate_aliases(&config, &output, &mut out)?;
+        let todo = notes.join("todo.md").display().to_string();
+        assert_eq!(
+            format!(
+                "function code {{ Set-Location \"${{env:HOME}}/code\" }}\n\
+                 function todo {{ & $env:EDITOR '{}' }}\n",
+                todo
+            ),
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_warns_when_an_alias_hides_a_command() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&file, "[work]/some/work/path\n[cd]/some/cd/path\n").map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> Result<(String, String), String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            Command::run_with_writers(&args, &mut out, &mut err)?;
+            Ok((
+                String::from_utf8(out).unwrap(),
+                String::from_utf8(err).unwrap(),
+            ))
+        };
+        let checked = run(&["dalia", "aliases"]);
+        let unchecked = run(&["dalia", "aliases", "--no-shadow-check"]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        let aliases = "alias cd='cd \"/some/cd/path\"'\nalias work='cd \"/some/work/path\"'\n";
+        assert_eq!(
+            (
+                aliases.to_string(),
+                format!(
+                    "dalia: {}: warning: alias 'cd' on line 2 hides the cd command; \
+                     consider renaming it\n",
+                    file.display()
+                )
+            ),
+            checked?
+        );
+        assert_eq!((aliases.to_string(), String::new()), unchecked?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_rewrites_the_configuration_file() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        let messy = "  [web] /code/web\n\n/some/Desktop  \nnotes=/home/me/notes \"todo\"\n\
+                     [section:work]\n[zz]/work/zz\n\t[api]/work/api\n";
+        write(&file, messy).map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> Result<String, String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let mut out = Vec::new();
+            Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+        let printed = run(&["dalia", "format"]);
+        let unformatted = run(&["dalia", "format", "--check"]);
+        let in_place = run(&["dalia", "format", "--in-place"]);
+        let rewritten = std::fs::read_to_string(&file).map_err(|e| e.to_string())?;
+        let formatted = run(&["dalia", "format", "--check"]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        let expected = "/some/Desktop\nnotes = /home/me/notes \"todo\"\n[web]/code/web\n\
+                        \n[section: work]\n[api]/work/api\n[zz]/work/zz\n";
+        assert_eq!(expected, printed?);
+        assert_eq!(
+            Err(format!(
+                "{} isn't formatted; run `dalia format --in-place` to format it",
+                file.display()
+            )),
+            unformatted
+        );
+        assert_eq!("", in_place?);
+        assert_eq!(expected, rewritten);
+        assert_eq!("", formatted?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_for_cmd() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(
+            &file,
+            "[docs]C:\\Users\\me\\My Documents\n[proj]D:\\Projects\n[résumé]C:\\cv\n",
+        )
+        .map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> Result<(String, String), String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            Command::run_with_writers(&args, &mut out, &mut err)?;
+            Ok((
+                String::from_utf8(out).unwrap(),
+                String::from_utf8(err).unwrap(),
+            ))
+        };
+        let macros = run(&["dalia", "aliases", "--shell", "cmd", "--no-cache"]);
+        let vars = run(&["dalia", "aliases", "--shell", "cmd", "--with-vars"]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        assert_eq!(
+            (
+                "docs=cd /d \"C:\\Users\\me\\My Documents\"\nproj=cd /d \"D:\\Projects\"\n"
+                    .to_string(),
+                format!(
+                    "dalia: {}: warning: skipping résumé: doskey macro names may only contain \
+                     ASCII letters, digits, '_', '-', and '.'\n",
+                    file.display()
+                )
+            ),
+            macros?
+        );
+        assert_eq!(
+            Err("--with-vars can't be used with --shell cmd".to_string()),
+            vars
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_doskey_macro_name() {
+        assert!(is_doskey_macro_name("docs"));
+        assert!(is_doskey_macro_name("2024_proj.api-v2"));
+        assert!(!is_doskey_macro_name("résumé"));
+    }
+
+    #[test]
+    fn test_is_powershell_function_name() {
+        assert!(is_powershell_function_name("docs"));
+        assert!(is_powershell_function_name("_proj.api-v2"));
+        assert!(!is_powershell_function_name("1kb"));
+        assert!(!is_powershell_function_name(".api"));
+        assert!(!is_powershell_function_name("-api"));
+    }
+
+    #[test]
+    fn test_generate_aliases_for_nushell() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(
+            &config_path,
+            "[docs]/home/me/My Documents\n[quote]/home/me/say \\\"hi\\\"\n/some/work\n",
+        )
+        .map_err(|e| e.to_string())?;
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let args = vec!["--shell".to_string(), "nu".to_string()];
+        let (output, _) = AliasOutput::from_args(&args)?;
+        let mut out = Vec::new();
+        generate_aliases(&config, &output, &mut out)?;
+        assert_eq!(
+            "def --env docs [] { cd \"/home/me/My Documents\" }\n\
+             def --env quote [] { cd \"/home/me/say \\\"hi\\\"\" }\n\
+             def --env work [] { cd \"/some/work\" }\n",
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_file_aliases_for_nushell() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        let notes = PathBuf::from(temp.as_ref()).join("notes");
+        create_dir_all(&notes).map_err(|e| e.to_string())?;
+        write(notes.join("todo.md"), "").map_err(|e| e.to_string())?;
+        write(
+            &config_path,
+            format!("[code]$HOME/code\n[*f]{}\n", notes.display()),
+        )
+        .map_err(|e| e.to_string())?;
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let args = vec!["--shell".to_string(), "nu".to_string()];
+        let (output, _) = AliasOutput::from_args(&args)?;
+        let mut out = Vec::new();
+        generate_aliases(&config, &output, &mut out)?;
+        let todo = notes.join("todo.md").display().to_string();
+        assert_eq!(
+            format!(
+                "def --env code [] {{ cd $\"($env.HOME)/code\" }}\n\
+                 def --env todo [] {{ ^$env.EDITOR \"{}\" }}\n",
+                todo
+            ),
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_aliases_for_elvish() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        let notes = PathBuf::from(temp.as_ref()).join("notes");
+        create_dir_all(&notes).map_err(|e| e.to_string())?;
+        write(notes.join("todo.md"), "").map_err(|e| e.to_string())?;
+        write(
+            &config_path,
+            format!(
+                "[docs]~/My Documents\n[here]/home/me/it's-here\n[open]{{code %p}}$HOME/app\n\
+                 [*f]{}\n",
+                notes.display()
+            ),
+        )
+        .map_err(|e| e.to_string())?;
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let args: Vec<String> = ["--shell", "elvish", "--with-unalias"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let (output, _) = AliasOutput::from_args(&args)?;
+        let mut out = Vec::new();
+        generate_aliases(&config, &output, &mut out)?;
+        let todo = notes.join("todo.md").display().to_string();
+        assert_eq!(
+            format!(
+                "edit:add-var docs~ {{ cd ~/'My Documents' }}\n\
+                 edit:add-var here~ {{ cd '/home/me/it''s-here' }}\n\
+                 edit:add-var open~ {{ code $E:HOME'/app' }}\n\
+                 edit:add-var todo~ {{ $E:EDITOR '{}' }}\n",
+                todo
+            ),
+            String::from_utf8(out).unwrap()
+        );
+
+        let args: Vec<String> = ["--shell", "elvish", "--with-vars"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let (output, _) = AliasOutput::from_args(&args)?;
+        let mut out = Vec::new();
+        generate_aliases(&config, &output, &mut out)?;
+        assert!(String::from_utf8(out).unwrap().ends_with(&format!(
+            "set-env DIR_DOCS ~/'My Documents'\n\
+             set-env DIR_HERE '/home/me/it''s-here'\n\
+             set-env DIR_OPEN $E:HOME'/app'\n\
+             set-env DIR_TODO '{}'\n",
+            todo
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_aliases_for_tcsh() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(
+            &config_path,
+            "[docs]~/My Documents\n[here]/home/me/it's-here\n[quote]/home/me/say \\\"hi\\\"\n\
+             /some/work\n[wow]/some/wow!\n[gs]{cd %p && echo hi!}/some/repo\n",
+        )
+        .map_err(|e| e.to_string())?;
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let args: Vec<String> = ["--shell", "tcsh", "--with-unalias", "--with-vars"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let (output, _) = AliasOutput::from_args(&args)?;
+        let mut out = Vec::new();
+        generate_aliases(&config, &output, &mut out)?;
+        assert_eq!(
+            r#"unalias docs
+unalias gs
+unalias here
+unalias quote
+unalias work
+unalias wow
+alias docs 'cd ~/"My Documents"'
+alias gs 'cd "/some/repo" && echo hi\!'
+alias here 'cd "/home/me/it'\''s-here"'
+alias quote 'cd "/home/me/say "\""hi"\"""'
+alias work 'cd "/some/work"'
+alias wow 'cd "/some/wow"\!""'
+setenv DIR_DOCS ~/"My Documents"
+setenv DIR_GS "/some/repo"
+setenv DIR_HERE "/home/me/it's-here"
+setenv DIR_QUOTE "/home/me/say "\""hi"\"""
+setenv DIR_WORK "/some/work"
+setenv DIR_WOW "/some/wow"\!""
+"#,
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_aliases_quotes_paths() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let config_path = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(
+            &config_path,
+            "[docs]~/My Documents\n[here]/home/me/it's-here\n[open]{code %p}$HOME/app\n",
+        )
+        .map_err(|e| e.to_string())?;
+        let mut config =
+            Configuration::from_path(config_path.display().to_string(), ParserOptions::default())?;
+        config.process_input()?;
+
+        let mut out = Vec::new();
+        generate_aliases(&config, &AliasOutput::default(), &mut out)?;
+        assert_eq!(
+            "alias docs='cd ~/\"My Documents\"'\nalias here='cd \"/home/me/it'\"'\"'s-here\"'\nalias open='code \"$HOME/app\"'\n",
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_quiet_suppresses_warnings_but_not_aliases() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join(CONFIG_FILE);
+        write(&file, "\u{200b}[work]/some/work/path\n").map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> Result<(Vec<u8>, Vec<u8>), String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            Command::run_with_writers(&args, &mut out, &mut err)?;
+            Ok((out, err))
+        };
+        let normal = run(&["dalia", "aliases"]);
+        let quiet = run(&["dalia", "--quiet", "aliases"]);
+        let short = run(&["dalia", "aliases", "-q"]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        let expected = "alias work='cd \"/some/work/path\"'\n";
+        let (out, err) = normal?;
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+        assert!(String::from_utf8(err).unwrap().contains("zero-width"));
+        for (out, err) in [quiet?, short?] {
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+            assert!(err.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_resolves_relative_paths() -> Result<(), String> {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let file = dir.join(CONFIG_FILE);
+        write(&file, "projects/foo\n").map_err(|e| e.to_string())?;
+
+        env::set_var(DALIA_CONFIG_FILE_ENV_VAR, &file);
+        let run = |args: &[&str]| -> Result<String, String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let mut out = Vec::new();
+            Command::run_with_writers(&args, &mut out, &mut Vec::new())?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+        let default = run(&["dalia", "aliases"]);
+        let base = run(&["dalia", "aliases", "--base", "/home/me"]);
+        env::remove_var(DALIA_CONFIG_FILE_ENV_VAR);
+
+        assert_eq!(
+            format!(
+                "alias foo='cd \"{}\"'\n",
+                dir.join("projects/foo").display()
+            ),
+            default?
+        );
+        assert_eq!("alias foo='cd \"/home/me/projects/foo\"'\n", base?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_with_writer_captures_output() -> Result<(), String> {
+        let args: Vec<String> = vec!["dalia".into(), "version".into()];
+        let mut out: Vec<u8> = Vec::new();
+        Command::run_with_writer(&args, &mut out)?;
+        assert_eq!(
+            format!("dalia version {}\n", VERSION.unwrap()),
+            String::from_utf8(out).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_with_writer_rejects_extra_arguments() {
+        let args: Vec<String> = vec!["dalia".into(), "version".into(), "extra".into()];
+        let mut out: Vec<u8> = Vec::new();
+        assert_eq!(
+            Err("wrong number of arguments provided.".to_string()),
+            Command::run_with_writer(&args, &mut out)
+        );
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_bash_completion_script_mentions_each_command() {
+        let script = completion_script(ShellFormat::Bash).unwrap();
+        for command in COMMAND_NAMES.iter() {
+            assert!(script.contains(command), "missing {}", command);
+        }
+        assert!(script.contains("complete -F _dalia dalia"));
+    }
+
+    #[test]
+    fn test_completion_scripts_complete_shell_names() {
+        for shell in ShellFormat::COMPLETIONS.iter() {
+            let script = completion_script(*shell).unwrap();
+            assert!(script.contains("bash zsh fish"), "{}", shell);
+        }
+        assert_eq!(
+            Err("completions aren't available for posix".to_string()),
+            completion_script(ShellFormat::Posix)
+        );
+    }
+
+    #[test]
+    fn test_bash_completion_script_completes_command_names() -> Result<(), String> {
+        let script = completion_script(ShellFormat::Bash)?;
+        assert!(script.contains(&format!("compgen -W \"{}\"", COMMAND_NAMES.join(" "))));
+        for command in COMMAND_NAMES.iter() {
+            assert!(script.contains(command), "{}", command);
+        }
+        assert!(!script.contains("debug"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_script_for_each_shell() -> Result<(), String> {
+        assert_eq!(
+            "eval \"$(dalia aliases)\"\n",
+            init_script(ShellFormat::Bash)?
+        );
+        assert_eq!(
+            "eval \"$(dalia aliases --shell zsh)\"\n",
+            init_script(ShellFormat::Zsh)?
+        );
+        assert_eq!(
+            "dalia aliases --shell fish | source\n",
+            init_script(ShellFormat::Fish)?
+        );
+        assert_eq!(
+            "eval (dalia aliases --shell elvish | slurp)\n",
+            init_script(ShellFormat::Elvish)?
+        );
+        assert_eq!(
+            Err("init isn't available for nushell".to_string()),
+            init_script(ShellFormat::Nushell)
+        );
+
+        let args: Vec<String> = vec!["dalia".into(), "init".into(), "zsh".into()];
+        let mut out = Vec::new();
+        Command::run_with_writer(&args, &mut out)?;
+        assert_eq!(init_script(ShellFormat::Zsh)?.as_bytes(), &out[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_alias_for_each_shell() {
+        let cases = [
+            (ShellFormat::Bash, "alias foo='cd /bar'\n"),
+            (ShellFormat::Zsh, "alias foo='cd /bar'\n"),
+            (ShellFormat::Posix, "alias foo='cd /bar'\n"),
+            (ShellFormat::Fish, "function foo\n  cd /bar\nend\n"),
+            (ShellFormat::Nushell, "def --env foo [] { cd /bar }\n"),
+            (ShellFormat::Powershell, "function foo { cd /bar }\n"),
+            (ShellFormat::Elvish, "edit:add-var foo~ { cd /bar }\n"),
+            (ShellFormat::Tcsh, "alias foo 'cd /bar'\n"),
+            (ShellFormat::Cmd, "foo=cd /bar\n"),
+        ];
+        for (shell, expected) in cases.iter() {
+            assert_eq!(
+                *expected,
+                format_alias("foo", "cd /bar", *shell),
+                "{}",
+                shell
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_alias_escapes_apostrophes() {
+        assert_eq!(
+            "alias here='cd \"/home/me/it'\"'\"'s-here\"'\n",
+            format_alias("here", "cd \"/home/me/it's-here\"", ShellFormat::Bash)
+        );
+        assert_eq!(
+            "function here\n  cd \"/home/me/it's-here\"\nend\n",
+            format_alias("here", "cd \"/home/me/it's-here\"", ShellFormat::Fish)
+        );
+    }
+
+    #[test]
+    fn test_parser_options_from_flags() -> Result<(), String> {
+        assert!(!parser_options(&[])?.preserve_case);
+        assert!(parser_options(&["--preserve-case".to_string()])?.preserve_case);
+        assert!(!parser_options(&[])?.include_hidden);
+        assert!(parser_options(&["--include-hidden".to_string()])?.include_hidden);
+        assert!(!parser_options(&[])?.include_unreadable);
+        assert!(parser_options(&["--include-unreadable".to_string()])?.include_unreadable);
+        assert!(parser_options(&[])?.follow_symlinks);
+        assert!(!parser_options(&["--no-follow-symlinks".to_string()])?.follow_symlinks);
+        assert!(parser_options(&["--disambiguate".to_string()])?.disambiguate);
+        assert!(!parser_options(&[])?.canonicalize);
+        assert!(parser_options(&["--canonicalize".to_string()])?.canonicalize);
+        assert!(parser_options(&[])?.expand_glob);
+        assert!(parser_options(&["--no-expand-glob".to_string()]).is_err());
+        assert_eq!(
+            Some(PathBuf::from("/home/me")),
+            parser_options(&["--base".to_string(), "/home/me".to_string()])?.base
+        );
+        assert_eq!(
+            Some("missing value for --base".to_string()),
+            parser_options(&["--base".to_string()]).err()
+        );
+        assert_eq!(
+            25,
+            parser_options(&["--max-glob".to_string(), "25".to_string()])?.max_glob_aliases
+        );
+        assert_eq!(
+            Some("invalid value for --max-glob: many".to_string()),
+            parser_options(&["--max-glob".to_string(), "many".to_string()]).err()
+        );
+        assert_eq!(
+            crate::parser::DEFAULT_FILE_COMMAND,
+            parser_options(&[])?.file_command
+        );
+        assert_eq!(
+            "code --wait",
+            parser_options(&["--file-command".to_string(), "code --wait".to_string()])?
+                .file_command
+        );
+        assert_eq!(
+            "unknown flag: --nope",
+            parser_options(&["--nope".to_string()]).unwrap_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_615_734_566);
+        assert_eq!("2021-03-14 15:09:26 UTC", format_timestamp(time));
+    }
+
+    #[test]
+    fn test_version_json() -> Result<(), String> {
+        let args: Vec<String> = vec!["dalia".into(), "version".into(), "--json".into()];
+        let mut out = Vec::new();
+        Command::run_with_writer(&args, &mut out)?;
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with('{') && out.ends_with("}\n"), "{}", out);
+        assert!(out.contains(&format!("\"version\":\"{}\"", env!("CARGO_PKG_VERSION"))));
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+        assert!(out.contains(&format!("\"profile\":\"{}\"", profile)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_max_aliases() {
+        assert_eq!(Ok(25), parse_max_aliases("25"));
+        assert_eq!(Ok(25), parse_max_aliases(" 25\n"));
+        assert_eq!(
+            Err("invalid value for DALIA_MAX_ALIASES: lots".to_string()),
+            parse_max_aliases("lots")
+        );
+        assert!(parse_max_aliases("-1").is_err());
     }
 }
@@ -0,0 +1,188 @@
+use std::fmt::Formatter;
+use std::io;
+use std::path::PathBuf;
+
+/// DaliaError describes everything that can go wrong while turning a configuration into aliases.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DaliaError {
+    /// The configuration doesn't follow dalia's grammar.
+    ParseError(String),
+    /// The configuration holds nothing but whitespace.
+    EmptyInput,
+    /// The configuration's first token couldn't be lexed.
+    LexError(String),
+    /// A path named in the configuration couldn't be read.
+    IoError(PathBuf, io::ErrorKind),
+    /// The directory of a `[*]` line on the given line couldn't be read.
+    InvalidGlob(PathBuf, usize, io::ErrorKind),
+    /// Aliases whose `@alias` references lead back to themselves, in the order they're followed.
+    CircularReference(Vec<String>),
+    /// Any of the other errors, found at the given line and column.
+    Located(usize, usize, Box<DaliaError>),
+    /// Any of the other errors, found in the given configuration file.
+    InConfig(PathBuf, Box<DaliaError>),
+}
+
+impl std::fmt::Display for DaliaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DaliaError::ParseError(msg) | DaliaError::LexError(msg) => write!(f, "{}", msg),
+            DaliaError::EmptyInput => write!(f, "no config file found to parse"),
+            DaliaError::IoError(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            DaliaError::InvalidGlob(path, line, e) => write!(
+                f,
+                "couldn't expand {} on line {}: {}",
+                path.display(),
+                line,
+                e
+            ),
+            DaliaError::CircularReference(aliases) => {
+                write!(f, "circular alias reference: {}", aliases.join(" -> "))
+            }
+            DaliaError::Located(line, column, e) => {
+                write!(f, "{} at line {}, column {}", e, line, column)
+            }
+            DaliaError::InConfig(path, e) => {
+                let (at, e) = match e.as_ref() {
+                    DaliaError::Located(line, _, e) => (format!(" at line {}", line), e.as_ref()),
+                    e => (String::new(), e),
+                };
+                match e {
+                    DaliaError::ParseError(msg) | DaliaError::LexError(msg) => {
+                        write!(f, "parse error in {}{}: {}", path.display(), at, msg)
+                    }
+                    e => write!(f, "error in {}{}: {}", path.display(), at, e),
+                }
+            }
+        }
+    }
+}
+
+impl DaliaError {
+    /// Returns the line and column the error was found at, when it's known.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            DaliaError::Located(line, column, _) => Some((*line, *column)),
+            DaliaError::InConfig(_, e) => e.location(),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for DaliaError {}
+
+/// DaliaWarning describes a problem in a configuration that dalia works around rather than
+/// failing on, for library users who need to tell one problem from another.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DaliaWarning {
+    /// The configuration's `version = N` header names a newer format than this parser reads.
+    UnsupportedVersion { found: u32, max_supported: u32 },
+}
+
+impl std::fmt::Display for DaliaWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DaliaWarning::UnsupportedVersion {
+                found,
+                max_supported,
+            } => write!(
+                f,
+                "format version {} is newer than the supported version {}; some lines may not be understood",
+                found, max_supported
+            ),
+        }
+    }
+}
+
+impl From<String> for DaliaError {
+    fn from(msg: String) -> Self {
+        DaliaError::ParseError(msg)
+    }
+}
+
+impl From<DaliaError> for String {
+    fn from(e: DaliaError) -> Self {
+        e.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_parse_error() {
+        let e = DaliaError::ParseError("expecting PATH; found <'[', LBRACK>".into());
+        assert_eq!("expecting PATH; found <'[', LBRACK>", e.to_string());
+    }
+
+    #[test]
+    fn test_display_io_error() {
+        let e = DaliaError::IoError(PathBuf::from("/some/path"), io::ErrorKind::PermissionDenied);
+        assert_eq!("couldn't read /some/path: permission denied", e.to_string());
+    }
+
+    #[test]
+    fn test_display_invalid_glob() {
+        let e = DaliaError::InvalidGlob(PathBuf::from("/some/path"), 3, io::ErrorKind::NotFound);
+        assert_eq!(
+            "couldn't expand /some/path on line 3: entity not found",
+            e.to_string()
+        );
+    }
+
+    #[test]
+    fn test_display_in_config() {
+        let file = PathBuf::from("/home/me/.config/dalia/config");
+        let e = DaliaError::InConfig(
+            file.clone(),
+            Box::new(DaliaError::ParseError(
+                "missing path after the alias on line 3".into(),
+            )),
+        );
+        assert_eq!(
+            "parse error in /home/me/.config/dalia/config: missing path after the alias on line 3",
+            e.to_string()
+        );
+
+        let e = DaliaError::InConfig(
+            file,
+            Box::new(DaliaError::InvalidGlob(
+                PathBuf::from("/code"),
+                2,
+                io::ErrorKind::NotFound,
+            )),
+        );
+        assert_eq!(
+            "error in /home/me/.config/dalia/config: couldn't expand /code on line 2: entity not found",
+            e.to_string()
+        );
+    }
+
+    #[test]
+    fn test_display_circular_reference() {
+        let e = DaliaError::CircularReference(vec!["a".into(), "b".into(), "a".into()]);
+        assert_eq!("circular alias reference: a -> b -> a", e.to_string());
+    }
+
+    #[test]
+    fn test_display_located() {
+        let e = DaliaError::Located(
+            3,
+            7,
+            Box::new(DaliaError::ParseError("expecting PATH; found <EOF>".into())),
+        );
+        assert_eq!(
+            "expecting PATH; found <EOF> at line 3, column 7",
+            e.to_string()
+        );
+        assert_eq!(Some((3, 7)), e.location());
+
+        let e = DaliaError::InConfig(PathBuf::from("/config"), Box::new(e));
+        assert_eq!(
+            "parse error in /config at line 3: expecting PATH; found <EOF>",
+            e.to_string()
+        );
+        assert_eq!(Some((3, 7)), e.location());
+    }
+}
@@ -0,0 +1,218 @@
+//! Rewrites a configuration in dalia's canonical style, which is what `dalia format` prints.
+
+use crate::lexer::{is_alias_char, is_alias_name_char, ZERO_WIDTH_CHARS};
+
+/// Line is a single line of a configuration, already written in canonical form.
+enum Line {
+    /// A line that affects the lines after it, like `[section: name]` or `$name = value`, which
+    /// nothing can move past.
+    Header(String),
+    /// A line that creates an alias, along with the name it's sorted by.
+    Alias { key: String, text: String },
+    /// A `[*]` or `!name` line, which keeps its place after the aliases around it.
+    Other(String),
+}
+
+/// The aliases and other lines between two headers.
+#[derive(Default)]
+struct Block {
+    aliases: Vec<(String, String)>,
+    others: Vec<String>,
+}
+
+impl Block {
+    /// Writes the block's aliases, sorted by name, and then its other lines to `formatted`,
+    /// leaving the block empty.
+    fn flush_into(&mut self, formatted: &mut String) {
+        // The sort is stable, so lines for the same alias keep the order that decides which
+        // one wins.
+        self.aliases.sort_by(|a, b| a.0.cmp(&b.0));
+        for line in self.aliases.drain(..).map(|(_, text)| text) {
+            push_line(formatted, &line);
+        }
+        for line in self.others.drain(..) {
+            push_line(formatted, &line);
+        }
+    }
+}
+
+/// Rewrites `contents` in canonical form: one entry per line without any blank lines or
+/// whitespace around it, a blank line before each section, and the aliases between two lines
+/// that affect the ones after them, like `[section: name]`, `$name = value`, or `@case`, sorted
+/// by name. `[*]` and `!name` lines keep their order after the aliases they're with.
+pub fn format_config(contents: &str) -> String {
+    let mut formatted = String::with_capacity(contents.len());
+    let mut block = Block::default();
+    let lines = contents.lines().map(trim).filter(|line| !line.is_empty());
+    for (i, line) in lines.enumerate() {
+        match classify(line, i == 0) {
+            Line::Header(text) => {
+                block.flush_into(&mut formatted);
+                if text.starts_with("[section:") && !formatted.is_empty() {
+                    formatted.push('\n');
+                }
+                push_line(&mut formatted, &text);
+            }
+            Line::Alias { key, text } => block.aliases.push((key, text)),
+            Line::Other(text) => block.others.push(text),
+        }
+    }
+    block.flush_into(&mut formatted);
+    formatted
+}
+
+fn push_line(formatted: &mut String, line: &str) {
+    formatted.push_str(line);
+    formatted.push('\n');
+}
+
+/// Removes the whitespace and zero-width characters around a line.
+fn trim(line: &str) -> &str {
+    line.trim_matches(|c: char| c.is_whitespace() || ZERO_WIDTH_CHARS.contains(&c))
+}
+
+/// Works out what a trimmed, non-empty line does and writes it in canonical form. Only the
+/// first line of a configuration can be its `version = N` header.
+fn classify(line: &str, first: bool) -> Line {
+    if let Some(name) = section_name(line) {
+        return match name {
+            "" => Line::Header("[section:]".to_string()),
+            name => Line::Header(format!("[section: {}]", name)),
+        };
+    }
+    match assignment(line) {
+        Some(("version", value)) if first => {
+            return Line::Header(format!("version = {}", value));
+        }
+        _ => {}
+    }
+    if line == "@case" || line.starts_with("@case ") {
+        return Line::Header(line.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+
+    let (guard, rest) = split_guard(line);
+    let with_guard = |text: String| match guard {
+        Some(guard) => format!("{} {}", guard, text),
+        None => text,
+    };
+    if let Some((name, value)) = rest.strip_prefix('$').and_then(assignment) {
+        if name.chars().all(is_alias_char) {
+            return Line::Header(with_guard(format!("${} = {}", name, value)));
+        }
+    }
+    if rest.starts_with('!') {
+        return Line::Other(with_guard(rest.to_string()));
+    }
+    if let Some((name, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+        let text = with_guard(format!("[{}]{}", name, after.trim_start()));
+        if name.contains('*') {
+            return Line::Other(text);
+        }
+        let key = name.split(':').next().unwrap_or(name).trim().to_lowercase();
+        return Line::Alias { key, text };
+    }
+    match assignment(rest) {
+        Some((name, value)) if name.chars().all(is_alias_name_char) => Line::Alias {
+            key: name.to_lowercase(),
+            text: with_guard(format!("{} = {}", name, value)),
+        },
+        _ => Line::Alias {
+            key: derived_name(rest),
+            text: with_guard(rest.to_string()),
+        },
+    }
+}
+
+/// Returns the name in a `[section: name]` line, or an empty name for `[section:]`, or `None`
+/// when the line isn't a section.
+fn section_name(line: &str) -> Option<&str> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim_start();
+    let name = inner
+        .strip_prefix("section")?
+        .trim_start()
+        .strip_prefix(':')?;
+    Some(name.trim())
+}
+
+/// Splits a `name = value` line into its trimmed name and value, when the name is a single
+/// word.
+fn assignment(line: &str) -> Option<(&str, &str)> {
+    let (name, value) = line.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((name, value.trim()))
+}
+
+/// Splits an `@os` guard off the start of a line, returning the rest of the line after it.
+fn split_guard(line: &str) -> (Option<&str>, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((guard, rest))
+            if guard.len() > 1
+                && guard.starts_with('@')
+                && guard[1..].chars().all(is_alias_char) =>
+        {
+            (Some(guard), rest.trim_start())
+        }
+        _ => (None, line),
+    }
+}
+
+/// Approximates the name of the alias that's derived from the path on a line, for sorting:
+/// the lowercase last component of the path, before any description, fallback, or exclusion.
+fn derived_name(line: &str) -> String {
+    let path = [" \"", " |", " !"]
+        .iter()
+        .fold(line, |path, separator| {
+            path.split(separator).next().unwrap_or(path)
+        })
+        .trim_end_matches('/');
+    path.rsplit('/').next().unwrap_or(path).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_config_sorts_aliases_and_trims_lines() {
+        let config = "\n  [web]  ~/code/web \n\n~/Desktop\t\n\u{200b}[api:pushd]/code/api\n\
+                      notes = ~/notes \"my notes\"\n";
+        assert_eq!(
+            "[api:pushd]/code/api\n~/Desktop\nnotes = ~/notes \"my notes\"\n[web]~/code/web\n",
+            format_config(config)
+        );
+    }
+
+    #[test]
+    fn test_format_config_keeps_headers_in_place() {
+        let config = "version =1\n$work=/home/me/work\n[zz]$work/zz\n[aa]$work/aa\n\
+                      [section:work ]\n/repos/web\n/repos/api\n[ section : ]\n\
+                      @case preserve\n/b/Beta\n/a/Alpha\n";
+        assert_eq!(
+            "version = 1\n$work = /home/me/work\n[aa]$work/aa\n[zz]$work/zz\n\
+             \n[section: work]\n/repos/api\n/repos/web\n\
+             \n[section:]\n@case preserve\n/a/Alpha\n/b/Beta\n",
+            format_config(config)
+        );
+    }
+
+    #[test]
+    fn test_format_config_keeps_globs_and_exclusions_after_aliases() {
+        let config = "!archive\n[*]~/code\n[web]~/web\n@linux   [music]/mnt/music\n\
+                      @macos [music]/Volumes/Music\n[**2!target]  ~/work\n";
+        assert_eq!(
+            "@linux [music]/mnt/music\n@macos [music]/Volumes/Music\n[web]~/web\n!archive\n\
+             [*]~/code\n[**2!target]~/work\n",
+            format_config(config)
+        );
+    }
+
+    #[test]
+    fn test_format_config_is_idempotent() {
+        let config = "[section: b]\n/z\n/y\n[*]/code\n";
+        let formatted = format_config(config);
+        assert_eq!(formatted, format_config(&formatted));
+    }
+}
@@ -1,7 +1,28 @@
 use std::borrow::Cow;
 use std::fmt::Formatter;
 
-const TOKEN_NAMES: [&str; 7] = ["n/a", "<EOF>", "LBRACK", "RBRACK", "ALIAS", "PATH", "GLOB"];
+use crate::error::DaliaError;
+
+const TOKEN_NAMES: [&str; 18] = [
+    "n/a",
+    "<EOF>",
+    "LBRACK",
+    "RBRACK",
+    "ALIAS",
+    "PATH",
+    "GLOB",
+    "DIRECTIVE",
+    "VARIABLE",
+    "EQUALS",
+    "COLON",
+    "PATTERN",
+    "VERSION",
+    "BANG",
+    "TEMPLATE",
+    "REF",
+    "SECTION",
+    "DESCRIPTION",
+];
 
 pub const TOKEN_EOF: i32 = 1;
 pub const TOKEN_LBRACK: i32 = 2;
@@ -9,28 +30,101 @@ pub const TOKEN_RBRACK: i32 = 3;
 pub const TOKEN_ALIAS: i32 = 4;
 pub const TOKEN_PATH: i32 = 5;
 pub const TOKEN_GLOB: i32 = 6;
+pub const TOKEN_DIRECTIVE: i32 = 7;
+pub const TOKEN_VARIABLE: i32 = 8;
+pub const TOKEN_EQUALS: i32 = 9;
+pub const TOKEN_COLON: i32 = 10;
+pub const TOKEN_PATTERN: i32 = 11;
+pub const TOKEN_VERSION: i32 = 12;
+pub const TOKEN_BANG: i32 = 13;
+pub const TOKEN_TEMPLATE: i32 = 14;
+pub const TOKEN_REF: i32 = 15;
+pub const TOKEN_SECTION: i32 = 16;
+pub const TOKEN_DESCRIPTION: i32 = 17;
 
-const EOF: char = !0 as char;
+/// Stands for the end of the input. It's a noncharacter, which text never contains, unlike
+/// `!0 as char`, which is `ÿ`.
+const EOF: char = '\u{ffff}';
 
 const UNDERSCORE: char = '_';
 const HYPHEN: char = '-';
+const DOT: char = '.';
 const ASTERISK: char = '*';
+const AT: char = '@';
+const DOLLAR: char = '$';
+const EQUALS: char = '=';
+const COLON: char = ':';
+const BANG: char = '!';
+const LBRACE: char = '{';
+const RBRACE: char = '}';
+const QUOTE: char = '"';
+const LPAREN: char = '(';
+const RPAREN: char = ')';
+const GLOB_ALL: char = 'a';
+const GLOB_FILES: char = 'f';
+const VERSION_KEYWORD: &str = "version";
+const SECTION_KEYWORD: &str = "section";
+
+/// Invisible characters that editors occasionally leave behind and that carry no meaning in a
+/// configuration file.
+pub(crate) const ZERO_WIDTH_CHARS: [char; 5] =
+    ['\u{200b}', '\u{200c}', '\u{200d}', '\u{2060}', '\u{feff}'];
 
 /// Token identifies a text and the kind of token it represents.
-#[derive(Debug, Eq, PartialEq)]
+///
+/// Two tokens are equal when their kind and text are, regardless of where they were found.
+#[derive(Clone, Debug)]
 pub struct Token<'a> {
     /// The specific atom this token represents.
     pub kind: i32,
     /// The particular text associated with this token when it was parsed.
     pub text: Cow<'a, String>,
+    /// The byte offset in the input where this token starts.
+    start: usize,
+    /// The byte offset in the input just past the end of this token.
+    end: usize,
 }
 
 impl<'a> Token<'a> {
     pub fn new(kind: i32, text: Cow<'a, String>) -> Self {
-        Self { kind, text }
+        Self {
+            kind,
+            text,
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Returns this token with its location in the input set to the bytes `start..end`.
+    pub fn with_span(self, start: usize, end: usize) -> Self {
+        Self { start, end, ..self }
+    }
+
+    /// Returns the byte offsets where this token starts and ends in the input, which is `(0, 0)`
+    /// for tokens that weren't produced by a lexer.
+    pub fn span(&self) -> (usize, usize) {
+        (self.start, self.end)
+    }
+
+    /// Returns a copy of this token that owns its text, so it can outlive the input.
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            kind: self.kind,
+            text: Cow::Owned(self.text.into_owned()),
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+impl<'a> PartialEq for Token<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.text == other.text
     }
 }
 
+impl<'a> Eq for Token<'a> {}
+
 impl<'a> std::fmt::Display for Token<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "<'{}', {}>", self.text, TOKEN_NAMES[self.kind as usize])
@@ -38,14 +132,19 @@ impl<'a> std::fmt::Display for Token<'a> {
 }
 
 /// Cursor allows traversing through an input String character by character while lexing.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Cursor {
     /// The input String being processed.
     input: String,
-    /// A pointer to the current character.
+    /// The characters of the input along with the byte offsets where they start, so that moving
+    /// to a character doesn't have to decode everything before it.
+    chars: Vec<(usize, char)>,
+    /// A pointer to the current character, counted in characters rather than bytes.
     pointer: usize,
     /// The current character being processed.
     current_char: char,
+    /// The line number of the current character, starting from 1.
+    line: usize,
 }
 
 impl Cursor {
@@ -53,27 +152,57 @@ impl Cursor {
     fn new(input: &str, pointer: usize, c: char) -> Self {
         Self {
             input: input.to_string(),
+            chars: input.char_indices().collect(),
             pointer,
             current_char: c,
+            line: 1,
         }
     }
 
+    /// Returns the character `n` positions after the current one without consuming anything.
+    fn peek(&self, n: usize) -> char {
+        self.chars.get(self.pointer + n).map_or(EOF, |&(_, c)| c)
+    }
+
+    /// Returns the byte offset of the character at position `pointer`.
+    fn byte_offset(&self, pointer: usize) -> usize {
+        self.chars
+            .get(pointer)
+            .map_or(self.input.len(), |&(i, _)| i)
+    }
+
     /// Consumes one character moving forward and detects "end of file".
     fn consume(&mut self) {
-        self.pointer += 1;
-        if self.pointer >= self.input.len() {
-            self.current_char = EOF;
-        } else if let Some(c) = self.input.chars().nth(self.pointer) {
-            self.current_char = c
+        if self.current_char == '\n' {
+            self.line += 1;
         }
+        self.pointer += 1;
+        self.current_char = self.chars.get(self.pointer).map_or(EOF, |&(_, c)| c);
     }
 }
 
 /// Creates and identifies tokens using the underlying cursor.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Lexer<'a> {
     pub cursor: Cursor,
     token_names: Vec<&'a str>,
+    /// Non-fatal problems found while lexing the input.
+    warnings: Vec<String>,
+    /// The line number where the most recently returned token starts.
+    token_line: usize,
+    /// Whether the rest of the current line, following an `=`, is a path.
+    path_follows: bool,
+    /// Whether the cursor is between a `[` and its `]`.
+    in_brackets: bool,
+    /// Whether the rest of the brackets, following a glob and a `:` or a `!`, is a directory name
+    /// pattern.
+    pattern_follows: bool,
+    /// The kind of the most recently returned token.
+    last_kind: i32,
+    /// The position of the character where the token being lexed starts.
+    token_start: usize,
+    /// The position just past the token being lexed, when it isn't where the cursor stopped.
+    token_end: Option<usize>,
 }
 
 impl<'a> Lexer<'a> {
@@ -81,55 +210,292 @@ impl<'a> Lexer<'a> {
         Self {
             cursor: Cursor::new(input, pointer, c),
             token_names: Vec::from(TOKEN_NAMES),
+            warnings: Vec::new(),
+            token_line: 1,
+            path_follows: false,
+            in_brackets: false,
+            pattern_follows: false,
+            last_kind: 0,
+            token_start: 0,
+            token_end: None,
         }
     }
 
+    /// Returns the input being lexed.
+    pub fn input(&self) -> &str {
+        &self.cursor.input
+    }
+
+    /// Moves back to the start of the input, so that the next token is the first one again.
+    pub fn reset(&mut self) {
+        let input = std::mem::take(&mut self.cursor.input);
+        let c = input.chars().next().unwrap_or(EOF);
+        *self = Lexer::new(&input, 0, c);
+    }
+
+    /// Returns the line number where the most recently returned token starts.
+    pub fn line(&self) -> usize {
+        self.token_line
+    }
+
+    /// Returns the warnings collected so far, leaving none behind.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
     pub fn token_names(&self, i: usize) -> String {
         self.token_names[i].to_string()
     }
 
+    /// Reports whether the cursor is still on the current line, which ends at a `\n` or at the
+    /// `\r\n` of a file written on Windows.
     fn is_not_end_line(&self) -> bool {
-        !matches!(self.cursor.current_char, '\u{ff}' | '\0' | '\n')
+        match self.cursor.current_char {
+            EOF | '\0' | '\n' => false,
+            '\r' => self.cursor.peek(1) != '\n',
+            _ => true,
+        }
     }
 
     fn is_alias_name(&self) -> bool {
-        self.cursor.current_char.is_ascii_alphanumeric()
-            || self.cursor.current_char == UNDERSCORE
-            || self.cursor.current_char == HYPHEN
+        is_alias_name_char(self.cursor.current_char)
     }
 
     fn is_glob_alias(&self) -> bool {
         self.cursor.current_char == ASTERISK
     }
 
+    /// Detects a glob with a prefix for the names of the aliases it creates, like `work-*`, right
+    /// after the opening bracket.
+    fn is_prefixed_glob(&self) -> bool {
+        if !self.in_brackets || self.last_kind != TOKEN_LBRACK {
+            return false;
+        }
+        let mut i = 0;
+        while is_alias_name_char(self.cursor.peek(i)) {
+            i += 1;
+        }
+        i > 0 && self.cursor.peek(i) == ASTERISK
+    }
+
+    /// Detects a `version = 1` header, which is only recognized as the first line of the input so
+    /// that an alias can still be named `version`.
+    fn is_version_header(&self) -> bool {
+        if self.last_kind != 0 {
+            return false;
+        }
+        let mut i = 0;
+        for c in VERSION_KEYWORD.chars() {
+            if self.cursor.peek(i) != c {
+                return false;
+            }
+            i += 1;
+        }
+        while matches!(self.cursor.peek(i), ' ' | '\t') {
+            i += 1;
+        }
+        if self.cursor.peek(i) != EQUALS {
+            return false;
+        }
+        i += 1;
+        while matches!(self.cursor.peek(i), ' ' | '\t') {
+            i += 1;
+        }
+        let digits = i;
+        while self.cursor.peek(i).is_ascii_digit() {
+            i += 1;
+        }
+        while matches!(self.cursor.peek(i), ' ' | '\t' | '\r') {
+            i += 1;
+        }
+        i > digits && matches!(self.cursor.peek(i), '\n' | EOF)
+    }
+
+    /// Detects a line like `[section: work]`, which is the only thing on its line so that an
+    /// alias can still be named `section` with a command, as in `[section:pushd]/path`.
+    fn is_section(&self) -> bool {
+        let mut i = 1;
+        while matches!(self.cursor.peek(i), ' ' | '\t') {
+            i += 1;
+        }
+        for c in SECTION_KEYWORD.chars() {
+            if self.cursor.peek(i) != c {
+                return false;
+            }
+            i += 1;
+        }
+        while matches!(self.cursor.peek(i), ' ' | '\t') {
+            i += 1;
+        }
+        if self.cursor.peek(i) != COLON {
+            return false;
+        }
+        i += 1;
+        while is_alias_name_char(self.cursor.peek(i)) || matches!(self.cursor.peek(i), ' ' | '\t') {
+            i += 1;
+        }
+        if self.cursor.peek(i) != ']' {
+            return false;
+        }
+        i += 1;
+        while matches!(self.cursor.peek(i), ' ' | '\t' | '\r') {
+            i += 1;
+        }
+        matches!(self.cursor.peek(i), '\n' | EOF)
+    }
+
+    /// Detects a variable definition like `$name = value`, as opposed to a path that starts with a
+    /// variable reference like `$name/some/path`.
+    fn is_variable_definition(&self) -> bool {
+        let mut i = 1;
+        while is_alias_char(self.cursor.peek(i)) {
+            i += 1;
+        }
+        while matches!(self.cursor.peek(i), ' ' | '\t') {
+            i += 1;
+        }
+        i > 1 && self.cursor.peek(i) == EQUALS
+    }
+
+    /// Detects the start of a Windows path with a drive letter, e.g. `C:\Users`.
+    fn is_drive_letter(&self) -> bool {
+        self.cursor.current_char.is_ascii_alphabetic()
+            && self.cursor.peek(1) == ':'
+            && matches!(self.cursor.peek(2), '\\' | '/')
+    }
+
+    /// Detects a relative path like `projects/api` or `../api`, whose first component would
+    /// otherwise be read as an alias name.
+    fn is_relative_path(&self) -> bool {
+        if self.in_brackets {
+            return false;
+        }
+        let mut i = 0;
+        while is_alias_name_char(self.cursor.peek(i)) {
+            i += 1;
+        }
+        i > 0 && matches!(self.cursor.peek(i), '/' | '\\')
+    }
+
+    fn is_zero_width(&self) -> bool {
+        ZERO_WIDTH_CHARS.contains(&self.cursor.current_char)
+    }
+
+    /// Lexes the rest of the input, returning every token up to and including `<EOF>`.
+    pub fn all_tokens(&mut self) -> Result<Vec<Token<'static>>, DaliaError> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next_token()?.into_owned();
+            let done = token.kind == TOKEN_EOF;
+            tokens.push(token);
+            if done {
+                return Ok(tokens);
+            }
+        }
+    }
+
     pub fn next_token(&mut self) -> Result<Token<'a>, String> {
+        self.token_start = self.cursor.pointer;
+        let token = self.lex_token()?;
+        let end = self.token_end.take().unwrap_or(self.cursor.pointer);
+        self.last_kind = token.kind;
+        let start = self.cursor.byte_offset(self.token_start);
+        Ok(token.with_span(start, self.cursor.byte_offset(end)))
+    }
+
+    fn lex_token(&mut self) -> Result<Token<'a>, String> {
+        if self.path_follows {
+            self.path_follows = false;
+            return Ok(self.path_after_equals());
+        }
+        if self.pattern_follows {
+            self.pattern_follows = false;
+            return Ok(self.pattern());
+        }
         while self.cursor.current_char != EOF {
+            self.token_line = self.cursor.line;
+            self.token_start = self.cursor.pointer;
             match self.cursor.current_char {
                 ' ' | '\t' | '\n' | '\r' => {
                     self.whitespace();
                     continue;
                 }
+                '[' if self.is_section() => return Ok(self.section()),
                 '[' => {
                     self.cursor.consume();
+                    self.in_brackets = true;
                     return Ok(Token::new(TOKEN_LBRACK, Cow::Owned("[".into())));
                 }
                 ']' => {
                     self.cursor.consume();
+                    self.in_brackets = false;
                     return Ok(Token::new(TOKEN_RBRACK, Cow::Owned("]".into())));
                 }
+                COLON if self.in_brackets => {
+                    self.cursor.consume();
+                    self.pattern_follows = self.last_kind == TOKEN_GLOB;
+                    return Ok(Token::new(TOKEN_COLON, Cow::Owned(":".into())));
+                }
+                AT if matches!(self.last_kind, TOKEN_RBRACK | TOKEN_TEMPLATE) => {
+                    self.cursor.consume();
+                    return Ok(self.reference());
+                }
+                AT => {
+                    self.cursor.consume();
+                    return Ok(self.directive());
+                }
+                BANG => {
+                    self.cursor.consume();
+                    self.pattern_follows = self.in_brackets;
+                    return Ok(Token::new(TOKEN_BANG, Cow::Owned("!".into())));
+                }
+                LBRACE if self.last_kind == TOKEN_RBRACK => {
+                    self.cursor.consume();
+                    return self.template();
+                }
+                QUOTE if self.last_kind == TOKEN_PATH => {
+                    self.cursor.consume();
+                    return self.description();
+                }
+                EQUALS => {
+                    self.cursor.consume();
+                    self.path_follows = true;
+                    return Ok(Token::new(TOKEN_EQUALS, Cow::Owned("=".into())));
+                }
+                _ if self.is_version_header() => return Ok(self.version()),
+                DOLLAR if self.is_variable_definition() => {
+                    self.cursor.consume();
+                    return Ok(self.variable());
+                }
+                _ if self.is_zero_width() => {
+                    self.warnings.push(format!(
+                        "skipping zero-width character U+{:04X}",
+                        self.cursor.current_char as u32
+                    ));
+                    self.cursor.consume();
+                    continue;
+                }
                 _ => {
-                    if self.is_alias_name() {
+                    if self.is_drive_letter() || self.is_relative_path() {
+                        return Ok(self.path());
+                    } else if self.is_prefixed_glob() {
+                        return Ok(self.glob());
+                    } else if self.is_alias_name() {
                         return Ok(self.alias());
                     } else if self.is_glob_alias() {
                         return Ok(self.glob());
                     } else if self.is_not_end_line() {
                         return Ok(self.path());
                     }
-                    return Err(format!("invalid character {}", self.cursor.current_char));
+                    let c = self.cursor.current_char;
+                    self.cursor.consume();
+                    return Err(format!("invalid character {}", c));
                 }
             }
         }
 
+        self.token_line = self.cursor.line;
+        self.token_start = self.cursor.pointer;
         Ok(Token::new(TOKEN_EOF, Cow::Owned("<EOF>".into())))
     }
 
@@ -139,6 +505,21 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Lexes a `[section: name]` line, whose token is the section's name.
+    fn section(&mut self) -> crate::lexer::Token<'a> {
+        while self.cursor.current_char != COLON {
+            self.cursor.consume();
+        }
+        self.cursor.consume();
+        let mut s = String::new();
+        while self.cursor.current_char != ']' {
+            s.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
+        self.cursor.consume();
+        Token::new(TOKEN_SECTION, Cow::Owned(s.trim().to_string()))
+    }
+
     fn alias(&mut self) -> crate::lexer::Token<'a> {
         let mut a: String = String::new();
         while self.is_alias_name() {
@@ -148,23 +529,211 @@ impl<'a> Lexer<'a> {
         Token::new(TOKEN_ALIAS, Cow::Owned(a))
     }
 
+    fn directive(&mut self) -> crate::lexer::Token<'a> {
+        let mut d: String = String::new();
+        while is_alias_char(self.cursor.current_char) {
+            d.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
+        Token::new(TOKEN_DIRECTIVE, Cow::Owned(d))
+    }
+
+    fn variable(&mut self) -> crate::lexer::Token<'a> {
+        let mut v: String = String::new();
+        while is_alias_char(self.cursor.current_char) {
+            v.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
+        Token::new(TOKEN_VARIABLE, Cow::Owned(v))
+    }
+
+    /// Lexes a `version = 1` header into a token holding just the number.
+    fn version(&mut self) -> crate::lexer::Token<'a> {
+        while !self.cursor.current_char.is_ascii_digit() {
+            self.cursor.consume();
+        }
+        let mut v = String::new();
+        while self.cursor.current_char.is_ascii_digit() {
+            v.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
+        Token::new(TOKEN_VERSION, Cow::Owned(v))
+    }
+
+    /// Lexes `*`, or `**` followed by an optional maximum depth like `**2`, either of which can end
+    /// with an `a` to include hidden directories and an `f` to include files. The glob can start
+    /// with a prefix and end with a suffix that starts with `-`, `_`, or `.`, like `work-*-dev`.
     fn glob(&mut self) -> crate::lexer::Token<'a> {
         let mut a: String = String::new();
+        while !self.is_glob_alias() {
+            a.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
         a.push(self.cursor.current_char);
         self.cursor.consume();
+        if self.is_glob_alias() {
+            a.push(self.cursor.current_char);
+            self.cursor.consume();
+            while self.cursor.current_char.is_ascii_digit() {
+                a.push(self.cursor.current_char);
+                self.cursor.consume();
+            }
+        }
+        while matches!(self.cursor.current_char, GLOB_ALL | GLOB_FILES)
+            && !a.contains(self.cursor.current_char)
+            && matches!(
+                self.cursor.peek(1),
+                ']' | COLON | BANG | GLOB_ALL | GLOB_FILES | UNDERSCORE | HYPHEN | DOT
+            )
+        {
+            a.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
+        // File extensions the directories have to contain, like `.(rs|py)`.
+        if self.cursor.current_char == DOT && self.cursor.peek(1) == LPAREN {
+            while !matches!(self.cursor.current_char, RPAREN | ']') && self.is_not_end_line() {
+                a.push(self.cursor.current_char);
+                self.cursor.consume();
+            }
+            if self.cursor.current_char == RPAREN {
+                a.push(self.cursor.current_char);
+                self.cursor.consume();
+            }
+        }
+        if matches!(self.cursor.current_char, UNDERSCORE | HYPHEN | DOT) {
+            while self.is_alias_name() {
+                a.push(self.cursor.current_char);
+                self.cursor.consume();
+            }
+        }
         Token::new(TOKEN_GLOB, Cow::Owned(a))
     }
 
+    /// Lexes a directory name pattern like `proj-*`, which runs up to the closing bracket or the
+    /// `!` that starts an exclusion.
+    fn pattern(&mut self) -> crate::lexer::Token<'a> {
+        while matches!(self.cursor.current_char, ' ' | '\t') {
+            self.cursor.consume();
+        }
+        self.token_start = self.cursor.pointer;
+        let mut p = String::new();
+        while !matches!(self.cursor.current_char, ']' | BANG) && self.is_not_end_line() {
+            p.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
+        self.token_end = Some(self.token_start + p.trim_end().chars().count());
+        Token::new(TOKEN_PATTERN, Cow::Owned(p.trim().to_string()))
+    }
+
+    /// Lexes a command template like `{cd %p && git status}`, which runs up to the closing brace.
+    fn template(&mut self) -> Result<crate::lexer::Token<'a>, String> {
+        let mut t = String::new();
+        while self.cursor.current_char != RBRACE {
+            if !self.is_not_end_line() {
+                return Err(format!(
+                    "missing '{}' after the template on line {}",
+                    RBRACE, self.token_line
+                ));
+            }
+            t.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
+        self.cursor.consume();
+        Ok(Token::new(TOKEN_TEMPLATE, Cow::Owned(t.trim().to_string())))
+    }
+
+    /// Lexes a description like `"my main project"` after a path, which runs up to the closing
+    /// quote.
+    fn description(&mut self) -> Result<crate::lexer::Token<'a>, String> {
+        let mut d = String::new();
+        while self.cursor.current_char != QUOTE {
+            if !self.is_not_end_line() {
+                return Err(format!(
+                    "missing '{}' after the description on line {}",
+                    QUOTE, self.token_line
+                ));
+            }
+            d.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
+        self.cursor.consume();
+        Ok(Token::new(
+            TOKEN_DESCRIPTION,
+            Cow::Owned(d.trim().to_string()),
+        ))
+    }
+
+    /// Lexes everything after an `=` up to the end of the line as a path, which is empty when
+    /// nothing follows the `=`.
+    fn path_after_equals(&mut self) -> crate::lexer::Token<'a> {
+        while matches!(self.cursor.current_char, ' ' | '\t') {
+            self.cursor.consume();
+        }
+        self.token_line = self.cursor.line;
+        self.token_start = self.cursor.pointer;
+        if matches!(self.cursor.current_char, '\r' | EOF) || !self.is_not_end_line() {
+            return Token::new(TOKEN_PATH, Cow::Owned(String::new()));
+        }
+        if self.cursor.current_char == AT {
+            self.cursor.consume();
+            return self.reference();
+        }
+        self.path()
+    }
+
+    /// Lexes the alias name in a reference like `@base/web`. The rest of the line, which may be
+    /// empty, follows as a PATH.
+    fn reference(&mut self) -> crate::lexer::Token<'a> {
+        let mut r = String::new();
+        while self.is_alias_name() {
+            r.push(self.cursor.current_char);
+            self.cursor.consume();
+        }
+        self.path_follows = true;
+        Token::new(TOKEN_REF, Cow::Owned(r))
+    }
+
+    /// Lexes a path up to the end of the line, or up to a quote after whitespace that starts a
+    /// description.
     fn path(&mut self) -> crate::lexer::Token<'a> {
         let mut p = String::new();
-        while self.is_not_end_line() {
+        while self.is_not_end_line()
+            && !(self.cursor.current_char == QUOTE && p.ends_with(char::is_whitespace))
+        {
             p.push(self.cursor.current_char);
             self.cursor.consume();
         }
+        // Leading whitespace is skipped before a path starts, but trailing whitespace isn't part
+        // of the path either.
+        p.truncate(p.trim_end().len());
+        self.token_end = Some(self.token_start + p.chars().count());
         Token::new(TOKEN_PATH, Cow::Owned(p))
     }
 }
 
+/// Lexers can be iterated over to get every token in the input, ending with the `<EOF>` token.
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.last_kind == TOKEN_EOF {
+            return None;
+        }
+        Some(self.next_token())
+    }
+}
+
+/// Reports whether `c` can appear in an alias, directive, or variable name.
+pub fn is_alias_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == UNDERSCORE || c == HYPHEN
+}
+
+/// Alias names can also contain dots, like `proj.work`, and letters and digits outside ASCII,
+/// like `résumé`, which directive and variable names can't.
+pub(crate) fn is_alias_name_char(c: char) -> bool {
+    is_alias_char(c) || c == DOT || c.is_alphanumeric()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,10 +746,10 @@ mod tests {
 
     #[test]
     fn test_create_cursor() {
-        let cur = Cursor::new("", 0, !0 as char);
+        let cur = Cursor::new("", 0, EOF);
         assert_eq!("".to_string(), cur.input);
         assert_eq!(0, cur.pointer);
-        assert_eq!(!0 as char, cur.current_char);
+        assert_eq!(EOF, cur.current_char);
     }
 
     #[test]
@@ -198,7 +767,54 @@ mod tests {
         cur.consume();
         assert_eq!("test".to_string(), cur.input);
         assert_eq!(5, cur.pointer);
-        assert_eq!(!0 as char, cur.current_char);
+        assert_eq!(EOF, cur.current_char);
+    }
+
+    #[test]
+    fn test_cursor_steps_over_multi_byte_characters() {
+        let input = "é🦀日ÿ!";
+        let mut cur = Cursor::new(input, 0, 'é');
+        let mut seen = vec![(cur.current_char, cur.byte_offset(cur.pointer))];
+        while cur.current_char != EOF {
+            cur.consume();
+            seen.push((cur.current_char, cur.byte_offset(cur.pointer)));
+        }
+        assert_eq!(
+            vec![
+                ('é', 0),
+                ('🦀', 2),
+                ('日', 6),
+                ('ÿ', 9),
+                ('!', 11),
+                (EOF, 12)
+            ],
+            seen
+        );
+        assert_eq!('日', Cursor::new(input, 0, 'é').peek(2));
+    }
+
+    #[test]
+    fn test_lexer_multi_byte_aliases_and_paths() {
+        let input = "[résumé]/home/me/résumé\n[日本]~/写真/🦀\n/data/ÿ";
+        let tokens = Lexer::new(input, 0, '[').all_tokens().unwrap();
+        let texts: Vec<(i32, &str)> = tokens.iter().map(|t| (t.kind, t.text.as_str())).collect();
+        assert_eq!(
+            vec![
+                (TOKEN_LBRACK, "["),
+                (TOKEN_ALIAS, "résumé"),
+                (TOKEN_RBRACK, "]"),
+                (TOKEN_PATH, "/home/me/résumé"),
+                (TOKEN_LBRACK, "["),
+                (TOKEN_ALIAS, "日本"),
+                (TOKEN_RBRACK, "]"),
+                (TOKEN_PATH, "~/写真/🦀"),
+                (TOKEN_PATH, "/data/ÿ"),
+                (TOKEN_EOF, "<EOF>"),
+            ],
+            texts
+        );
+        let (start, end) = tokens[7].span();
+        assert_eq!("~/写真/🦀", &input[start..end]);
     }
 
     #[test]
@@ -234,6 +850,8 @@ mod tests {
     fn test_lexer_can_check_is_alis_name() {
         let lexer = Lexer::new("test0123", 0, 't');
         assert!(lexer.is_alias_name());
+        let lexer = Lexer::new(".work", 0, '.');
+        assert!(lexer.is_alias_name());
     }
 
     #[test]
@@ -263,7 +881,52 @@ mod tests {
         let input = r#"[test]/some/absolute/path
         /another/absolute/path
         "#;
-        let mut lexer = Lexer::new(input, 0, '[');
+        let tokens = Lexer::new(input, 0, '[').all_tokens().unwrap();
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("test".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/some/absolute/path".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/another/absolute/path".into())),
+                Token::new(TOKEN_EOF, Cow::Owned("<EOF>".into())),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_lexer_all_tokens_outlive_the_input() {
+        let tokens = {
+            let input = String::from("[gs]{cd %p && git status}/code\n[*:api*]~/work");
+            Lexer::new(&input, 0, '[').all_tokens().unwrap()
+        };
+        let kinds: Vec<i32> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            vec![
+                TOKEN_LBRACK,
+                TOKEN_ALIAS,
+                TOKEN_RBRACK,
+                TOKEN_TEMPLATE,
+                TOKEN_PATH,
+                TOKEN_LBRACK,
+                TOKEN_GLOB,
+                TOKEN_COLON,
+                TOKEN_PATTERN,
+                TOKEN_RBRACK,
+                TOKEN_PATH,
+                TOKEN_EOF,
+            ],
+            kinds
+        );
+        assert_eq!("cd %p && git status", tokens[3].text.as_str());
+        assert_eq!((33, 34), tokens[7].span());
+    }
+
+    #[test]
+    fn test_lexer_parses_path_without_initial_slash() {
+        let input = "some/absolute/path";
+        let mut lexer = Lexer::new(input, 0, 's');
         let mut tokens: Vec<Token> = Vec::new();
         while let Ok(t) = lexer.next_token() {
             if t.kind == TOKEN_EOF {
@@ -271,26 +934,122 @@ mod tests {
             }
             tokens.push(t);
         }
-        assert_eq!(Token::new(TOKEN_LBRACK, Cow::Owned("[".into())), tokens[0]);
         assert_eq!(
-            Token::new(TOKEN_ALIAS, Cow::Owned("test".into())),
-            tokens[1]
+            vec![Token::new(TOKEN_PATH, Cow::Owned(input.into()))],
+            tokens
+        );
+
+        let mut lexer = Lexer::new("[api]../api", 0, '[');
+        for _ in 0..3 {
+            lexer.next_token().unwrap();
+        }
+        assert_eq!(
+            Token::new(TOKEN_PATH, Cow::Owned("../api".into())),
+            lexer.next_token().unwrap()
         );
+    }
+
+    #[test]
+    fn test_lexer_parses_glob() {
+        let input = "[*]/some/absolute/path";
+        let mut lexer = Lexer::new(input, 0, '[');
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            tokens.push(t);
+        }
+        assert_eq!(Token::new(TOKEN_LBRACK, Cow::Owned("[".into())), tokens[0]);
+        assert_eq!(Token::new(TOKEN_GLOB, Cow::Owned("*".into())), tokens[1]);
         assert_eq!(Token::new(TOKEN_RBRACK, Cow::Owned("]".into())), tokens[2]);
         assert_eq!(
             Token::new(TOKEN_PATH, Cow::Owned("/some/absolute/path".into())),
             tokens[3]
         );
+    }
+
+    #[test]
+    fn test_lexer_parses_glob_with_prefix_and_suffix() {
+        for (input, glob) in [
+            ("[*-dev]/code", "*-dev"),
+            ("[work-**2]/code", "work-**2"),
+            ("[w.*af_dev:api*]/code", "w.*af_dev"),
+            ("[*.(rs|py)]/code", "*.(rs|py)"),
+            ("[w-*a.(go)-dev]/code", "w-*a.(go)-dev"),
+        ]
+        .iter()
+        {
+            let tokens = Lexer::new(input, 0, '[').all_tokens().unwrap();
+            assert_eq!(
+                Token::new(TOKEN_GLOB, Cow::Owned(glob.to_string())),
+                tokens[1],
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_lexer_skips_zero_width_characters_with_warning() {
+        let input = "\u{200b}/some/path";
+        let mut lexer = Lexer::new(input, 0, '\u{200b}');
+        let token = lexer.next_token().unwrap();
+        assert_eq!(
+            Token::new(TOKEN_PATH, Cow::Owned("/some/path".into())),
+            token
+        );
+        assert_eq!(
+            vec!["skipping zero-width character U+200B".to_string()],
+            lexer.take_warnings()
+        );
+    }
+
+    #[test]
+    fn test_lexer_parses_directive() {
+        let input = "@macos [music]/Volumes/Media/Music";
+        let mut lexer = Lexer::new(input, 0, '@');
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            tokens.push(t);
+        }
+        assert_eq!(
+            Token::new(TOKEN_DIRECTIVE, Cow::Owned("macos".into())),
+            tokens[0]
+        );
+        assert_eq!(Token::new(TOKEN_LBRACK, Cow::Owned("[".into())), tokens[1]);
         assert_eq!(
-            Token::new(TOKEN_PATH, Cow::Owned("/another/absolute/path".into())),
+            Token::new(TOKEN_ALIAS, Cow::Owned("music".into())),
+            tokens[2]
+        );
+        assert_eq!(Token::new(TOKEN_RBRACK, Cow::Owned("]".into())), tokens[3]);
+        assert_eq!(
+            Token::new(TOKEN_PATH, Cow::Owned("/Volumes/Media/Music".into())),
             tokens[4]
         );
     }
 
     #[test]
-    fn test_lexer_parses_path_without_initial_slash() {
-        let input = "some/absolute/path";
-        let mut lexer = Lexer::new(input, 0, 's');
+    fn test_lexer_tracks_token_lines() {
+        let input = "[test]/some/path\n\n  /another/path\n";
+        let mut lexer = Lexer::new(input, 0, '[');
+        let mut lines: Vec<usize> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            lines.push(lexer.line());
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+        }
+        assert_eq!(vec![1, 1, 1, 1, 3, 4], lines);
+    }
+
+    #[test]
+    fn test_lexer_parses_windows_path_with_drive_letter() {
+        let input = "C:\\Users\\me\\project\n[docs]D:/Documents";
+        let mut lexer = Lexer::new(input, 0, 'C');
         let mut tokens: Vec<Token> = Vec::new();
         while let Ok(t) = lexer.next_token() {
             if t.kind == TOKEN_EOF {
@@ -298,13 +1057,95 @@ mod tests {
             }
             tokens.push(t);
         }
-        assert!(!tokens.is_empty());
-        assert_eq!(2, tokens.len())
+        assert_eq!(
+            Token::new(TOKEN_PATH, Cow::Owned("C:\\Users\\me\\project".into())),
+            tokens[0]
+        );
+        assert_eq!(
+            Token::new(TOKEN_ALIAS, Cow::Owned("docs".into())),
+            tokens[2]
+        );
+        assert_eq!(
+            Token::new(TOKEN_PATH, Cow::Owned("D:/Documents".into())),
+            tokens[4]
+        );
     }
 
     #[test]
-    fn test_lexer_parses_glob() {
-        let input = "[*]/some/absolute/path";
+    fn test_lexer_parses_variable_definition_and_reference() {
+        let input = "$work = /home/me/work\n[api]$work/services/api";
+        let mut lexer = Lexer::new(input, 0, '$');
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            tokens.push(t);
+        }
+        assert_eq!(
+            Token::new(TOKEN_VARIABLE, Cow::Owned("work".into())),
+            tokens[0]
+        );
+        assert_eq!(Token::new(TOKEN_EQUALS, Cow::Owned("=".into())), tokens[1]);
+        assert_eq!(
+            Token::new(TOKEN_PATH, Cow::Owned("/home/me/work".into())),
+            tokens[2]
+        );
+        assert_eq!(
+            Token::new(TOKEN_PATH, Cow::Owned("$work/services/api".into())),
+            tokens[6]
+        );
+    }
+
+    #[test]
+    fn test_lexer_parses_rest_of_line_after_equals_as_path() {
+        let input = "docs   =   ~/My Documents\nempty =\n";
+        let mut lexer = Lexer::new(input, 0, 'd');
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            tokens.push(t);
+        }
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_ALIAS, Cow::Owned("docs".into())),
+                Token::new(TOKEN_EQUALS, Cow::Owned("=".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("~/My Documents".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("empty".into())),
+                Token::new(TOKEN_EQUALS, Cow::Owned("=".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("".into())),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_lexer_trims_trailing_whitespace_from_paths() {
+        let input = "[docs]   /My Documents/work \t \r\n";
+        let mut lexer = Lexer::new(input, 0, '[');
+        let mut path = None;
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            if t.kind == TOKEN_PATH {
+                path = Some(t);
+            }
+        }
+        assert_eq!(
+            Some(Token::new(
+                TOKEN_PATH,
+                Cow::Owned("/My Documents/work".into())
+            )),
+            path
+        );
+    }
+
+    #[test]
+    fn test_lexer_colon_inside_brackets() {
+        let input = "[mydir:pushd]/some/path:with/colon";
         let mut lexer = Lexer::new(input, 0, '[');
         let mut tokens: Vec<Token> = Vec::new();
         while let Ok(t) = lexer.next_token() {
@@ -313,12 +1154,371 @@ mod tests {
             }
             tokens.push(t);
         }
-        assert_eq!(Token::new(TOKEN_LBRACK, Cow::Owned("[".into())), tokens[0]);
-        assert_eq!(Token::new(TOKEN_GLOB, Cow::Owned("*".into())), tokens[1]);
-        assert_eq!(Token::new(TOKEN_RBRACK, Cow::Owned("]".into())), tokens[2]);
         assert_eq!(
-            Token::new(TOKEN_PATH, Cow::Owned("/some/absolute/path".into())),
-            tokens[3]
+            vec![
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("mydir".into())),
+                Token::new(TOKEN_COLON, Cow::Owned(":".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("pushd".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/some/path:with/colon".into())),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_lexer_recursive_globs() {
+        for glob in [
+            "*", "**", "**2", "**10", "*a", "**2a", "*f", "**af", "**3fa",
+        ]
+        .iter()
+        {
+            let input = format!("[{}]/code", glob);
+            let mut lexer = Lexer::new(&input, 0, '[');
+            assert_eq!(TOKEN_LBRACK, lexer.next_token().unwrap().kind);
+            assert_eq!(
+                Token::new(TOKEN_GLOB, Cow::Owned(glob.to_string())),
+                lexer.next_token().unwrap()
+            );
+            assert_eq!(TOKEN_RBRACK, lexer.next_token().unwrap().kind);
+        }
+    }
+
+    #[test]
+    fn test_lexer_glob_pattern() {
+        let input = "[*:proj-*]/home/me/code";
+        let mut lexer = Lexer::new(input, 0, '[');
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            tokens.push(t);
+        }
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_GLOB, Cow::Owned("*".into())),
+                Token::new(TOKEN_COLON, Cow::Owned(":".into())),
+                Token::new(TOKEN_PATTERN, Cow::Owned("proj-*".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/home/me/code".into())),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_lexer_glob_exclusions() {
+        let input = "[*a:proj-*!.git!*_old]/home/me/code";
+        let mut lexer = Lexer::new(input, 0, '[');
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            tokens.push(t);
+        }
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_GLOB, Cow::Owned("*a".into())),
+                Token::new(TOKEN_COLON, Cow::Owned(":".into())),
+                Token::new(TOKEN_PATTERN, Cow::Owned("proj-*".into())),
+                Token::new(TOKEN_BANG, Cow::Owned("!".into())),
+                Token::new(TOKEN_PATTERN, Cow::Owned(".git".into())),
+                Token::new(TOKEN_BANG, Cow::Owned("!".into())),
+                Token::new(TOKEN_PATTERN, Cow::Owned("*_old".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/home/me/code".into())),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_lexer_version_header() {
+        let mut lexer = Lexer::new("\n version = 12 \nversion = 3", 0, '\n');
+        assert_eq!(
+            Token::new(TOKEN_VERSION, Cow::Owned("12".into())),
+            lexer.next_token().unwrap()
+        );
+        assert_eq!(TOKEN_ALIAS, lexer.next_token().unwrap().kind);
+
+        let mut lexer = Lexer::new("version = /some/path", 0, 'v');
+        assert_eq!(TOKEN_ALIAS, lexer.next_token().unwrap().kind);
+    }
+
+    #[test]
+    fn test_lexer_exclusions() {
+        let input = "!archive\n!/home/me/code/archive";
+        let mut lexer = Lexer::new(input, 0, '!');
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            tokens.push(t);
+        }
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_BANG, Cow::Owned("!".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("archive".into())),
+                Token::new(TOKEN_BANG, Cow::Owned("!".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/home/me/code/archive".into())),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_lexer_token_spans() {
+        let input = "[alias]/path  \n[*:proj-*] ~/code\n[é]/tmp";
+        let mut lexer = Lexer::new(input, 0, '[');
+        let mut spans = Vec::new();
+        loop {
+            let t = lexer.next_token().unwrap();
+            spans.push((t.kind, t.span()));
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+        }
+        assert_eq!(
+            vec![
+                (TOKEN_LBRACK, (0, 1)),
+                (TOKEN_ALIAS, (1, 6)),
+                (TOKEN_RBRACK, (6, 7)),
+                (TOKEN_PATH, (7, 12)),
+                (TOKEN_LBRACK, (15, 16)),
+                (TOKEN_GLOB, (16, 17)),
+                (TOKEN_COLON, (17, 18)),
+                (TOKEN_PATTERN, (18, 24)),
+                (TOKEN_RBRACK, (24, 25)),
+                (TOKEN_PATH, (26, 32)),
+                (TOKEN_LBRACK, (33, 34)),
+                (TOKEN_ALIAS, (34, 36)),
+                (TOKEN_RBRACK, (36, 37)),
+                (TOKEN_PATH, (37, 41)),
+                (TOKEN_EOF, (41, 41)),
+            ],
+            spans
+        );
+        assert_eq!(&input[1..6], "alias");
+        assert_eq!(&input[34..36], "é");
+    }
+
+    #[test]
+    fn test_token_span_ignored_by_equality() {
+        let tok = Token::new(TOKEN_ALIAS, Cow::Owned("a".into()));
+        assert_eq!((0, 0), tok.span());
+        assert_eq!(tok.clone().with_span(3, 4), tok);
+    }
+
+    #[test]
+    fn test_lexer_description() {
+        let input = "[proj]/path/to/proj  \"my main project\" \n/my/\"quoted\"/dir";
+        let tokens = Lexer::new(input, 0, '[').all_tokens().unwrap();
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("proj".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/path/to/proj".into())),
+                Token::new(TOKEN_DESCRIPTION, Cow::Owned("my main project".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/my/\"quoted\"/dir".into())),
+                Token::new(TOKEN_EOF, Cow::Owned("<EOF>".into())),
+            ],
+            tokens
+        );
+
+        let mut lexer = Lexer::new("/some/path \"unfinished\n/other", 0, '/');
+        lexer.next_token().unwrap();
+        assert_eq!(
+            Err("missing '\"' after the description on line 1".to_string()),
+            lexer.next_token()
+        );
+    }
+
+    #[test]
+    fn test_lexer_template() {
+        let input = "[gs]{cd %p && git status} /some/path";
+        let mut lexer = Lexer::new(input, 0, '[');
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            tokens.push(t);
+        }
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("gs".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_TEMPLATE, Cow::Owned("cd %p && git status".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/some/path".into())),
+            ],
+            tokens
+        );
+
+        let mut lexer = Lexer::new("[gs]{cd %p\n/some/path", 0, '[');
+        for _ in 0..3 {
+            lexer.next_token().unwrap();
+        }
+        assert_eq!(
+            Err("missing '}' after the template on line 1".to_string()),
+            lexer.next_token()
+        );
+    }
+
+    #[test]
+    fn test_lexer_alias_with_dots() {
+        let mut lexer = Lexer::new("[proj.work.main]/path/to/repo", 0, '[');
+        lexer.next_token().unwrap();
+        assert_eq!(
+            Token::new(TOKEN_ALIAS, Cow::Owned("proj.work.main".into())),
+            lexer.next_token().unwrap()
+        );
+        assert_eq!(TOKEN_RBRACK, lexer.next_token().unwrap().kind);
+    }
+
+    #[test]
+    fn test_lexer_crlf_line_endings() {
+        let mut lexer = Lexer::new("[a]/p1\r\n/p2\r\n", 0, '[');
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Ok(t) = lexer.next_token() {
+            if t.kind == TOKEN_EOF {
+                break;
+            }
+            tokens.push(t);
+        }
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("a".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/p1".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/p2".into())),
+            ],
+            tokens
+        );
+        assert!(tokens.iter().all(|t| !t.text.contains('\r')));
+        assert_eq!((3, 6), tokens[3].span());
+    }
+
+    #[test]
+    fn test_lexer_reset() {
+        // Describes every token, along with where it was found, and then every warning.
+        fn lex_all(lexer: &mut Lexer) -> Vec<String> {
+            let mut lexed = Vec::new();
+            loop {
+                let t = lexer.next_token().unwrap();
+                lexed.push(format!("{} {:?} on line {}", t, t.span(), lexer.line()));
+                if t.kind == TOKEN_EOF {
+                    break;
+                }
+            }
+            lexed.append(&mut lexer.take_warnings());
+            lexed
+        }
+
+        let input = "version = 1\n[a:pushd]/p1\n\u{200b}[*!x]/p2";
+        let mut lexer = Lexer::new(input, 0, 'v');
+        let first = lex_all(&mut lexer);
+        assert_eq!("<'1', VERSION> (0, 11) on line 1", first[0]);
+        assert!(first.last().unwrap().contains("zero-width"));
+
+        lexer.reset();
+        assert_eq!(input, lexer.input());
+        assert_eq!(first, lex_all(&mut lexer));
+    }
+
+    #[test]
+    fn test_lexer_iterator() {
+        let input = "$work = /home/me/work\n[api]$work/api\n@macos ~/Music\n";
+        let lexer = Lexer::new(input, 0, '$');
+        let tokens: Result<Vec<Token>, String> = lexer.collect();
+        let kinds: Vec<i32> = tokens.unwrap().iter().map(|t| t.kind).collect();
+        assert_eq!(
+            vec![
+                TOKEN_VARIABLE,
+                TOKEN_EQUALS,
+                TOKEN_PATH,
+                TOKEN_LBRACK,
+                TOKEN_ALIAS,
+                TOKEN_RBRACK,
+                TOKEN_PATH,
+                TOKEN_DIRECTIVE,
+                TOKEN_PATH,
+                TOKEN_EOF,
+            ],
+            kinds
+        );
+
+        let mut lexer = Lexer::new("/p", 0, '/');
+        assert_eq!(TOKEN_PATH, lexer.next().unwrap().unwrap().kind);
+        assert_eq!(TOKEN_EOF, lexer.next().unwrap().unwrap().kind);
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn test_lexer_reference() {
+        fn lex_all(input: &str) -> Vec<Token<'_>> {
+            Lexer::new(input, 0, input.chars().next().unwrap())
+                .filter_map(Result::ok)
+                .filter(|t| t.kind != TOKEN_EOF)
+                .collect()
+        }
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("web".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_REF, Cow::Owned("base".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/apps/web".into())),
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("api".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_REF, Cow::Owned("base".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("".into())),
+            ],
+            lex_all("[web]@base/apps/web\n[api] @base\n")
+        );
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_ALIAS, Cow::Owned("web".into())),
+                Token::new(TOKEN_EQUALS, Cow::Owned("=".into())),
+                Token::new(TOKEN_REF, Cow::Owned("proj.base".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/web".into())),
+            ],
+            lex_all("web = @proj.base/web")
+        );
+        assert_eq!(
+            Token::new(TOKEN_DIRECTIVE, Cow::Owned("macos".into())),
+            lex_all("@macos [web]@base/web")[0]
+        );
+    }
+
+    #[test]
+    fn test_lexer_section() {
+        let input = "[section: work]\n[section:pushd]/code\n[ section : ]";
+        let tokens: Vec<Token<'_>> = Lexer::new(input, 0, '[')
+            .filter_map(Result::ok)
+            .filter(|t| t.kind != TOKEN_EOF)
+            .collect();
+        assert_eq!(
+            vec![
+                Token::new(TOKEN_SECTION, Cow::Owned("work".into())),
+                Token::new(TOKEN_LBRACK, Cow::Owned("[".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("section".into())),
+                Token::new(TOKEN_COLON, Cow::Owned(":".into())),
+                Token::new(TOKEN_ALIAS, Cow::Owned("pushd".into())),
+                Token::new(TOKEN_RBRACK, Cow::Owned("]".into())),
+                Token::new(TOKEN_PATH, Cow::Owned("/code".into())),
+                Token::new(TOKEN_SECTION, Cow::Owned("".into())),
+            ],
+            tokens
         );
     }
 }
@@ -1,3 +1,8 @@
 pub mod lexer;
 pub mod parser;
-pub mod command;
\ No newline at end of file
+pub mod command;
+pub mod error;
+pub mod report;
+pub mod shell;
+pub mod cache;
+pub mod format;
@@ -1,11 +1,11 @@
 extern crate shellexpand;
 
 use dalia::command::Command;
-use std::{env, process};
+use std::{env, io, process};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if let Err(e) = Command::run(args) {
+    if let Err(e) = Command::run_with_writer(&args, &mut io::stdout()) {
         eprintln!("dalia: {}", e);
         process::exit(1);
     }
@@ -2,299 +2,4366 @@
 extern crate temp_testdir;
 
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, ReadDir};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
 
+use crate::error::{DaliaError, DaliaWarning};
 use crate::lexer::{
-    Lexer, Token, TOKEN_ALIAS, TOKEN_EOF, TOKEN_GLOB, TOKEN_LBRACK, TOKEN_PATH, TOKEN_RBRACK,
+    is_alias_char, Lexer, Token, TOKEN_ALIAS, TOKEN_BANG, TOKEN_COLON, TOKEN_DESCRIPTION,
+    TOKEN_DIRECTIVE, TOKEN_EOF, TOKEN_EQUALS, TOKEN_GLOB, TOKEN_LBRACK, TOKEN_PATH, TOKEN_PATTERN,
+    TOKEN_RBRACK, TOKEN_REF, TOKEN_SECTION, TOKEN_TEMPLATE, TOKEN_VARIABLE, TOKEN_VERSION,
 };
 
+/// Written at the start of a file by some editors (e.g. Notepad on Windows).
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// Operating systems that can guard a line, e.g. `@macos [music]/Volumes/Media/Music`.
+const OPERATING_SYSTEMS: [&str; 11] = [
+    "android",
+    "dragonfly",
+    "freebsd",
+    "illumos",
+    "ios",
+    "linux",
+    "macos",
+    "netbsd",
+    "openbsd",
+    "solaris",
+    "windows",
+];
+
+/// AliasSource records which kind of configuration line produced an alias.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AliasSource {
+    /// The alias name was given explicitly, e.g. `[name]/some/path`.
+    Explicit,
+    /// The alias name was derived from the last component of a path.
+    PathDerived,
+    /// The alias was created by expanding a `[*]` line.
+    GlobExpanded,
+}
+
+/// The newest configuration format version this parser understands.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How many aliases a configuration can create unless its options allow more.
+pub const DEFAULT_MAX_ALIASES: usize = 1000;
+
+/// How many aliases a single `[*]` line can create before it's warned about, unless its options
+/// allow more.
+pub const DEFAULT_MAX_GLOB_ALIASES: usize = 200;
+
+/// What separates a section's name from the names of the aliases in it, e.g. `work-api`.
+pub const DEFAULT_SECTION_SEPARATOR: &str = "-";
+
+/// The command an alias runs when the configuration doesn't name one.
+pub const DEFAULT_COMMAND: &str = "cd";
+
+/// The command an alias for a file, from a `[*f]` line, runs unless the options name another.
+pub const DEFAULT_FILE_COMMAND: &str = "$EDITOR";
+
+/// Separates the paths of an alias that points at the first of them that exists.
+pub const FALLBACK_SEPARATOR: char = '|';
+
+/// Stands in for the path in a command template like `{cd %p && git status}`.
+pub const TEMPLATE_PATH: &str = "%p";
+
+/// AliasEntry is a single alias along with the path it navigates to and the command that does it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AliasEntry {
+    pub alias: String,
+    pub path: String,
+    /// Either a command that's run with the path, or a template that contains `%p`.
+    pub command: String,
+    /// Whether the path is a directory or a file.
+    pub kind: EntryKind,
+    /// The description given after the path, like `"my main project"`, which isn't part of the
+    /// generated alias.
+    pub description: Option<String>,
+    /// The configuration line that created the alias, counting from 1.
+    pub line: usize,
+}
+
+/// EntryKind tells apart aliases for directories from aliases for files, which `[*f]` lines
+/// create and which open the file instead of changing to it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EntryKind {
+    Directory,
+    File,
+}
+
+impl AliasEntry {
+    /// Returns the shell code that the alias runs.
+    pub fn body(&self) -> String {
+        if self.command.contains(TEMPLATE_PATH) {
+            self.command.replace(TEMPLATE_PATH, &self.path)
+        } else {
+            format!("{} {}", self.command, self.path)
+        }
+    }
+}
+
+/// Directive is what a single configuration line asks for, as parsed, before any directories are
+/// read from disk.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Directive {
+    /// A path with a custom name, like `[name]/path` or `name = /path`, along with the command or
+    /// template the alias runs when one is configured.
+    ExplicitAlias {
+        alias: String,
+        path: String,
+        command: Option<String>,
+        line: usize,
+    },
+    /// A path whose alias is named after its last component.
+    DerivedAlias { path: String, line: usize },
+    /// Several paths separated by `|`, like `[work]/opt/work | ~/work`, whose alias points at
+    /// the first one that exists.
+    FallbackAlias {
+        alias: Option<String>,
+        paths: Vec<String>,
+        command: Option<String>,
+        line: usize,
+    },
+    /// A custom name for a path that continues another alias's path, like `[web]@base/web`,
+    /// which is resolved once every other line has created its alias.
+    AliasReference {
+        alias: String,
+        target: String,
+        path: String,
+        command: Option<String>,
+        line: usize,
+    },
+    /// A `[*]` line, which creates an alias for the directories below `base`.
+    GlobExpansion {
+        base: String,
+        options: GlobOptions,
+        line: usize,
+    },
+    /// An `@case` line, which changes how the names of the aliases on the lines after it are
+    /// derived from paths.
+    Case { preserve: bool },
+    /// A `[section: name]` line, which prefixes the names of the aliases on the lines after it
+    /// with the section's name, up to the next section. `[section:]` ends the section.
+    Section { name: Option<String> },
+}
+
+impl Directive {
+    /// Returns the line of the configuration the directive was parsed from, for the directives
+    /// that create aliases.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Directive::ExplicitAlias { line, .. }
+            | Directive::DerivedAlias { line, .. }
+            | Directive::FallbackAlias { line, .. }
+            | Directive::AliasReference { line, .. }
+            | Directive::GlobExpansion { line, .. } => Some(*line),
+            Directive::Case { .. } | Directive::Section { .. } => None,
+        }
+    }
+}
+
+/// GlobOptions controls how the directory on a `[*]` line is expanded into aliases.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GlobOptions {
+    /// How many levels below the directory get aliases, or `None` for every level.
+    pub depth: Option<usize>,
+    /// A shell-style pattern, like `proj-*`, that directory names must match to get an alias.
+    pub pattern: Option<String>,
+    /// Directory names, or patterns like `*_old`, that are skipped along with everything
+    /// inside them.
+    pub exclude: Vec<String>,
+    /// Creates aliases for hidden directories too, as `[*a]` asks for.
+    pub include_hidden: bool,
+    /// Creates aliases for files too, as `[*f]` asks for.
+    pub include_files: bool,
+    /// Text added to the start of every alias name, like `work-` in `[work-*]`, or empty.
+    pub prefix: String,
+    /// Text added to the end of every alias name, like `-dev` in `[*-dev]`, or empty.
+    pub suffix: String,
+    /// File extensions, like `rs` and `py` in `[*.(rs|py)]`, one of which a directory has to
+    /// directly contain a file with to get an alias, or empty for every directory.
+    pub extensions: Vec<String>,
+}
+
+impl GlobOptions {
+    /// Reads the options from a glob token: `*` for the immediate children, `**` for every level,
+    /// and `**N` for at most N levels, each optionally followed by `a` to include hidden
+    /// directories and `f` to include files. Text before the asterisks is a prefix for the alias
+    /// names, a list of file extensions like `.(rs|py)` after the flags limits the directories to
+    /// those containing such files, and text after them that starts with `-`, `_`, or `.` is a
+    /// suffix.
+    fn from_token(token: &str, line: usize) -> Result<Self, DaliaError> {
+        let (token, extensions) = match token.split_once(".(") {
+            Some((glob, rest)) => {
+                let invalid = || {
+                    DaliaError::ParseError(format!(
+                        "invalid file extensions '.({}' on line {}; expected something like .(rs|py)",
+                        rest, line
+                    ))
+                };
+                let (list, suffix) = rest.split_once(')').ok_or_else(invalid)?;
+                let extensions: Vec<String> = list.split('|').map(str::to_string).collect();
+                if extensions
+                    .iter()
+                    .any(|e| e.is_empty() || !e.chars().all(char::is_alphanumeric))
+                {
+                    return Err(invalid());
+                }
+                (format!("{}{}", glob, suffix), extensions)
+            }
+            None => (token.to_string(), Vec::new()),
+        };
+        let (prefix, token) = token.split_at(token.find('*').unwrap_or(0));
+        let (token, suffix) =
+            token.split_at(token.find(&['-', '_', '.'][..]).unwrap_or(token.len()));
+        let text = token.trim_end_matches(&['a', 'f'][..]);
+        let flags = &token[text.len()..];
+        let depth = match text {
+            "*" => Some(1),
+            "**" => None,
+            _ => match text.trim_start_matches('*').parse::<usize>() {
+                Ok(depth) if depth > 0 => Some(depth),
+                _ => {
+                    return Err(DaliaError::ParseError(format!(
+                        "invalid glob depth '{}' on line {}; expected a number of at least 1",
+                        text, line
+                    )))
+                }
+            },
+        };
+        Ok(GlobOptions {
+            depth,
+            pattern: None,
+            exclude: Vec::new(),
+            include_hidden: flags.contains('a'),
+            include_files: flags.contains('f'),
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            extensions,
+        })
+    }
+
+    /// Returns the alias name for a directory named `name`, with the prefix and suffix added.
+    fn alias_name(&self, name: &str) -> String {
+        format!("{}{}{}", self.prefix, name, self.suffix)
+    }
+
+    /// Reports whether a directory with the given name is skipped entirely.
+    fn excludes(&self, name: &str) -> bool {
+        self.exclude.iter().any(|e| matches_pattern(e, name))
+    }
+
+    /// Reports whether a directory with the given name gets an alias.
+    fn includes(&self, name: &str) -> bool {
+        match &self.pattern {
+            Some(pattern) => matches_pattern(pattern, name),
+            None => true,
+        }
+    }
+
+    /// Reports whether the entry at `path` has the file types the glob asks for: a file with one
+    /// of its extensions, or a directory that directly contains one.
+    fn has_file_types(&self, path: &Path, is_file: bool) -> bool {
+        if self.extensions.is_empty() {
+            return true;
+        }
+        let matches = |path: &Path| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| self.extensions.iter().any(|x| x == e))
+        };
+        if is_file {
+            return matches(path);
+        }
+        match std::fs::read_dir(path) {
+            Ok(entries) => entries.flatten().any(|entry| {
+                entry.file_type().is_ok_and(|t| t.is_file()) && matches(&entry.path())
+            }),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Splits a path at each `|` into the paths an alias falls back through, turning `\|` into a `|`
+/// that's part of a path instead.
+fn split_fallbacks(path: &str) -> Vec<String> {
+    let mut paths = vec![String::new()];
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&FALLBACK_SEPARATOR) => {
+                paths.last_mut().unwrap().push(FALLBACK_SEPARATOR);
+                chars.next();
+            }
+            FALLBACK_SEPARATOR => paths.push(String::new()),
+            c => paths.last_mut().unwrap().push(c),
+        }
+    }
+    paths
+}
+
+/// Splits the exclusions off the end of a glob line's path, e.g. `~/code !node_modules !target`
+/// becomes `~/code` and `["node_modules", "target"]`.
+fn split_exclusions(path: &str) -> (&str, Vec<String>) {
+    let mut path = path.trim_end();
+    let mut exclude = Vec::new();
+    while let Some(i) = path.rfind(char::is_whitespace) {
+        match path[i + 1..].strip_prefix('!') {
+            Some(name) if !name.is_empty() => exclude.insert(0, name.to_string()),
+            _ => break,
+        }
+        path = path[..i].trim_end();
+    }
+    (path, exclude)
+}
+
+/// The file in a glob line's directory that lists the names and patterns to skip when expanding
+/// it, like the exclusions on the line itself.
+const IGNORE_FILE: &str = ".daliaignore";
+
+/// The directories, and files if asked for, that a glob line creates aliases for, along with
+/// the warnings about what it skipped and the directories below the glob's own that it read.
+#[derive(Debug, Default)]
+struct GlobScan {
+    entries: Vec<GlobEntry>,
+    warnings: Vec<String>,
+    nested_dirs: Vec<String>,
+}
+
+/// A directory, or file, that a glob line found.
 #[derive(Debug)]
+struct GlobEntry {
+    path: String,
+    is_file: bool,
+}
+
+/// The error for a glob that creates more than `max` aliases.
+fn too_many_aliases(line: usize, max: usize) -> DaliaError {
+    DaliaError::ParseError(format!(
+        "the glob on line {} creates more than the maximum of {} aliases",
+        line, max
+    ))
+}
+
+/// Scans the directories of every glob directive at the same time, since each one can be slow
+/// on a network mount, and returns the scans in the same order as the directives, with `None`
+/// for the directives that aren't globs.
+fn scan_globs(
+    directives: &[Directive],
+    options: &ParserOptions,
+) -> Vec<Option<Result<GlobScan, DaliaError>>> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = directives
+            .iter()
+            .map(|directive| match directive {
+                Directive::GlobExpansion {
+                    base,
+                    options: glob,
+                    line,
+                } if options.expand_glob => {
+                    Some(scope.spawn(move || scan_glob(base, glob, options, *line)))
+                }
+                _ => None,
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e))))
+            .collect()
+    })
+}
+
+/// Finds what the glob on `line` creates aliases for below `dir`, without creating them.
+fn scan_glob(
+    dir: &str,
+    glob: &GlobOptions,
+    options: &ParserOptions,
+    line: usize,
+) -> Result<GlobScan, DaliaError> {
+    let expanded = expand_read_path(dir);
+    let paths = match std::fs::read_dir(&expanded) {
+        Ok(paths) => paths,
+        Err(e) => return Err(DaliaError::InvalidGlob(PathBuf::from(dir), line, e.kind())),
+    };
+    let mut scan = GlobScan::default();
+    let mut glob = glob.clone();
+    let ignore_file = Path::new(&expanded).join(IGNORE_FILE);
+    match std::fs::read_to_string(&ignore_file) {
+        Ok(contents) => glob.exclude.extend(parse_ignore_file(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => scan
+            .warnings
+            .push(format!("ignoring {}: {}", ignore_file.display(), e.kind())),
+    }
+    scan_glob_entries(
+        &expanded, paths, &glob, glob.depth, options, line, &mut scan,
+    )?;
+    Ok(scan)
+}
+
+/// Expands `~` and environment variables in a path that dalia reads itself, like a glob line's
+/// directory or the paths an alias falls back through. The paths in alias bodies are left for
+/// the shell to expand.
+fn expand_read_path(path: &str) -> String {
+    shellexpand::full(path)
+        .unwrap_or_else(|_| shellexpand::tilde(path))
+        .to_string()
+}
+
+/// Returns the names of the environment variables that expanding `path` with
+/// [`expand_read_path`] reads, including `HOME` for a leading `~`.
+fn referenced_variables(path: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    if path.starts_with('~') {
+        names.push("HOME".to_string());
+    }
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            continue;
+        }
+        let braced = chars.peek() == Some(&'{');
+        if braced {
+            chars.next();
+        }
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '_') {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        if !name.is_empty() {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Reads the names and patterns in a `.daliaignore` file, one per line, leaving out blank lines
+/// and `#` comments.
+fn parse_ignore_file(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Adds the entries in `paths` that the glob on `line` includes to `scan`, sorted by name,
+/// descending into directories while `depth` allows.
+fn scan_glob_entries(
+    dir: &str,
+    paths: ReadDir,
+    glob: &GlobOptions,
+    depth: Option<usize>,
+    options: &ParserOptions,
+    line: usize,
+    scan: &mut GlobScan,
+) -> Result<(), DaliaError> {
+    let remaining = depth.map(|d| d - 1);
+    let mut entries = Vec::new();
+    for path in paths {
+        match path {
+            Ok(entry) => entries.push(entry),
+            Err(e) => scan
+                .warnings
+                .push(format!("skipping an entry in {}: {}", dir, e.kind())),
+        }
+    }
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let include_hidden = options.include_hidden || glob.include_hidden;
+        if (!include_hidden && name.starts_with('.')) || glob.excludes(&name) {
+            continue;
+        }
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(e) => {
+                scan.warnings
+                    .push(format!("skipping {}: {}", entry.path().display(), e.kind()));
+                continue;
+            }
+        };
+        let is_link = file_type.is_symlink();
+        let mut is_file = false;
+        if is_link {
+            if !options.follow_symlinks {
+                continue;
+            }
+            match std::fs::metadata(entry.path()) {
+                Ok(target) if target.is_dir() => {}
+                Ok(target) if target.is_file() && glob.include_files => is_file = true,
+                Ok(_) => continue,
+                Err(_) => {
+                    scan.warnings.push(format!(
+                        "skipping {}: it's a broken symbolic link",
+                        entry.path().display()
+                    ));
+                    continue;
+                }
+            }
+        } else if file_type.is_file() && glob.include_files {
+            is_file = true;
+        } else if !file_type.is_dir() {
+            continue;
+        }
+        let child = match entry.path().to_str() {
+            Some(child) => child.to_string(),
+            None => {
+                scan.warnings.push(format!(
+                    "skipping {}: its name isn't valid UTF-8",
+                    entry.path().display()
+                ));
+                continue;
+            }
+        };
+        // A directory the current user can't search would get an alias that fails to `cd`.
+        if !is_file && !options.include_unreadable {
+            if let Err(e) = std::fs::metadata(Path::new(&child).join(".")) {
+                if e.kind() == io::ErrorKind::PermissionDenied {
+                    scan.warnings
+                        .push(format!("skipping {}: {}", child, e.kind()));
+                    continue;
+                }
+            }
+        }
+        if glob.includes(&name) && glob.has_file_types(Path::new(&child), is_file) {
+            scan.entries.push(GlobEntry {
+                path: child.clone(),
+                is_file,
+            });
+            // Every entry gets an alias, so there's no need to keep reading a runaway glob.
+            if scan.entries.len() > options.max_aliases {
+                return Err(too_many_aliases(line, options.max_aliases));
+            }
+        }
+        // The alias for a symbolic link points at the link itself, and the directory it
+        // points to isn't descended into, so a link back up the tree can't recurse forever.
+        if is_link || is_file || remaining == Some(0) {
+            continue;
+        }
+        scan.nested_dirs.push(child.clone());
+        match std::fs::read_dir(&child) {
+            Ok(children) => {
+                scan_glob_entries(&child, children, glob, remaining, options, line, scan)?
+            }
+            Err(e) => {
+                scan.warnings
+                    .push(format!("skipping the contents of {}: {}", child, e.kind()))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Matches `name` against a shell-style pattern where `*` matches any run of characters and `?`
+/// matches exactly one.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` seen and the name position it's currently matched up to.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Exclusion removes aliases generated by other lines, either by the alias name or by the path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Exclusion {
+    Name(String),
+    Path(String),
+}
+
+/// ParserOptions controls how a Parser turns configuration lines into aliases.
+#[derive(Clone, Debug)]
+pub struct ParserOptions {
+    /// Keeps the original casing of alias names derived from paths instead of lowercasing them.
+    pub preserve_case: bool,
+    /// Turns problems that are only warnings by default, like aliases that differ only in case,
+    /// into errors.
+    pub strict: bool,
+    /// The operating system that `@os` guarded lines are checked against.
+    pub target_os: String,
+    /// Creates aliases for hidden directories, whose names start with a `.`, when expanding `[*]`.
+    pub include_hidden: bool,
+    /// Creates aliases for directories from `[*]` lines that the current user can't enter,
+    /// instead of skipping them, for aliases that another user will run.
+    pub include_unreadable: bool,
+    /// Creates aliases for symbolic links to directories when expanding `[*]`, instead of
+    /// skipping them.
+    pub follow_symlinks: bool,
+    /// Renames directories from `[*]` lines that would get the same alias, like `work/notes` and
+    /// `personal/notes`, to include their parent's name (`work-notes` and `personal-notes`)
+    /// instead of failing.
+    pub disambiguate: bool,
+    /// The directory that relative paths, like `projects/api`, are resolved against. Relative
+    /// paths are kept as they're written when there isn't one.
+    pub base: Option<PathBuf>,
+    /// The most aliases the configuration can create. Expanding a `[*]` line past it fails, so
+    /// that a line like `[**]/` doesn't walk the entire disk.
+    pub max_aliases: usize,
+    /// How many aliases a single `[*]` line can create before it's warned about, or reported as
+    /// an error in strict mode, since that many usually means the line points at the wrong
+    /// directory.
+    pub max_glob_aliases: usize,
+    /// The command that aliases for files from `[*f]` lines run.
+    pub file_command: String,
+    /// What separates a section's name from the names of the aliases in it.
+    pub section_separator: String,
+    /// Resolves symbolic links and `..` on disk when checking whether two paths configured for
+    /// the same alias are the same directory, which only works for paths that exist.
+    pub canonicalize: bool,
+    /// Creates aliases for what `[*]` lines find on disk. When unset, the directory of each `[*]`
+    /// line is kept in [`Parser::unexpanded_globs`] instead, for tools that expand it later.
+    pub expand_glob: bool,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            preserve_case: false,
+            strict: false,
+            target_os: std::env::consts::OS.to_string(),
+            include_hidden: false,
+            include_unreadable: false,
+            follow_symlinks: true,
+            disambiguate: false,
+            base: None,
+            max_aliases: DEFAULT_MAX_ALIASES,
+            max_glob_aliases: DEFAULT_MAX_GLOB_ALIASES,
+            file_command: DEFAULT_FILE_COMMAND.to_string(),
+            section_separator: DEFAULT_SECTION_SEPARATOR.to_string(),
+            canonicalize: false,
+            expand_glob: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Parser<'a> {
     /// The lexer responsible for returning tokenized input.
     input: Lexer<'a>,
     /// The current lookahead token used by this parser.
     lookahead: Token<'a>,
+    /// The line number where the lookahead token starts.
+    lookahead_line: usize,
+    /// The token after the lookahead token, and the line it starts on, once it's been peeked at.
+    next_lookahead: Option<(Token<'a>, usize)>,
+    /// What each parsed line asks for, in the order the lines appear.
+    directives: Vec<Directive>,
     /// The internal representation of a parsed configuration file.
     int_rep: HashMap<String, String>,
+    /// The kind of line each alias in the internal representation came from.
+    sources: HashMap<String, AliasSource>,
+    /// The navigation command of each alias configured with `[alias:command]`.
+    commands: HashMap<String, String>,
+    /// The paths from `[*f]` lines that are files rather than directories.
+    files: HashSet<String>,
+    /// The description of each alias whose line has one.
+    descriptions: HashMap<String, String>,
+    /// The line that created each alias in the internal representation.
+    lines: HashMap<String, usize>,
+    /// The descriptions at the end of lines, by line number, until the lines are resolved.
+    line_descriptions: HashMap<usize, String>,
+    /// Variables defined with `$name = value`, along with the line each was defined on.
+    variables: HashMap<String, (String, usize)>,
+    /// Exclusions from `!name` and `!path` lines, along with the line each was found on, which are
+    /// applied once every other line has been parsed.
+    exclusions: Vec<(Exclusion, usize)>,
+    /// The directory of each `[*]` line, in order, when they aren't expanded.
+    unexpanded_globs: Vec<String>,
+    /// The directories that recursive glob lines read below their own directory.
+    nested_glob_dirs: Vec<PathBuf>,
+    /// Non-fatal problems found while parsing, in the order they were encountered.
+    warnings: Vec<String>,
+    /// The warnings that library users can match on, whose messages are also in `warnings`.
+    typed_warnings: Vec<DaliaWarning>,
+    /// Errors collected by process_input_collecting_errors, in the order they were encountered.
+    errors: Vec<DaliaError>,
+    /// The options this parser was created with.
+    options: ParserOptions,
+    /// The format version from the configuration's `version = N` header, if it has one.
+    format_version: Option<u32>,
+    /// The section of the directive being resolved, whose name prefixes every alias in it.
+    section: Option<String>,
+    /// The line of the directive being resolved, which the aliases it creates are recorded with.
+    line: usize,
+    /// The line and column of the token the last syntax error was found at.
+    error_position: Option<(usize, usize)>,
+    /// Names shared by more than one expanded directory, which are disambiguated wherever they
+    /// appear.
+    ambiguous: HashSet<String>,
 }
 
 impl<'a> Parser<'a> {
+    /// Constructs a parser for `s`, panicking if it's empty or its first token can't be lexed.
+    /// Use try_new to get an error instead.
     pub fn new(s: &str) -> Self {
+        Parser::new_with_options(s, ParserOptions::default())
+    }
+
+    pub fn new_with_options(s: &str, options: ParserOptions) -> Self {
+        Parser::try_new_with_options(s, options).expect("couldn't create new parser")
+    }
+
+    /// Constructs a parser for `s`, failing with EmptyInput if there's nothing but whitespace in
+    /// it, or with LexError if its first token can't be lexed.
+    pub fn try_new(s: &str) -> Result<Self, DaliaError> {
+        Parser::try_new_with_options(s, ParserOptions::default())
+    }
+
+    /// Constructs a parser for everything that can be read from `r`.
+    pub fn from_reader<R: Read>(r: R) -> Result<Self, DaliaError> {
+        Parser::from_reader_with_options(r, ParserOptions::default())
+    }
+
+    pub fn from_reader_with_options<R: Read>(
+        r: R,
+        options: ParserOptions,
+    ) -> Result<Self, DaliaError> {
+        match io::read_to_string(r) {
+            Ok(s) => Parser::try_new_with_options(&s, options),
+            Err(e) => Err(DaliaError::IoError(PathBuf::from("<input>"), e.kind())),
+        }
+    }
+
+    /// Constructs a parser for the contents of the file at `p`.
+    pub fn from_path(p: &Path) -> Result<Self, DaliaError> {
+        match File::open(p) {
+            Ok(file) => Parser::from_reader(file).map_err(|e| match e {
+                DaliaError::IoError(_, e) => DaliaError::IoError(p.to_path_buf(), e),
+                e => e,
+            }),
+            Err(e) => Err(DaliaError::IoError(p.to_path_buf(), e.kind())),
+        }
+    }
+
+    pub fn try_new_with_options(s: &str, options: ParserOptions) -> Result<Self, DaliaError> {
+        let s = s.strip_prefix(BYTE_ORDER_MARK).unwrap_or(s);
         if s.trim().is_empty() {
-            panic!("no config file found to parse")
+            return Err(DaliaError::EmptyInput);
         }
         let c = s.chars().next().unwrap();
         let mut input = Lexer::new(s, 0, c);
-        match input.next_token() {
-            Ok(lookahead) => Self {
-                input,
-                lookahead,
-                int_rep: HashMap::new(),
-            },
-            Err(e) => panic!("couldn't create new parser: {}", e),
+        let lookahead = input.next_token().map_err(DaliaError::LexError)?;
+        let lookahead_line = input.line();
+        let warnings = input.take_warnings();
+        Ok(Self {
+            input,
+            lookahead,
+            lookahead_line,
+            next_lookahead: None,
+            directives: Vec::new(),
+            int_rep: HashMap::new(),
+            sources: HashMap::new(),
+            commands: HashMap::new(),
+            files: HashSet::new(),
+            descriptions: HashMap::new(),
+            lines: HashMap::new(),
+            line_descriptions: HashMap::new(),
+            variables: HashMap::new(),
+            exclusions: Vec::new(),
+            unexpanded_globs: Vec::new(),
+            warnings,
+            typed_warnings: Vec::new(),
+            nested_glob_dirs: Vec::new(),
+            errors: Vec::new(),
+            options,
+            format_version: None,
+            ambiguous: HashSet::new(),
+            error_position: None,
+            section: None,
+            line: 0,
+        })
+    }
+
+    pub fn aliases(&self) -> HashMap<String, String> {
+        self.int_rep.to_owned()
+    }
+
+    pub fn sources(&self) -> HashMap<String, AliasSource> {
+        self.sources.to_owned()
+    }
+
+    /// Returns every alias with its path and navigation command, sorted by alias name.
+    pub fn entries(&self) -> Vec<AliasEntry> {
+        let mut entries: Vec<AliasEntry> = self
+            .int_rep
+            .iter()
+            .map(|(alias, path)| {
+                let (kind, default_command) = if self.files.contains(path) {
+                    (EntryKind::File, self.options.file_command.as_str())
+                } else {
+                    (EntryKind::Directory, DEFAULT_COMMAND)
+                };
+                AliasEntry {
+                    alias: alias.to_owned(),
+                    path: path.to_owned(),
+                    command: self
+                        .commands
+                        .get(alias)
+                        .map_or(default_command, String::as_str)
+                        .to_string(),
+                    kind,
+                    description: self.descriptions.get(alias).cloned(),
+                    line: self.lines.get(alias).copied().unwrap_or_default(),
+                }
+            })
+            .collect();
+        entries.sort_by(|a, b| a.alias.cmp(&b.alias));
+        entries
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Returns the warnings that can be matched on by kind. Their messages are also among
+    /// warnings(), in the order they were found.
+    pub fn typed_warnings(&self) -> &[DaliaWarning] {
+        &self.typed_warnings
+    }
+
+    /// Returns the directory of each `[*]` line, in the order they appear, when the parser was
+    /// created with `expand_glob` unset. Nothing is returned when they're expanded into aliases.
+    pub fn unexpanded_globs(&self) -> &[String] {
+        &self.unexpanded_globs
+    }
+
+    /// Returns the version from the configuration's `version = N` header, or None without one.
+    /// A configuration without a header is version 1, so callers that only need the version can
+    /// use `format_version().unwrap_or(1)`.
+    pub fn format_version(&self) -> Option<u32> {
+        self.format_version
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    pub fn errors(&self) -> &[DaliaError] {
+        &self.errors
+    }
+
+    fn consume(&mut self) -> Result<(), DaliaError> {
+        let (token, line) = match self.next_lookahead.take() {
+            Some(next) => next,
+            None => (self.input.next_token()?, self.input.line()),
+        };
+        self.lookahead = token;
+        self.lookahead_line = line;
+        self.warnings.append(&mut self.input.take_warnings());
+        Ok(())
+    }
+
+    /// Returns the line and column, counted in characters from 1, where the lookahead token
+    /// starts. The end of the input is placed just after the last line that isn't blank, where
+    /// whatever's missing would have gone.
+    fn lookahead_position(&self) -> (usize, usize) {
+        let source = self.input.input();
+        let start = if self.lookahead.kind == TOKEN_EOF {
+            source.trim_end().len()
+        } else {
+            self.lookahead.span().0.min(source.len())
+        };
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        (
+            source[..start].matches('\n').count() + 1,
+            source[line_start..start].chars().count() + 1,
+        )
+    }
+
+    /// Returns the line and column of the token that the last syntax error was found at.
+    pub fn error_position(&self) -> Option<(usize, usize)> {
+        self.error_position
+    }
+
+    /// Returns the configuration text being parsed.
+    pub fn source(&self) -> &str {
+        self.input.input()
+    }
+
+    /// Returns the token after the lookahead token without consuming either of them.
+    pub fn peek_next(&mut self) -> Result<&Token<'a>, DaliaError> {
+        let next = match self.next_lookahead.take() {
+            Some(next) => next,
+            None => (self.input.next_token()?, self.input.line()),
+        };
+        Ok(&self.next_lookahead.insert(next).0)
+    }
+
+    fn matches(&mut self, k: i32) -> Result<(), DaliaError> {
+        if self.lookahead.kind == k {
+            return self.consume();
+        }
+        self.error_position = Some(self.lookahead_position());
+        Err(DaliaError::ParseError(format!(
+            "expecting {}; found {}",
+            self.input.token_names(k as usize),
+            self.lookahead
+        )))
+    }
+
+    /// file: line* EOF
+    ///
+    /// Blank lines never produce tokens, so any number of them can appear before, between, or
+    /// after the configured lines.
+    ///
+    /// Parsing only records what each line asks for, without reading anything from disk, so
+    /// that a configuration can be checked without the directories it names.
+    pub fn parse(&mut self) -> Result<(), DaliaError> {
+        while self.lookahead.kind != TOKEN_EOF {
+            self.line()?;
+        }
+        self.matches(TOKEN_EOF)
+    }
+
+    /// Builds the aliases for every parsed line, reading the directories that `[*]` lines expand,
+    /// and then applies the exclusions.
+    pub fn resolve(&mut self) -> Result<(), DaliaError> {
+        let references = self.resolve_directives(false)?;
+        self.resolve_references(references)?;
+        self.apply_exclusions();
+        self.check_case_collisions()
+    }
+
+    /// Parses the whole input and resolves the parsed lines into aliases.
+    fn file(&mut self) -> Result<(), DaliaError> {
+        self.parse()?;
+        self.resolve()
+    }
+
+    /// Returns what each parsed line asks for, in the order the lines appear.
+    pub fn directives(&self) -> &[Directive] {
+        &self.directives
+    }
+
+    /// Returns the directory of every parsed glob line along with the `.daliaignore` file in it,
+    /// and every directory below them that a recursive glob read while resolving, which are what
+    /// the aliases they create depend on besides the configuration itself.
+    pub fn glob_paths(&self) -> Vec<PathBuf> {
+        self.directives
+            .iter()
+            .filter_map(|directive| match directive {
+                Directive::GlobExpansion { base, .. } if self.options.expand_glob => {
+                    Some(PathBuf::from(expand_read_path(base)))
+                }
+                _ => None,
+            })
+            .flat_map(|dir| {
+                let ignore_file = dir.join(IGNORE_FILE);
+                [dir, ignore_file]
+            })
+            .chain(self.nested_glob_dirs.iter().cloned())
+            .collect()
+    }
+
+    /// Returns every path that a line with `|` between its paths checks for, since which of them
+    /// exist decides the alias it creates.
+    pub fn fallback_paths(&self) -> Vec<PathBuf> {
+        self.directives
+            .iter()
+            .flat_map(|directive| match directive {
+                Directive::FallbackAlias { paths, .. } => paths.as_slice(),
+                _ => &[],
+            })
+            .map(|path| PathBuf::from(expand_read_path(path)))
+            .collect()
+    }
+
+    /// Returns the names of the environment variables used in the directories of glob lines and
+    /// the paths of `|` lines, which dalia expands itself rather than leaving to the shell.
+    pub fn environment_variables(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .directives
+            .iter()
+            .flat_map(|directive| match directive {
+                Directive::GlobExpansion { base, .. } => vec![base.as_str()],
+                Directive::FallbackAlias { paths, .. } => {
+                    paths.iter().map(String::as_str).collect()
+                }
+                _ => Vec::new(),
+            })
+            .flat_map(referenced_variables)
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Resolves every directive in order except for references, which are returned with the
+    /// section they're in applied to their names, to be resolved once every other alias exists.
+    /// When `collect_errors` is set, errors are recorded and the remaining directives are still
+    /// resolved.
+    fn resolve_directives(&mut self, collect_errors: bool) -> Result<Vec<Directive>, DaliaError> {
+        self.section = None;
+        self.nested_glob_dirs.clear();
+        let mut references = Vec::new();
+        let mut scans = scan_globs(&self.directives, &self.options);
+        for (i, directive) in self.directives.clone().into_iter().enumerate() {
+            let result = match directive {
+                Directive::GlobExpansion {
+                    base,
+                    options,
+                    line,
+                } => {
+                    self.line = line;
+                    match scans[i].take() {
+                        _ if !self.options.expand_glob => {
+                            let dir = strip_trailing_slash(&base).to_string();
+                            self.unexpanded_globs.push(dir);
+                            Ok(())
+                        }
+                        Some(scan) => self.add_glob_aliases(base, &options, line, scan),
+                        None => self.expand_glob_paths(base, &options, line),
+                    }
+                }
+                Directive::AliasReference {
+                    alias,
+                    target,
+                    path,
+                    command,
+                    line,
+                } => {
+                    references.push(Directive::AliasReference {
+                        alias: self.in_section(alias),
+                        target,
+                        path,
+                        command,
+                        line,
+                    });
+                    Ok(())
+                }
+                directive => self.resolve_directive(directive),
+            };
+            match result {
+                Err(e) if collect_errors => self.errors.push(e),
+                result => result?,
+            }
+        }
+        Ok(references)
+    }
+
+    /// Prefixes `alias` with the name of the section it's in, if any.
+    fn in_section(&self, alias: String) -> String {
+        match &self.section {
+            Some(section) => format!("{}{}{}", section, self.options.section_separator, alias),
+            None => alias,
+        }
+    }
+
+    fn resolve_directive(&mut self, directive: Directive) -> Result<(), DaliaError> {
+        if let Some(line) = directive.line() {
+            self.line = line;
+        }
+        match directive {
+            Directive::ExplicitAlias {
+                alias,
+                path,
+                command,
+                line,
+            } => {
+                let alias = self.in_section(alias);
+                if let Some(command) = command {
+                    self.commands.insert(alias.to_owned(), command);
+                }
+                self.add_path_alias(Some(alias), path, line)
+            }
+            Directive::DerivedAlias { path, line } => self.add_path_alias(None, path, line),
+            Directive::AliasReference {
+                alias,
+                target,
+                path,
+                command,
+                line,
+            } => {
+                let base = match self.int_rep.get(&target) {
+                    Some(base) => base.to_owned(),
+                    None => {
+                        return Err(DaliaError::ParseError(format!(
+                            "alias '{}' on line {} refers to '@{}', which isn't defined",
+                            alias, line, target
+                        )))
+                    }
+                };
+                if let Some(command) = command {
+                    self.commands.insert(alias.to_owned(), command);
+                }
+                let path = normalize_path(&format!("{}{}", base, path));
+                self.add_path_alias(Some(alias), path, line)
+            }
+            Directive::GlobExpansion {
+                base,
+                options,
+                line,
+            } => self.expand_glob_paths(base, &options, line),
+            Directive::Case { preserve } => {
+                self.options.preserve_case = preserve;
+                Ok(())
+            }
+            Directive::Section { name } => {
+                self.section = name;
+                Ok(())
+            }
+            Directive::FallbackAlias {
+                alias,
+                paths,
+                command,
+                line,
+            } => {
+                let path = paths
+                    .iter()
+                    .find(|path| Path::new(&expand_read_path(path)).exists());
+                let path = match path {
+                    Some(path) => path.to_owned(),
+                    None => {
+                        self.warnings.push(format!(
+                            "skipping the alias on line {}: none of {} exist",
+                            line,
+                            paths.join(", ")
+                        ));
+                        return Ok(());
+                    }
+                };
+                self.resolve_directive(match alias {
+                    Some(alias) => Directive::ExplicitAlias {
+                        alias,
+                        path,
+                        command,
+                        line,
+                    },
+                    None => Directive::DerivedAlias { path, line },
+                })
+            }
+        }
+    }
+
+    /// Resolves `[name]@alias/path` lines in whichever order lets each one find the alias it
+    /// refers to, including aliases that are themselves defined by a reference.
+    fn resolve_references(&mut self, mut pending: Vec<Directive>) -> Result<(), DaliaError> {
+        while !pending.is_empty() {
+            let waiting: HashMap<String, String> = pending
+                .iter()
+                .filter_map(|d| match d {
+                    Directive::AliasReference { alias, target, .. } => {
+                        Some((alias.to_owned(), target.to_owned()))
+                    }
+                    _ => None,
+                })
+                .collect();
+            let (ready, blocked): (Vec<Directive>, Vec<Directive>) =
+                pending.into_iter().partition(|d| match d {
+                    Directive::AliasReference { target, .. } => !waiting.contains_key(target),
+                    _ => true,
+                });
+            if ready.is_empty() {
+                return Err(DaliaError::CircularReference(reference_cycle(&waiting)));
+            }
+            for directive in ready {
+                self.resolve_directive(directive)?;
+            }
+            pending = blocked;
+        }
+        Ok(())
+    }
+
+    /// Reports aliases whose names differ only in case, which are easy to mix up when typing.
+    fn check_case_collisions(&mut self) -> Result<(), DaliaError> {
+        let mut by_lowercase: HashMap<String, Vec<&String>> = HashMap::new();
+        for alias in self.int_rep.keys() {
+            by_lowercase
+                .entry(alias.to_lowercase())
+                .or_default()
+                .push(alias);
+        }
+
+        let mut collisions: Vec<String> = by_lowercase
+            .values_mut()
+            .filter(|aliases| aliases.len() > 1)
+            .map(|aliases| {
+                aliases.sort();
+                let names: Vec<String> = aliases.iter().map(|a| format!("'{}'", a)).collect();
+                format!("aliases {} differ only in case", names.join(" and "))
+            })
+            .collect();
+        collisions.sort();
+
+        if self.options.strict {
+            if let Some(collision) = collisions.into_iter().next() {
+                return Err(DaliaError::ParseError(collision));
+            }
+        } else {
+            self.warnings.append(&mut collisions);
+        }
+        Ok(())
+    }
+
+    pub fn process_input(&mut self) -> Result<(), DaliaError> {
+        self.file()
+    }
+
+    /// Parses the whole input like process_input, but instead of stopping at the first error it
+    /// records each one and carries on with the next line, returning every alias that could
+    /// still be parsed alongside all of the errors.
+    pub fn process_input_collecting_errors(
+        &mut self,
+    ) -> (HashMap<String, String>, Vec<DaliaError>) {
+        while self.lookahead.kind != TOKEN_EOF {
+            let line = self.lookahead_line;
+            if let Err(e) = self.line() {
+                self.errors.push(e);
+                self.recover(line);
+            }
+        }
+        let references = self.resolve_directives(true).unwrap_or_default();
+        if let Err(e) = self.resolve_references(references) {
+            self.errors.push(e);
+        }
+        self.apply_exclusions();
+        if let Err(e) = self.check_case_collisions() {
+            self.errors.push(e);
+        }
+        (self.aliases(), self.errors.to_owned())
+    }
+
+    /// Skips the remaining tokens on the given line so parsing can resume on the next one.
+    fn recover(&mut self, line: usize) {
+        while self.lookahead.kind != TOKEN_EOF && self.lookahead_line == line {
+            let _ = self.consume();
         }
     }
 
-    pub fn aliases(&self) -> HashMap<String, String> {
-        self.int_rep.to_owned()
-    }
+    pub fn line(&mut self) -> Result<(), DaliaError> {
+        if self.lookahead.kind == TOKEN_VERSION {
+            return self.version();
+        }
+        if self.lookahead.kind == TOKEN_DIRECTIVE && self.lookahead.text.as_str() == "case" {
+            return self.case();
+        }
+        if self.lookahead.kind == TOKEN_SECTION {
+            return self.section();
+        }
+        let active = self.guard()?;
+        if self.lookahead.kind == TOKEN_VARIABLE {
+            return self.definition(active);
+        }
+        if self.lookahead.kind == TOKEN_BANG {
+            return self.exclusion(active);
+        }
+        let mut alias: Option<String> = None;
+        let mut command: Option<String> = None;
+        let mut glob: Option<GlobOptions> = None;
+        if self.lookahead.kind == TOKEN_LBRACK {
+            self.matches(TOKEN_LBRACK)?;
+
+            if self.lookahead.kind == TOKEN_GLOB {
+                let (text, line) = (self.lookahead.text.to_string(), self.lookahead_line);
+                self.glob()?;
+                let mut options = GlobOptions::from_token(&text, line)?;
+                if self.lookahead.kind == TOKEN_COLON {
+                    self.matches(TOKEN_COLON)?;
+                    let pattern = self.lookahead.text.to_string();
+                    self.matches(TOKEN_PATTERN)?;
+                    if pattern.is_empty() {
+                        return Err(DaliaError::ParseError(format!(
+                            "missing pattern after ':' on line {}",
+                            line
+                        )));
+                    }
+                    options.pattern = Some(pattern);
+                }
+                while self.lookahead.kind == TOKEN_BANG {
+                    self.matches(TOKEN_BANG)?;
+                    let exclude = self.lookahead.text.to_string();
+                    self.matches(TOKEN_PATTERN)?;
+                    if exclude.is_empty() {
+                        return Err(DaliaError::ParseError(format!(
+                            "missing name after '!' on line {}",
+                            line
+                        )));
+                    }
+                    options.exclude.push(exclude);
+                }
+                glob = Some(options);
+            } else if self.lookahead.kind == TOKEN_ALIAS {
+                alias = Some(self.lookahead.text.to_string());
+                let (start, line) = (self.lookahead.span().0, self.lookahead_line);
+                self.alias()?;
+                if self.lookahead.kind == TOKEN_ALIAS {
+                    return Err(self.spaced_alias(start, line));
+                }
+                if self.lookahead.kind == TOKEN_COLON {
+                    self.matches(TOKEN_COLON)?;
+                    command = Some(self.lookahead.text.to_string());
+                    self.alias()?;
+                }
+            }
+
+            self.matches(TOKEN_RBRACK)?;
+
+            if self.lookahead.kind == TOKEN_TEMPLATE {
+                let (template, line) = (self.lookahead.text.to_string(), self.lookahead_line);
+                self.matches(TOKEN_TEMPLATE)?;
+                if alias.is_none() || command.is_some() {
+                    return Err(DaliaError::ParseError(format!(
+                        "a template on line {} needs a custom name without a command, like [name]{{...}}",
+                        line
+                    )));
+                }
+                if !template.contains(TEMPLATE_PATH) {
+                    return Err(DaliaError::ParseError(format!(
+                        "template '{}' on line {} doesn't contain {}",
+                        template, line, TEMPLATE_PATH
+                    )));
+                }
+                command = Some(template);
+            }
+        } else if self.lookahead.kind == TOKEN_ALIAS {
+            alias = Some(self.assignment()?);
+        }
+        if self.lookahead.kind == TOKEN_REF {
+            return self.reference(alias, command, glob.is_some(), active);
+        }
+        let (path, line) = (self.lookahead.text.to_string(), self.lookahead_line);
+        self.path()?;
+        if path.trim().is_empty() {
+            return Err(DaliaError::ParseError(format!(
+                "missing path after '=' on line {}",
+                line
+            )));
+        }
+        let description = self.description()?;
+        if glob.is_some() && description.is_some() {
+            return Err(DaliaError::ParseError(format!(
+                "a description on line {} needs a single alias, not a [*] line",
+                line
+            )));
+        }
+        if !active {
+            return Ok(());
+        }
+        if let Some(description) = description {
+            self.line_descriptions.insert(line, description);
+        }
+        if let Some(mut glob) = glob {
+            let (path, exclude) = split_exclusions(&path);
+            glob.exclude.extend(exclude);
+            let base = self.resolve_path(normalize_path(&self.substitute(path, line)?));
+            self.directives.push(Directive::GlobExpansion {
+                base,
+                options: glob,
+                line,
+            });
+            return Ok(());
+        }
+        let candidates = split_fallbacks(&path);
+        if candidates.len() > 1 {
+            let mut paths = Vec::new();
+            for candidate in &candidates {
+                if candidate.trim().is_empty() {
+                    return Err(DaliaError::ParseError(format!(
+                        "missing path around '{}' on line {}",
+                        FALLBACK_SEPARATOR, line
+                    )));
+                }
+                paths.push(self.resolve_path(normalize_path(&self.substitute(candidate, line)?)));
+            }
+            self.directives.push(Directive::FallbackAlias {
+                alias,
+                paths,
+                command,
+                line,
+            });
+            return Ok(());
+        }
+        let path = &candidates[0];
+        let path = self.resolve_path(normalize_path(&self.substitute(path, line)?));
+        self.directives.push(match alias {
+            Some(alias) => Directive::ExplicitAlias {
+                alias,
+                path,
+                command,
+                line,
+            },
+            None => Directive::DerivedAlias { path, line },
+        });
+        Ok(())
+    }
+
+    /// Handles the `@alias/path` part of a line, whose path continues the path of another alias.
+    fn reference(
+        &mut self,
+        alias: Option<String>,
+        command: Option<String>,
+        is_glob: bool,
+        active: bool,
+    ) -> Result<(), DaliaError> {
+        let (target, line) = (self.lookahead.text.to_string(), self.lookahead_line);
+        self.matches(TOKEN_REF)?;
+        let path = self.lookahead.text.to_string();
+        self.path()?;
+        let description = self.description()?;
+        let alias = match alias {
+            Some(alias) if !is_glob => alias,
+            _ => {
+                return Err(DaliaError::ParseError(format!(
+                    "a reference to '@{}' on line {} needs a custom name, like [name]@{}/path",
+                    target, line, target
+                )))
+            }
+        };
+        if target.is_empty() {
+            return Err(DaliaError::ParseError(format!(
+                "missing alias name after '@' on line {}",
+                line
+            )));
+        }
+        if !active {
+            return Ok(());
+        }
+        let path = self.substitute(&path, line)?;
+        if let Some(description) = description {
+            self.line_descriptions.insert(line, description);
+        }
+        self.directives.push(Directive::AliasReference {
+            alias,
+            target,
+            path,
+            command,
+            line,
+        });
+        Ok(())
+    }
+
+    /// Consumes the quoted description after a path, if there is one, and returns it.
+    fn description(&mut self) -> Result<Option<String>, DaliaError> {
+        if self.lookahead.kind != TOKEN_DESCRIPTION {
+            return Ok(None);
+        }
+        let description = self.lookahead.text.to_string();
+        self.matches(TOKEN_DESCRIPTION)?;
+        Ok(Some(description))
+    }
+
+    /// Consumes the `name =` part of a `name = path` line, returning the name.
+    fn assignment(&mut self) -> Result<String, DaliaError> {
+        let name = self.lookahead.text.to_string();
+        self.alias()?;
+        if self.lookahead.kind != TOKEN_EQUALS {
+            // Without an `=`, the name was most likely meant to be the start of a path.
+            return Err(DaliaError::ParseError(format!(
+                "expecting PATH; found {}",
+                Token::new(TOKEN_ALIAS, Cow::Owned(name))
+            )));
+        }
+        self.matches(TOKEN_EQUALS)?;
+        Ok(name)
+    }
+
+    /// Handles a `$name = value` line, which defines a variable that paths on later lines can
+    /// reference as `$name`.
+    fn definition(&mut self, active: bool) -> Result<(), DaliaError> {
+        let (name, line) = (self.lookahead.text.to_string(), self.lookahead_line);
+        self.variable()?;
+        self.matches(TOKEN_EQUALS)?;
+        let value = self.lookahead.text.to_string();
+        self.path()?;
+        if value.trim().is_empty() {
+            return Err(DaliaError::ParseError(format!(
+                "missing path after '=' on line {}",
+                line
+            )));
+        }
+        if !active {
+            return Ok(());
+        }
+        if let Some((_, defined)) = self.variables.get(&name) {
+            return Err(DaliaError::ParseError(format!(
+                "variable '{}' on line {} is already defined on line {}",
+                name, line, defined
+            )));
+        }
+        let value = normalize_path(&self.substitute(&value, line)?);
+        self.variables.insert(name, (value, line));
+        Ok(())
+    }
+
+    /// Replaces every `$name` reference in `path` with the value of the variable it names.
+    /// References to names that aren't defined in the configuration, but are set in the
+    /// environment, are left for the shell to expand.
+    fn substitute(&self, path: &str, line: usize) -> Result<String, DaliaError> {
+        let mut substituted = String::with_capacity(path.len());
+        let mut chars = path.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                substituted.push(c);
+                continue;
+            }
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if !is_alias_char(c) {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            match self.variables.get(&name) {
+                Some((value, _)) => substituted.push_str(value),
+                None if name.is_empty() || std::env::var_os(&name).is_some() => {
+                    substituted.push('$');
+                    substituted.push_str(&name);
+                }
+                None => {
+                    return Err(DaliaError::ParseError(format!(
+                        "undefined variable '{}' on line {}",
+                        name, line
+                    )))
+                }
+            }
+        }
+        Ok(substituted)
+    }
+
+    /// Handles a `!name` or `!path` line, which removes the matching alias once the whole
+    /// configuration has been parsed.
+    fn exclusion(&mut self, active: bool) -> Result<(), DaliaError> {
+        let line = self.lookahead_line;
+        self.matches(TOKEN_BANG)?;
+        let text = self.lookahead.text.to_string();
+        let exclusion = if self.lookahead.kind == TOKEN_ALIAS {
+            self.alias()?;
+            Exclusion::Name(text)
+        } else {
+            self.path()?;
+            Exclusion::Path(self.resolve_path(normalize_path(&self.substitute(&text, line)?)))
+        };
+        if active {
+            self.exclusions.push((exclusion, line));
+        }
+        Ok(())
+    }
+
+    /// Joins a relative path onto the base directory, leaving every other path unchanged. `.` and
+    /// `..` in a relative path refer to the base directory and its parent.
+    fn resolve_path(&self, path: String) -> String {
+        let base = match &self.options.base {
+            Some(base) if !is_absolute_config_path(&path) => base,
+            _ => return path,
+        };
+        let mut resolved = base.to_path_buf();
+        for component in Path::new(&path).components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                component => resolved.push(component),
+            }
+        }
+        normalize_path(&resolved.display().to_string())
+    }
+
+    /// Removes the aliases matched by exclusions, warning about exclusions that match nothing.
+    fn apply_exclusions(&mut self) {
+        for (exclusion, line) in std::mem::take(&mut self.exclusions) {
+            let (excluded, text): (Vec<String>, &str) = match &exclusion {
+                Exclusion::Name(name) if self.int_rep.contains_key(name) => {
+                    (vec![name.to_owned()], name)
+                }
+                Exclusion::Name(name) => (Vec::new(), name),
+                Exclusion::Path(path) => (
+                    self.int_rep
+                        .iter()
+                        .filter(|(_, p)| *p == path)
+                        .map(|(alias, _)| alias.to_owned())
+                        .collect(),
+                    path,
+                ),
+            };
+            if excluded.is_empty() {
+                self.warnings.push(format!(
+                    "exclusion '!{}' on line {} doesn't match any alias",
+                    text, line
+                ));
+            }
+            for alias in excluded {
+                self.int_rep.remove(&alias);
+                self.sources.remove(&alias);
+                self.commands.remove(&alias);
+                self.lines.remove(&alias);
+            }
+        }
+    }
+
+    /// Handles the `version = N` header, warning when the configuration is newer than this parser.
+    fn version(&mut self) -> Result<(), DaliaError> {
+        let (text, line) = (self.lookahead.text.to_string(), self.lookahead_line);
+        self.matches(TOKEN_VERSION)?;
+        let version = match text.parse::<u32>() {
+            Ok(version) if version > 0 => version,
+            _ => {
+                return Err(DaliaError::ParseError(format!(
+                    "invalid format version '{}' on line {}",
+                    text, line
+                )))
+            }
+        };
+        if version > FORMAT_VERSION {
+            let warning = DaliaWarning::UnsupportedVersion {
+                found: version,
+                max_supported: FORMAT_VERSION,
+            };
+            self.warnings.push(warning.to_string());
+            self.typed_warnings.push(warning);
+        }
+        self.format_version = Some(version);
+        Ok(())
+    }
+
+    /// Handles an `@case preserve` or `@case lower` line, which decides how the names of aliases
+    /// derived from the paths on the lines after it are cased.
+    fn case(&mut self) -> Result<(), DaliaError> {
+        self.directive()?;
+        let mode = self.lookahead.text.to_string();
+        self.alias()?;
+        match mode.as_str() {
+            "preserve" => self.directives.push(Directive::Case { preserve: true }),
+            "lower" => self.directives.push(Directive::Case { preserve: false }),
+            _ => {
+                return Err(DaliaError::ParseError(format!(
+                    "unknown case '{}'; expecting preserve or lower",
+                    mode
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a `[section: name]` line.
+    fn section(&mut self) -> Result<(), DaliaError> {
+        let (name, line) = (self.lookahead.text.to_string(), self.lookahead_line);
+        self.matches(TOKEN_SECTION)?;
+        if !name.is_empty() && !is_valid_alias_name(&name) {
+            return Err(DaliaError::ParseError(format!(
+                "invalid section name '{}' on line {}; {}",
+                name, line, ALIAS_NAME_RULE
+            )));
+        }
+        self.directives.push(Directive::Section {
+            name: Some(name).filter(|name| !name.is_empty()),
+        });
+        Ok(())
+    }
+
+    /// Consumes an optional `@os` guard, returning whether the rest of the line applies to the
+    /// target operating system.
+    fn guard(&mut self) -> Result<bool, DaliaError> {
+        if self.lookahead.kind != TOKEN_DIRECTIVE {
+            return Ok(true);
+        }
+        let os = self.lookahead.text.to_string();
+        if !OPERATING_SYSTEMS.contains(&os.as_str()) {
+            return Err(DaliaError::ParseError(format!(
+                "unknown directive '@{}'",
+                os
+            )));
+        }
+        self.directive()?;
+        Ok(os == self.options.target_os)
+    }
+
+    /// Warns about an alias name that ends with `.`, which leaves a namespace like `proj.`
+    /// unfinished. That's likely a mistake, but the name still works as an alias.
+    fn warn_about_trailing_dot(&mut self, alias: &str, line: usize) {
+        if alias.ends_with('.') {
+            self.warnings.push(format!(
+                "alias name '{}' on line {} ends with '.'",
+                alias, line
+            ));
+        }
+    }
+
+    fn add_path_alias(
+        &mut self,
+        alias: Option<String>,
+        path: String,
+        line: usize,
+    ) -> Result<(), DaliaError> {
+        let path = strip_trailing_slash(&path).to_string();
+        let (alias, source) = match alias {
+            Some(a) => (a, AliasSource::Explicit),
+            None => match self.alias_from_path(&path) {
+                Some(a) if has_alias_name(&path) => (self.in_section(a), AliasSource::PathDerived),
+                _ => {
+                    return Err(DaliaError::ParseError(format!(
+                    "can't name an alias for '{}' on line {}; give it a custom name like [name]{}",
+                    path, line, path
+                )))
+                }
+            },
+        };
+        if !is_valid_alias_name(&alias) {
+            return Err(DaliaError::ParseError(format!(
+                "invalid alias name '{}' on line {}; {}",
+                alias, line, ALIAS_NAME_RULE
+            )));
+        }
+        self.warn_about_trailing_dot(&alias, line);
+        self.insert_alias(alias.to_owned(), path.to_owned(), source)?;
+        if let Some(description) = self.line_descriptions.get(&line) {
+            if self.int_rep.get(&alias) == Some(&path) {
+                self.descriptions.insert(alias, description.to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    fn expand_glob_paths(
+        &mut self,
+        dir: String,
+        glob: &GlobOptions,
+        line: usize,
+    ) -> Result<(), DaliaError> {
+        let scan = scan_glob(&dir, glob, &self.options, line);
+        self.add_glob_aliases(dir, glob, line, scan)
+    }
+
+    /// Creates aliases for the directories a glob line's scan found, in the order they were
+    /// found.
+    fn add_glob_aliases(
+        &mut self,
+        dir: String,
+        glob: &GlobOptions,
+        line: usize,
+        scan: Result<GlobScan, DaliaError>,
+    ) -> Result<(), DaliaError> {
+        let scan = scan?;
+        self.warnings.extend(scan.warnings);
+        self.nested_glob_dirs
+            .extend(scan.nested_dirs.into_iter().map(PathBuf::from));
+        let count = scan.entries.len();
+        for entry in scan.entries {
+            if entry.is_file {
+                self.files.insert(entry.path.clone());
+            }
+            self.insert_alias_from_path(entry.path, glob, AliasSource::GlobExpanded)?;
+            if self.int_rep.len() > self.options.max_aliases {
+                return Err(too_many_aliases(line, self.options.max_aliases));
+            }
+        }
+        if count > self.options.max_glob_aliases {
+            let problem = format!(
+                "the glob on line {} created {} aliases, more than the limit of {}; narrow it with a pattern or exclusions, or raise the limit with --max-glob",
+                line, count, self.options.max_glob_aliases
+            );
+            if self.options.strict {
+                return Err(DaliaError::ParseError(problem));
+            }
+            self.warnings.push(problem);
+        }
+        if let (Some(pattern), 0) = (&glob.pattern, count) {
+            self.warnings.push(format!(
+                "pattern '{}' on line {} didn't match any directories in {}",
+                pattern, line, dir
+            ));
+        }
+        Ok(())
+    }
+
+    /// Adds an alias named after the directory, with the glob's prefix and suffix, skipping
+    /// directories whose names can't be used as an alias with a warning.
+    fn insert_alias_from_path(
+        &mut self,
+        dir: String,
+        glob: &GlobOptions,
+        source: AliasSource,
+    ) -> Result<(), DaliaError> {
+        let dir = strip_trailing_slash(&dir).to_string();
+        let alias = match self.alias_from_path(&dir) {
+            Some(alias) => glob.alias_name(&alias),
+            None => return Ok(()),
+        };
+        if !is_valid_alias_name(&alias) {
+            self.warnings.push(format!(
+                "skipping {}: '{}' isn't a valid alias name",
+                dir, alias
+            ));
+            return Ok(());
+        }
+        self.warn_about_trailing_dot(&alias, self.line);
+        let alias = self.in_section(alias);
+        if source == AliasSource::GlobExpanded && self.options.disambiguate {
+            return self.insert_disambiguated_alias(alias, dir);
+        }
+        self.insert_alias(alias, dir, source)
+    }
+
+    /// Adds an alias for an expanded directory, prefixing it and any other expanded directory
+    /// with the same name with the name of their parent directory.
+    fn insert_disambiguated_alias(&mut self, alias: String, dir: String) -> Result<(), DaliaError> {
+        let source = AliasSource::GlobExpanded;
+        let clash = match (self.int_rep.get(&alias), self.sources.get(&alias)) {
+            (Some(existing), Some(AliasSource::GlobExpanded)) if *existing != dir => {
+                Some(existing.to_owned())
+            }
+            _ => None,
+        };
+        if let Some(existing) = clash {
+            self.int_rep.remove(&alias);
+            self.sources.remove(&alias);
+            let line = self.lines.remove(&alias);
+            let renamed = self.parent_qualified_alias(&alias, &existing);
+            self.insert_alias(renamed.to_owned(), existing, source)?;
+            if let Some(line) = line {
+                self.lines.insert(renamed, line);
+            }
+            self.ambiguous.insert(alias.to_owned());
+        }
+        if self.ambiguous.contains(&alias) {
+            let renamed = self.parent_qualified_alias(&alias, &dir);
+            return self.insert_alias(renamed, dir, source);
+        }
+        self.insert_alias(alias, dir, source)
+    }
+
+    /// Prefixes `alias` with the name of the directory containing `dir`, e.g. `work-notes` for
+    /// `/home/me/work/notes`.
+    fn parent_qualified_alias(&self, alias: &str, dir: &str) -> String {
+        let parent = Path::new(dir)
+            .parent()
+            .and_then(|p| p.to_str())
+            .unwrap_or("");
+        match self.alias_from_path(parent) {
+            Some(parent) => format!("{}-{}", parent, alias),
+            None => alias.to_string(),
+        }
+    }
+
+    /// Derives an alias name from the last component of `dir`, leaving out anything after its
+    /// last dot, so `/code/dalia.rs` is named `dalia`. Names with dots have to be given as
+    /// custom names, like `[dalia.rs]/code/dalia.rs`.
+    fn alias_from_path(&self, dir: &str) -> Option<String> {
+        let alias = Path::new(dir).file_stem()?.to_str()?;
+        if self.options.preserve_case {
+            Some(alias.to_string())
+        } else {
+            Some(alias.to_lowercase())
+        }
+    }
+
+    /// Adds an alias to the internal representation, failing if the alias already points
+    /// somewhere else. A custom name always wins over the same name from a `[*]` line, whichever
+    /// line comes first, with a warning about the directory that doesn't get the alias.
+    fn insert_alias(
+        &mut self,
+        alias: String,
+        path: String,
+        source: AliasSource,
+    ) -> Result<(), DaliaError> {
+        let existing_source = self.sources.get(&alias).copied();
+        if let Some(existing) = self.int_rep.get(&alias) {
+            match (existing_source, source) {
+                _ if *existing == path => {}
+                _ if same_directory(existing, &path, self.options.canonicalize) => {
+                    self.warnings.push(format!(
+                        "alias '{}' is configured for both {} and {}, which are the same directory; keeping {}",
+                        alias, existing, path, existing
+                    ));
+                    return Ok(());
+                }
+                (Some(AliasSource::Explicit), AliasSource::GlobExpanded) => {
+                    self.warnings.push(format!(
+                        "alias '{}' for {} is shadowed by the custom name for {}",
+                        alias, path, existing
+                    ));
+                    return Ok(());
+                }
+                (Some(AliasSource::GlobExpanded), AliasSource::Explicit) => {
+                    self.warnings.push(format!(
+                        "alias '{}' for {} is shadowed by the custom name for {}",
+                        alias, existing, path
+                    ));
+                }
+                _ => {
+                    return Err(DaliaError::ParseError(format!(
+                        "duplicate alias '{}' for {} on line {} and {} on line {}",
+                        alias,
+                        existing,
+                        self.lines.get(&alias).copied().unwrap_or_default(),
+                        path,
+                        self.line
+                    )))
+                }
+            }
+        }
+        if existing_source != Some(AliasSource::Explicit) {
+            self.sources.insert(alias.clone(), source);
+        }
+        if self.int_rep.get(&alias) != Some(&path) {
+            self.lines.insert(alias.clone(), self.line);
+        }
+        self.int_rep.insert(alias, path);
+        Ok(())
+    }
+
+    fn alias(&mut self) -> Result<(), DaliaError> {
+        self.matches(TOKEN_ALIAS)
+    }
+
+    /// Builds the error for a custom name with spaces in it, like `[my alias]`, whose first word
+    /// starts at byte `start` and whose next word is the lookahead token. The error's position
+    /// is recorded, so the message leaves the line out.
+    fn spaced_alias(&mut self, start: usize, line: usize) -> DaliaError {
+        self.error_position = Some(self.lookahead_position());
+        let mut end = start;
+        while self.lookahead.kind == TOKEN_ALIAS && self.lookahead_line == line {
+            end = self.lookahead.span().1;
+            if self.consume().is_err() {
+                break;
+            }
+        }
+        DaliaError::ParseError(format!(
+            "alias names can't contain spaces: '{}'",
+            &self.source()[start..end]
+        ))
+    }
+
+    fn variable(&mut self) -> Result<(), DaliaError> {
+        self.matches(TOKEN_VARIABLE)
+    }
+
+    fn directive(&mut self) -> Result<(), DaliaError> {
+        self.matches(TOKEN_DIRECTIVE)
+    }
+
+    fn glob(&mut self) -> Result<(), DaliaError> {
+        self.matches(TOKEN_GLOB)
+    }
+
+    fn path(&mut self) -> Result<(), DaliaError> {
+        self.matches(TOKEN_PATH)
+    }
+}
+
+/// Normalizes a configured path by trimming surrounding whitespace, collapsing repeated
+/// separators, and removing a trailing separator unless the path is a root like `/` or `C:\`.
+///
+/// Separators are platform specific, so a backslash is only treated as one on Windows; elsewhere
+/// it's an ordinary character, e.g. escaping a space in `~/Mobile\ Documents`.
+fn normalize_path(path: &str) -> String {
+    let mut normalized = String::with_capacity(path.len());
+    let mut previous_separator = false;
+    for c in path.trim().chars() {
+        let separator = std::path::is_separator(c);
+        if !(separator && previous_separator) {
+            normalized.push(c);
+        }
+        previous_separator = separator;
+    }
+    strip_trailing_slash(&normalized).to_string()
+}
+
+/// Follows the references in `waiting`, which maps each alias to the one it refers to, from its
+/// first alias until one repeats, and returns the aliases that form the cycle.
+fn reference_cycle(waiting: &HashMap<String, String>) -> Vec<String> {
+    let mut chain: Vec<String> = Vec::new();
+    let mut alias = waiting.keys().min().cloned().unwrap_or_default();
+    while !chain.contains(&alias) {
+        chain.push(alias.to_owned());
+        alias = match waiting.get(&alias) {
+            Some(target) => target.to_owned(),
+            None => break,
+        };
+    }
+    let start = chain.iter().position(|a| *a == alias).unwrap_or(0);
+    let mut cycle = chain.split_off(start);
+    cycle.push(alias);
+    cycle
+}
+
+/// Describes the characters allowed in alias names, for error messages.
+const ALIAS_NAME_RULE: &str =
+    "alias names may only contain letters, digits, '_', '-', and '.', and can't start with '-'";
+
+/// Reports whether `alias` is safe to use as a shell alias name: it contains only letters,
+/// digits, `_`, `-`, and `.`, and doesn't start with a `-` that would be read as an option.
+fn is_valid_alias_name(alias: &str) -> bool {
+    !alias.is_empty()
+        && !alias.starts_with('-')
+        && alias
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Reports whether `path` is absolute once the shell expands it: it starts at the root, the home
+/// directory, a drive letter, or a variable the shell fills in.
+pub(crate) fn is_absolute_config_path(path: &str) -> bool {
+    let mut chars = path.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('/'), _, _) | (Some('~'), _, _) | (Some('$'), _, _) | (Some('\\'), _, _) => true,
+        (Some(drive), Some(':'), Some('\\')) | (Some(drive), Some(':'), Some('/')) => {
+            drive.is_ascii_alphabetic()
+        }
+        _ => false,
+    }
+}
+
+/// Reports whether an alias name can be derived from `path`, which isn't the case for a root
+/// like `/` or for the home directory `~`.
+fn has_alias_name(path: &str) -> bool {
+    match Path::new(path).components().next_back() {
+        Some(Component::Normal(name)) => name != "~",
+        _ => false,
+    }
+}
+
+/// Reports whether two configured paths are the same directory once `~` and environment variables
+/// are expanded and repeated or trailing separators are removed. With `canonicalize`, paths that
+/// exist are also compared after resolving symbolic links and `..`.
+fn same_directory(a: &str, b: &str, canonicalize: bool) -> bool {
+    let expand = |p: &str| PathBuf::from(normalize_path(&expand_read_path(p)));
+    let (a, b) = (expand(a), expand(b));
+    if a == b {
+        return true;
+    }
+    canonicalize
+        && matches!(
+            (std::fs::canonicalize(&a), std::fs::canonicalize(&b)),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+/// Removes trailing separators from `s`, keeping a single one when `s` is a root like `/` or `C:\`.
+fn strip_trailing_slash(s: &str) -> &str {
+    let trimmed = s.trim_end_matches(std::path::is_separator);
+    if trimmed.len() == s.len() {
+        return s;
+    }
+    match Path::new(trimmed).components().next_back() {
+        Some(Component::Normal(_)) => trimmed,
+        _ => {
+            &s[..s.len()
+                - s[trimmed.len()..]
+                    .chars()
+                    .skip(1)
+                    .map(char::len_utf8)
+                    .sum::<usize>()]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::fs::{create_dir, create_dir_all, write};
+
+    use super::*;
+
+    #[test]
+    fn test_create_parser() {
+        let p = Parser::new("/some/absolute/path");
+        assert_eq!(
+            Token::new(TOKEN_PATH, Cow::Owned("/some/absolute/path".into())),
+            p.lookahead
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_create_parser_panics() {
+        Parser::new("");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_create_parser_panics_with_empty_str() {
+        Parser::new("    ");
+    }
+
+    #[test]
+    fn test_try_new_fails_with_empty_input() {
+        assert_eq!(Some(DaliaError::EmptyInput), Parser::try_new("").err());
+        assert_eq!(Some(DaliaError::EmptyInput), Parser::try_new(" \n\t").err());
+    }
+
+    #[test]
+    fn test_try_new_fails_when_the_first_token_is_invalid() {
+        assert!(matches!(
+            Parser::try_new("\0/some/path"),
+            Err(DaliaError::LexError(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_new() -> Result<(), DaliaError> {
+        let mut p = Parser::try_new("[work]/some/work/path")?;
+        p.file()?;
+        assert_eq!("/some/work/path", p.int_rep.get("work").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_consume() {
+        let mut p = Parser::new("[alias]/some/absolute/path");
+        let _ = p.consume();
+        assert_eq!(
+            Token::new(TOKEN_ALIAS, Cow::Owned("alias".into())),
+            p.lookahead
+        );
+    }
+
+    #[test]
+    fn test_parser_matches() {
+        let mut p = Parser::new("[alias]/some/absolute/path");
+        let _ = p.matches(TOKEN_LBRACK);
+        assert_eq!(
+            Token::new(TOKEN_ALIAS, Cow::Owned("alias".into())),
+            p.lookahead
+        );
+    }
+
+    #[test]
+    fn test_parser_does_not_match() {
+        let mut p = Parser::new("[alias]/some/absolute/path");
+        if let Err(e) = p.matches(TOKEN_RBRACK) {
+            assert_eq!("expecting RBRACK; found <'[', LBRACK>", e.to_string());
+        }
+    }
+
+    #[test]
+    fn test_parse_file_with_alias_config() -> Result<(), String> {
+        let mut p = Parser::new("[alias]/some/absolute/path");
+        p.file()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_file_with_single_path() -> Result<(), String> {
+        let mut p = Parser::new("/some/absolute/path");
+        p.file()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fails_with_invalid_path() {
+        let input = "some absolute path";
+        let mut p = Parser::new(input);
+        let result: Result<(), DaliaError> = p.file();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "expecting PATH; found <'some', ALIAS>"
+        )
+    }
+
+    #[test]
+    fn test_parse_relative_paths() -> Result<(), String> {
+        let input = "projects/foo\n[up]../shared\n[here]./notes\n[abs]/some/path";
+        let mut p = Parser::new(input);
+        p.file()?;
+        assert_eq!("projects/foo", p.int_rep.get("foo").unwrap());
+
+        let options = ParserOptions {
+            base: Some(PathBuf::from("/home/me")),
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options(input, options);
+        p.file()?;
+        assert_eq!("/home/me/projects/foo", p.int_rep.get("foo").unwrap());
+        assert_eq!("/home/shared", p.int_rep.get("up").unwrap());
+        assert_eq!("/home/me/notes", p.int_rep.get("here").unwrap());
+        assert_eq!("/some/path", p.int_rep.get("abs").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_peek_next_keeps_the_lookahead() -> Result<(), String> {
+        let mut p = Parser::new("[alias]/some/path\n/other/path");
+        assert_eq!(
+            &Token::new(TOKEN_ALIAS, Cow::Owned("alias".into())),
+            p.peek_next()?
+        );
+        assert_eq!(
+            &Token::new(TOKEN_ALIAS, Cow::Owned("alias".into())),
+            p.peek_next()?
+        );
+        assert_eq!(TOKEN_LBRACK, p.lookahead.kind);
+
+        p.matches(TOKEN_LBRACK)?;
+        assert_eq!(
+            Token::new(TOKEN_ALIAS, Cow::Owned("alias".into())),
+            p.lookahead
+        );
+        assert_eq!(TOKEN_RBRACK, p.peek_next()?.kind);
+        p.alias()?;
+        p.matches(TOKEN_RBRACK)?;
+        assert_eq!(TOKEN_PATH, p.peek_next()?.kind);
+        p.path()?;
+        assert_eq!(
+            Token::new(TOKEN_PATH, Cow::Owned("/other/path".into())),
+            p.lookahead
+        );
+        assert_eq!(2, p.lookahead_line);
+        assert_eq!(TOKEN_EOF, p.peek_next()?.kind);
+        p.path()?;
+        assert_eq!(TOKEN_EOF, p.lookahead.kind);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_complex_file() -> Result<(), String> {
+        let mut p = Parser::new(
+            r#"[alias]/another/absolute/path
+        /yet/another/path
+        "#,
+        );
+        p.file()?;
+        assert!(!p.int_rep.is_empty());
+        assert_eq!(2, p.int_rep.len());
+        assert_eq!("/another/absolute/path", p.int_rep.get("alias").unwrap());
+        assert_eq!("/yet/another/path", p.int_rep.get("path").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parsed_alias_is_lowercase() -> Result<(), String> {
+        let mut p = Parser::new("/absolute/Path");
+        p.file()?;
+        assert_eq!("/absolute/Path", p.int_rep.get("path").unwrap().as_str());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parsed_alias_with_tilde() -> Result<(), String> {
+        let mut p = Parser::new(
+            r#"
+        ~/absolute/Path
+        [another-path]~/absolute/Path
+        "#,
+        );
+        p.file()?;
+        assert!(!p.int_rep.is_empty());
+        assert_eq!("~/absolute/Path", p.int_rep.get("path").unwrap().as_str());
+        assert_eq!(
+            "~/absolute/Path",
+            p.int_rep.get("another-path").unwrap().as_str()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_asterisk() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let file_path = PathBuf::from(temp.as_ref());
+
+        let path1 = format!("{}/one", file_path.to_str().unwrap());
+        if let Err(e) = create_dir(&path1) {
+            return Err(format!("couldn't create temp dir one: {}", e));
+        }
+
+        let path2 = format!("{}/two", file_path.to_str().unwrap());
+        if let Err(e) = create_dir(&path2) {
+            return Err(format!("couldn't create temp dir two: {}", e));
+        }
+
+        let path3 = format!("{}/three", file_path.to_str().unwrap());
+        if let Err(e) = create_dir(&path3) {
+            return Err(format!("couldn't create temp dir three: {}", e));
+        }
+
+        let glob_path = format!("[*]{}", file_path.to_str().unwrap());
+        let mut p = Parser::new(glob_path.as_str());
+
+        p.file()?;
+
+        assert!(!p.int_rep.is_empty());
+        assert_eq!(3, p.int_rep.len());
+        assert_eq!(path1, p.int_rep.get("one").unwrap().to_string());
+        assert_eq!(path2, p.int_rep.get("two").unwrap().to_string());
+        assert_eq!(path3, p.int_rep.get("three").unwrap().to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_is_kept_without_expanding() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("one")).map_err(|e| e.to_string())?;
+
+        let options = ParserOptions {
+            expand_glob: false,
+            ..Default::default()
+        };
+        let config = format!(
+            "[*]{}/\n[work]/some/work/path\n[*]/some/other\n",
+            dir.display()
+        );
+        let mut p = Parser::new_with_options(&config, options);
+        p.file()?;
+
+        assert_eq!(1, p.int_rep.len());
+        assert_eq!("/some/work/path", p.int_rep.get("work").unwrap());
+        assert_eq!(
+            [dir.display().to_string(), "/some/other".to_string()],
+            p.unexpanded_globs()
+        );
+        assert!(p.glob_paths().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parsed_alias_preserves_case() -> Result<(), String> {
+        let options = ParserOptions {
+            preserve_case: true,
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options("/code/MyProject\n[Explicit]/code/other", options);
+        p.file()?;
+        assert_eq!("/code/MyProject", p.int_rep.get("MyProject").unwrap());
+        assert_eq!("/code/other", p.int_rep.get("Explicit").unwrap());
+        assert!(!p.int_rep.contains_key("myproject"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_case_directive() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let file_path = PathBuf::from(temp.as_ref());
+        let path = format!("{}/MixedCase", file_path.to_str().unwrap());
+        create_dir(&path).map_err(|e| format!("couldn't create temp dir: {}", e))?;
+
+        let input = format!(
+            "/before/MixedCase\n@case preserve\n[*]{}\n@case lower\n/after/OtherCase",
+            file_path.to_str().unwrap()
+        );
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+
+        assert_eq!("/before/MixedCase", p.int_rep.get("mixedcase").unwrap());
+        assert_eq!(path, p.int_rep.get("MixedCase").unwrap().to_string());
+        assert_eq!("/after/OtherCase", p.int_rep.get("othercase").unwrap());
+        assert_eq!(3, p.int_rep.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fails_with_unknown_case() {
+        let mut p = Parser::new("@case upper\n/some/path");
+        assert_eq!(
+            "unknown case 'upper'; expecting preserve or lower",
+            p.file().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_glob_lowercases_alias_by_default() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let file_path = PathBuf::from(temp.as_ref());
+        let path = format!("{}/MixedCase", file_path.to_str().unwrap());
+        create_dir(&path).map_err(|e| format!("couldn't create temp dir: {}", e))?;
+
+        let mut p = Parser::new(format!("[*]{}", file_path.to_str().unwrap()).as_str());
+        p.file()?;
+
+        assert_eq!(path, p.int_rep.get("mixedcase").unwrap().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_preserves_case() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let file_path = PathBuf::from(temp.as_ref());
+        let path = format!("{}/MixedCase", file_path.to_str().unwrap());
+        create_dir(&path).map_err(|e| format!("couldn't create temp dir: {}", e))?;
+
+        let options = ParserOptions {
+            preserve_case: true,
+            ..Default::default()
+        };
+        let glob_path = format!("[*]{}", file_path.to_str().unwrap());
+        let mut p = Parser::new_with_options(glob_path.as_str(), options);
+        p.file()?;
+
+        assert_eq!(path, p.int_rep.get("MixedCase").unwrap().to_string());
+        assert!(!p.int_rep.contains_key("mixedcase"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_record_their_lines() -> Result<(), String> {
+        let mut p = Parser::new("[work]/some/work\n/some/play");
+        p.file()?;
+        let lines: Vec<(String, usize)> =
+            p.entries().into_iter().map(|e| (e.alias, e.line)).collect();
+        assert_eq!(
+            vec![("play".to_string(), 2), ("work".to_string(), 1)],
+            lines
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_record_the_lines_of_globs_and_references() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir_all(dir.join("code/api")).map_err(|e| e.to_string())?;
+        let input = format!(
+            "[web]@base/web\n\n[base]/some/base\n[*]{}",
+            dir.join("code").display()
+        );
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        let lines: Vec<(String, usize)> =
+            p.entries().into_iter().map(|e| (e.alias, e.line)).collect();
+        assert_eq!(
+            vec![
+                ("api".to_string(), 4),
+                ("base".to_string(), 3),
+                ("web".to_string(), 1)
+            ],
+            lines
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_records_alias_sources() -> Result<(), String> {
+        let mut p = Parser::new(
+            r#"[alias]/another/absolute/path
+        /yet/another/path
+        "#,
+        );
+        p.file()?;
+        assert_eq!(Some(&AliasSource::Explicit), p.sources.get("alias"));
+        assert_eq!(Some(&AliasSource::PathDerived), p.sources.get("path"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_input_with_byte_order_mark() -> Result<(), String> {
+        let mut p = Parser::new("\u{feff}[work]/some/path");
+        p.file()?;
+        assert_eq!("/some/path", p.int_rep.get("work").unwrap());
+        assert!(p.warnings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_skips_zero_width_characters_at_line_start() -> Result<(), String> {
+        let mut p = Parser::new("/some/path\n\u{200b}[work]/another/path");
+        p.file()?;
+        assert_eq!("/some/path", p.int_rep.get("path").unwrap());
+        assert_eq!("/another/path", p.int_rep.get("work").unwrap());
+        assert_eq!(
+            ["skipping zero-width character U+200B".to_string()],
+            p.warnings()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!("/some/path", normalize_path("/some/path/"));
+        assert_eq!("/some/path", normalize_path("  /some/path   "));
+        assert_eq!("/", normalize_path("/"));
+        assert_eq!("/", normalize_path("//"));
+        assert_eq!("/double/slashes", normalize_path("//double//slashes"));
+        assert_eq!("/My Documents", normalize_path("/My Documents"));
+    }
+
+    #[test]
+    fn test_parse_path_with_trailing_slash() -> Result<(), String> {
+        let mut p = Parser::new("/home/me/projects/");
+        p.file()?;
+        assert_eq!("/home/me/projects", p.int_rep.get("projects").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_path_with_trailing_spaces() -> Result<(), String> {
+        let mut p = Parser::new("[work]/home/me/work   \n/home/me/code  ");
+        p.file()?;
+        assert_eq!("/home/me/work", p.int_rep.get("work").unwrap());
+        assert_eq!("/home/me/code", p.int_rep.get("code").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_root_path() -> Result<(), String> {
+        let mut p = Parser::new("[root]/");
+        p.file()?;
+        assert_eq!("/", p.int_rep.get("root").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_path_with_double_slashes() -> Result<(), String> {
+        let mut p = Parser::new("//double//slashes");
+        p.file()?;
+        assert_eq!("/double/slashes", p.int_rep.get("slashes").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_warns_about_aliases_differing_in_case() -> Result<(), String> {
+        let mut p = Parser::new("[Docs]/some/docs\n[docs]/other/docs");
+        p.file()?;
+        assert_eq!(2, p.int_rep.len());
+        assert_eq!(
+            ["aliases 'Docs' and 'docs' differ only in case".to_string()],
+            p.warnings()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rejects_aliases_differing_in_case_when_strict() {
+        let options = ParserOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options("[Docs]/some/docs\n[docs]/other/docs", options);
+        assert_eq!(
+            Err("aliases 'Docs' and 'docs' differ only in case".to_string()),
+            p.file().map_err(String::from)
+        );
+    }
+
+    #[test]
+    fn test_parse_warns_about_glob_alias_differing_in_case() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let file_path = PathBuf::from(temp.as_ref());
+        create_dir(file_path.join("docs")).map_err(|e| e.to_string())?;
+
+        let input = format!("[Docs]/some/docs\n[*]{}", file_path.to_str().unwrap());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(
+            ["aliases 'Docs' and 'docs' differ only in case".to_string()],
+            p.warnings()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rejects_duplicate_alias() {
+        let mut p = Parser::new("[docs]/some/docs\n[docs]/other/docs");
+        assert_eq!(
+            Err(
+                "duplicate alias 'docs' for /some/docs on line 1 and /other/docs on line 2"
+                    .to_string()
+            ),
+            p.file().map_err(String::from)
+        );
+        assert!(p.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_parse_allows_repeated_identical_alias() -> Result<(), String> {
+        let mut p = Parser::new("[docs]/some/docs\n[docs]/some/docs/");
+        p.file()?;
+        assert_eq!("/some/docs", p.int_rep.get("docs").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_fails_with_missing_directory() {
+        let temp = temp_testdir::TempDir::default();
+        let missing = PathBuf::from(temp.as_ref()).join("missing");
+        let input = format!("/some/path\n[*]{}", missing.display());
+        let mut p = Parser::new(input.as_str());
+        match p.file() {
+            Err(DaliaError::InvalidGlob(path, line, kind)) => {
+                assert_eq!(missing, path);
+                assert_eq!(2, line);
+                assert_eq!(std::io::ErrorKind::NotFound, kind);
+            }
+            other => panic!("expected an InvalidGlob, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_glob_fails_with_unreadable_directory() -> Result<(), String> {
+        use std::fs::{read_dir, set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref()).join("locked");
+        create_dir(&dir).map_err(|e| e.to_string())?;
+        set_permissions(&dir, Permissions::from_mode(0o000)).map_err(|e| e.to_string())?;
+
+        let input = format!("[*]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        let result = p.file();
+        let readable = read_dir(&dir).is_ok();
+        set_permissions(&dir, Permissions::from_mode(0o755)).map_err(|e| e.to_string())?;
+
+        // Permissions aren't enforced for privileged users, so there's nothing to assert.
+        if readable {
+            return Ok(());
+        }
+        let e = result.unwrap_err();
+        assert!(matches!(e, DaliaError::InvalidGlob(_, 1, _)));
+        assert_eq!(
+            format!(
+                "couldn't expand {} on line 1: permission denied",
+                dir.display()
+            ),
+            e.to_string()
+        );
+        Ok(())
+    }
+
+    /// Creates `code/org-a/{api,web}/src`, `code/org-b/cli`, and a file `code/org-a/notes.txt`.
+    fn create_nested_tree(dir: &Path) -> Result<(), String> {
+        for d in ["org-a/api/src", "org-a/web", "org-b/cli"].iter() {
+            std::fs::create_dir_all(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(dir.join("org-a/notes.txt"), "").map_err(|e| e.to_string())
+    }
+
+    fn sorted_aliases(p: &Parser) -> Vec<String> {
+        let mut aliases: Vec<String> = p.int_rep.keys().cloned().collect();
+        aliases.sort();
+        aliases
+    }
+
+    #[test]
+    fn test_parse_recursive_glob_depths() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_nested_tree(&dir)?;
+
+        let cases = [
+            ("*", vec!["org-a", "org-b"]),
+            ("**1", vec!["org-a", "org-b"]),
+            ("**2", vec!["api", "cli", "org-a", "org-b", "web"]),
+            ("**", vec!["api", "cli", "org-a", "org-b", "src", "web"]),
+        ];
+        for (glob, expected) in cases.iter() {
+            let input = format!("[{}]{}", glob, dir.display());
+            let mut p = Parser::new(input.as_str());
+            p.file()?;
+            assert_eq!(*expected, sorted_aliases(&p), "{}", glob);
+            assert_eq!(
+                dir.join("org-a").display().to_string(),
+                *p.int_rep.get("org-a").unwrap()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_paths_include_the_directories_a_recursive_glob_read() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_nested_tree(&dir)?;
+
+        let input = format!("[**2]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(
+            vec![
+                dir.clone(),
+                dir.join(IGNORE_FILE),
+                dir.join("org-a"),
+                dir.join("org-b")
+            ],
+            p.glob_paths()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_recursive_glob_fails_on_repeated_names() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_nested_tree(&dir)?;
+        create_dir(dir.join("org-b/api")).map_err(|e| e.to_string())?;
+
+        let input = format!("[**2]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        match p.file() {
+            Err(DaliaError::ParseError(msg)) => {
+                assert!(msg.starts_with("duplicate alias 'api' for "), "{}", msg)
+            }
+            other => panic!("expected a duplicate alias error, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_recursive_glob_fails_with_zero_depth() {
+        let mut p = Parser::new("[**0]/code");
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "invalid glob depth '**0' on line 1; expected a number of at least 1".into()
+            )),
+            p.file()
+        );
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("proj-*", "proj-api"));
+        assert!(matches_pattern("proj-*", "proj-"));
+        assert!(!matches_pattern("proj-*", "my-proj-api"));
+        assert!(matches_pattern("*-api", "proj-api"));
+        assert!(matches_pattern("p?oj*i", "proj-api"));
+        assert!(!matches_pattern("p?oj", "proj-api"));
+        assert!(matches_pattern("*a*b*", "xxaxxbxx"));
+        assert!(matches_pattern("*", ""));
+        assert!(!matches_pattern("?", ""));
+    }
+
+    #[test]
+    fn test_parse_glob_with_pattern() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["proj-api", "proj-web", "scratch"].iter() {
+            create_dir(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+
+        let input = format!("[*:proj-*]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(vec!["proj-api", "proj-web"], sorted_aliases(&p));
+        assert!(p.warnings().is_empty());
+
+        let input = format!("[*:tmp-?]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert!(p.int_rep.is_empty());
+        assert_eq!(
+            [format!(
+                "pattern 'tmp-?' on line 1 didn't match any directories in {}",
+                dir.display()
+            )],
+            p.warnings()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_with_prefix_and_suffix() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["acme", "Tools"].iter() {
+            create_dir(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+
+        for (glob, expected) in [
+            ("*-dev", ["acme-dev", "tools-dev"]),
+            ("w.*", ["w.acme", "w.tools"]),
+            ("w-*a_dev", ["w-acme_dev", "w-tools_dev"]),
+        ]
+        .iter()
+        {
+            let input = format!("[{}]{}", glob, dir.display());
+            let mut p = Parser::new(input.as_str());
+            p.file()?;
+            assert_eq!(expected.to_vec(), sorted_aliases(&p), "{}", glob);
+        }
+
+        let input = format!("[*.]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(vec!["acme.", "tools."], sorted_aliases(&p));
+        let mut warnings = p.warnings().to_vec();
+        warnings.sort();
+        assert_eq!(
+            [
+                "alias name 'acme.' on line 1 ends with '.'",
+                "alias name 'tools.' on line 1 ends with '.'"
+            ],
+            warnings.as_slice()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_with_prefix_and_pattern() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["proj-api", "proj-web", "scratch"].iter() {
+            create_dir(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+
+        // Text before the asterisk names the aliases; only the pattern picks directories.
+        let input = format!("[proj-*]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(
+            vec!["proj-proj-api", "proj-proj-web", "proj-scratch"],
+            sorted_aliases(&p)
+        );
+
+        let input = format!("[w-*:proj-*]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(vec!["w-proj-api", "w-proj-web"], sorted_aliases(&p));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_with_file_extensions() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["crate/src", "script", "docs", "nested/deeper"].iter() {
+            create_dir_all(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+        for file in [
+            "crate/build.rs",
+            "script/run.py",
+            "docs/README.md",
+            "nested/deeper/main.rs",
+        ]
+        .iter()
+        {
+            std::fs::write(dir.join(file), "").map_err(|e| e.to_string())?;
+        }
+
+        for (glob, expected) in [
+            ("*.(rs)", vec!["crate"]),
+            ("*.(rs|py)", vec!["crate", "script"]),
+            ("*.(rs)-dev", vec!["crate-dev"]),
+            ("**.(rs)", vec!["crate", "deeper"]),
+        ]
+        .iter()
+        {
+            let input = format!("[{}]{}", glob, dir.display());
+            let mut p = Parser::new(input.as_str());
+            p.file()?;
+            assert_eq!(*expected, sorted_aliases(&p), "{}", glob);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_options_from_token_with_file_extensions() -> Result<(), DaliaError> {
+        let options = GlobOptions::from_token("w-*a.(rs|py)-dev", 1)?;
+        assert_eq!(vec!["rs".to_string(), "py".to_string()], options.extensions);
+        assert_eq!(
+            ("w-", "-dev"),
+            (options.prefix.as_str(), options.suffix.as_str())
+        );
+        assert!(options.include_hidden);
+
+        for token in ["*.()", "*.(rs|)", "*.(r.s)", "*.(rs"].iter() {
+            match GlobOptions::from_token(token, 2) {
+                Err(DaliaError::ParseError(msg)) => {
+                    assert!(msg.starts_with("invalid file extensions"), "{}", msg)
+                }
+                other => panic!("expected an error for {}, got {:?}", token, other),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_suffix_collides_with_custom_name() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("acme")).map_err(|e| e.to_string())?;
+
+        let input = format!("[acme-dev]/home/me/acme\n[*-dev]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!("/home/me/acme", p.int_rep.get("acme-dev").unwrap());
+        assert_eq!(
+            vec![format!(
+                "alias 'acme-dev' for {} is shadowed by the custom name for /home/me/acme",
+                dir.join("acme").display()
+            )],
+            p.warnings()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_with_empty_pattern_fails() {
+        let mut p = Parser::new("[*:]/code");
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "missing pattern after ':' on line 1".into()
+            )),
+            p.file()
+        );
+    }
+
+    #[test]
+    fn test_split_exclusions() {
+        assert_eq!(("/code", vec![]), split_exclusions("/code"));
+        assert_eq!(
+            (
+                "~/My Code",
+                vec!["node_modules".to_string(), "target".to_string()]
+            ),
+            split_exclusions("~/My Code !node_modules   !target  ")
+        );
+        assert_eq!(("/code/!x", vec![]), split_exclusions("/code/!x"));
+        assert_eq!(("/code !", vec![]), split_exclusions("/code !"));
+    }
+
+    #[test]
+    fn test_parse_glob_with_exclusions() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["api", "node_modules/pkg", "web"].iter() {
+            std::fs::create_dir_all(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+
+        let input = format!("[**]{} !node_modules", dir.display());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(vec!["api", "web"], sorted_aliases(&p));
+        assert_eq!(
+            dir.join("api").display().to_string(),
+            *p.int_rep.get("api").unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_lines_concurrently_matches_sequential_order() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        let mut lines = Vec::new();
+        for (root, children) in [
+            ("mnt1", &["zeta", "alpha", "Mid"][..]),
+            ("mnt2", &["beta", "gamma"][..]),
+            ("mnt3", &["delta", "bad name"][..]),
+            ("mnt4", &["omega"][..]),
+        ]
+        .iter()
+        {
+            for child in children.iter() {
+                create_dir_all(dir.join(root).join(child)).map_err(|e| e.to_string())?;
+            }
+            lines.push(format!("[*]{}", dir.join(root).display()));
+        }
+        lines.insert(2, "[section: work]".into());
+        let input = lines.join("\n");
+
+        let mut concurrent = Parser::new(&input);
+        concurrent.file()?;
+        let mut sequential = Parser::new(&input);
+        sequential.parse()?;
+        for directive in sequential.directives.clone() {
+            sequential.resolve_directive(directive)?;
+        }
+        assert_eq!(sequential.int_rep, concurrent.int_rep);
+        assert_eq!(sequential.warnings(), concurrent.warnings());
+        assert_eq!(
+            vec![
+                "alpha",
+                "beta",
+                "gamma",
+                "mid",
+                "work-delta",
+                "work-omega",
+                "zeta"
+            ],
+            sorted_aliases(&concurrent)
+        );
+
+        let input = format!(
+            "[*]{}\n[*]{}\n[*]{}",
+            dir.join("mnt1").display(),
+            dir.join("missing2").display(),
+            dir.join("missing3").display()
+        );
+        let mut p = Parser::new(&input);
+        assert_eq!(
+            Err(DaliaError::InvalidGlob(
+                dir.join("missing2"),
+                2,
+                io::ErrorKind::NotFound
+            )),
+            p.file()
+        );
+        let mut p = Parser::new(&input);
+        let (aliases, errors) = p.process_input_collecting_errors();
+        assert_eq!(3, aliases.len());
+        assert_eq!(
+            vec![
+                DaliaError::InvalidGlob(dir.join("missing2"), 2, io::ErrorKind::NotFound),
+                DaliaError::InvalidGlob(dir.join("missing3"), 3, io::ErrorKind::NotFound),
+            ],
+            errors
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_respects_ignore_file() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["api", "build", "tmp-1", "tmp-old", "web", "web/build"].iter() {
+            create_dir(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+        write(
+            dir.join(IGNORE_FILE),
+            "# generated output\nbuild\n\n  tmp-*  \n",
+        )
+        .map_err(|e| e.to_string())?;
+        create_dir(dir.join("web/tmp-2")).map_err(|e| e.to_string())?;
+        // Only the ignore file in the glob line's own directory is read.
+        write(dir.join("web").join(IGNORE_FILE), "docs\n").map_err(|e| e.to_string())?;
+        create_dir(dir.join("web/docs")).map_err(|e| e.to_string())?;
+
+        let input = format!("[**]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(vec!["api", "docs", "web"], sorted_aliases(&p));
+        assert!(p.warnings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_lines_with_the_same_directory_name() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["work/notes", "work/api", "personal/notes", "archive/notes"].iter() {
+            std::fs::create_dir_all(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+        let two_lines = format!(
+            "[*]{}\n[*]{}",
+            dir.join("work").display(),
+            dir.join("personal").display()
+        );
+
+        let mut p = Parser::new(two_lines.as_str());
+        assert_eq!(
+            Err(DaliaError::ParseError(format!(
+                "duplicate alias 'notes' for {} on line 1 and {} on line 2",
+                dir.join("work/notes").display(),
+                dir.join("personal/notes").display()
+            ))),
+            p.file()
+        );
+
+        let options = ParserOptions {
+            disambiguate: true,
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options(two_lines.as_str(), options.clone());
+        p.file()?;
+        assert_eq!(
+            vec!["api", "personal-notes", "work-notes"],
+            sorted_aliases(&p)
+        );
+        assert_eq!(
+            dir.join("work/notes").display().to_string(),
+            *p.int_rep.get("work-notes").unwrap()
+        );
+
+        let three_lines = format!("{}\n[*]{}", two_lines, dir.join("archive").display());
+        let mut p = Parser::new_with_options(three_lines.as_str(), options);
+        p.file()?;
+        assert_eq!(
+            vec!["api", "archive-notes", "personal-notes", "work-notes"],
+            sorted_aliases(&p)
+        );
+        assert!(p
+            .sources()
+            .values()
+            .all(|s| *s == AliasSource::GlobExpanded));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_custom_name_beats_glob_alias() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("api")).map_err(|e| e.to_string())?;
+        create_dir(dir.join("web")).map_err(|e| e.to_string())?;
+        let glob = format!("[*]{}", dir.display());
+        let explicit = "[api]/home/me/code/api-v2";
+        let warning = format!(
+            "alias 'api' for {} is shadowed by the custom name for /home/me/code/api-v2",
+            dir.join("api").display()
+        );
+
+        for input in [
+            format!("{}\n{}", glob, explicit),
+            format!("{}\n{}", explicit, glob),
+        ]
+        .iter()
+        {
+            let mut p = Parser::new(input);
+            p.file()?;
+            assert_eq!("/home/me/code/api-v2", p.int_rep.get("api").unwrap());
+            assert_eq!(Some(&AliasSource::Explicit), p.sources.get("api"));
+            assert_eq!(Some(&AliasSource::GlobExpanded), p.sources.get("web"));
+            assert_eq!(vec![warning.as_str()], p.warnings(), "{}", input);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_without_resolving_doesnt_read_directories() -> Result<(), String> {
+        let input = "[*:proj-*]/does/not/exist\n@case preserve\n[work:pushd]/some/work\n/Some/Path";
+        let mut p = Parser::new(input);
+        p.parse()?;
+        assert!(p.int_rep.is_empty());
+        assert_eq!(
+            &[
+                Directive::GlobExpansion {
+                    base: "/does/not/exist".into(),
+                    options: GlobOptions {
+                        depth: Some(1),
+                        pattern: Some("proj-*".into()),
+                        exclude: Vec::new(),
+                        include_hidden: false,
+                        include_files: false,
+                        prefix: String::new(),
+                        suffix: String::new(),
+                        extensions: Vec::new(),
+                    },
+                    line: 1,
+                },
+                Directive::Case { preserve: true },
+                Directive::ExplicitAlias {
+                    alias: "work".into(),
+                    path: "/some/work".into(),
+                    command: Some("pushd".into()),
+                    line: 3,
+                },
+                Directive::DerivedAlias {
+                    path: "/Some/Path".into(),
+                    line: 4,
+                },
+            ],
+            p.directives()
+        );
+        assert!(matches!(
+            p.resolve(),
+            Err(DaliaError::InvalidGlob(_, 1, io::ErrorKind::NotFound))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_stops_at_max_aliases() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["a/one", "a/two", "b/three"].iter() {
+            std::fs::create_dir_all(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+        let input = format!("[work]/some/work\n[**]{}", dir.display());
+        let options = |max_aliases| ParserOptions {
+            max_aliases,
+            ..Default::default()
+        };
+
+        let mut p = Parser::new_with_options(input.as_str(), options(6));
+        p.file()?;
+        assert_eq!(6, p.int_rep.len());
+
+        let mut p = Parser::new_with_options(input.as_str(), options(4));
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "the glob on line 2 creates more than the maximum of 4 aliases".into()
+            )),
+            p.file()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_warns_past_max_glob_aliases() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for i in 0..30 {
+            create_dir(dir.join(format!("dir{}", i))).map_err(|e| e.to_string())?;
+        }
+        let input = format!("[*]{}", dir.display());
+        let expected = "the glob on line 1 created 30 aliases, more than the limit of 25; narrow it with a pattern or exclusions, or raise the limit with --max-glob";
+        let options = |strict| ParserOptions {
+            max_glob_aliases: 25,
+            strict,
+            ..Default::default()
+        };
+
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert!(p.warnings.is_empty());
+
+        let mut p = Parser::new_with_options(input.as_str(), options(false));
+        p.file()?;
+        assert_eq!(30, p.int_rep.len());
+        assert_eq!(vec![expected.to_string()], p.warnings);
+
+        let mut p = Parser::new_with_options(input.as_str(), options(true));
+        assert_eq!(Err(DaliaError::ParseError(expected.into())), p.file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_with_bracket_exclusions() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["one", "node_modules", "two", "two_old"].iter() {
+            create_dir(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+
+        let mut p = Parser::new(format!("[*!node_modules]{}", dir.display()).as_str());
+        p.file()?;
+        assert_eq!(vec!["one", "two", "two_old"], sorted_aliases(&p));
+
+        let mut p = Parser::new(format!("[*!node_modules!*_old]{}", dir.display()).as_str());
+        p.file()?;
+        assert_eq!(vec!["one", "two"], sorted_aliases(&p));
+
+        let mut p = Parser::new(format!("[*!]{}", dir.display()).as_str());
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "missing name after '!' on line 1".into()
+            )),
+            p.file()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_skips_hidden_directories() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("code")).map_err(|e| e.to_string())?;
+        create_dir(dir.join(".idea")).map_err(|e| e.to_string())?;
+        let input = format!("[*]{}", dir.display());
+
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(1, p.int_rep.len());
+        assert!(p.int_rep.contains_key("code"));
+
+        let options = ParserOptions {
+            include_hidden: true,
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options(input.as_str(), options);
+        p.file()?;
+        assert_eq!(2, p.int_rep.len());
+        assert!(p.int_rep.contains_key("code"));
+        assert!(p.int_rep.contains_key(".idea"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_includes_hidden_directories_with_marker() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("code")).map_err(|e| e.to_string())?;
+        create_dir(dir.join(".hidden")).map_err(|e| e.to_string())?;
+
+        let mut p = Parser::new(format!("[*]{}", dir.display()).as_str());
+        p.file()?;
+        assert_eq!(vec!["code"], sorted_aliases(&p));
+
+        let mut p = Parser::new(format!("[*a]{}", dir.display()).as_str());
+        p.file()?;
+        assert_eq!(vec![".hidden", "code"], sorted_aliases(&p));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_glob_includes_files_with_marker() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("code")).map_err(|e| e.to_string())?;
+        write(dir.join("code").join("todo.md"), "").map_err(|e| e.to_string())?;
+        write(dir.join("ideas.txt"), "").map_err(|e| e.to_string())?;
+
+        let mut p = Parser::new(format!("[**]{}", dir.display()).as_str());
+        p.file()?;
+        assert_eq!(vec!["code"], sorted_aliases(&p));
+
+        let options = ParserOptions {
+            file_command: "vim".into(),
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options(format!("[**f]{}", dir.display()).as_str(), options);
+        p.file()?;
+        let entries: Vec<(String, String, EntryKind)> = p
+            .entries()
+            .into_iter()
+            .map(|e| (e.alias, e.command, e.kind))
+            .collect();
+        assert_eq!(
+            vec![
+                ("code".to_string(), "cd".to_string(), EntryKind::Directory),
+                ("ideas".to_string(), "vim".to_string(), EntryKind::File),
+                ("todo".to_string(), "vim".to_string(), EntryKind::File),
+            ],
+            entries
+        );
+
+        let mut p = Parser::new(format!("[*f]{}", dir.display()).as_str());
+        p.file()?;
+        let ideas = p.entries().into_iter().find(|e| e.alias == "ideas");
+        assert_eq!(
+            Some(format!("$EDITOR {}", dir.join("ideas.txt").display())),
+            ideas.map(|e| e.body())
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_glob_keeps_siblings_of_unreadable_directory() -> Result<(), String> {
+        use std::fs::{set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("one")).map_err(|e| e.to_string())?;
+        create_dir(dir.join("two")).map_err(|e| e.to_string())?;
+        let locked = dir.join("locked");
+        create_dir(&locked).map_err(|e| e.to_string())?;
+        set_permissions(&locked, Permissions::from_mode(0o000)).map_err(|e| e.to_string())?;
+
+        let input = format!("[*]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        let result = p.file();
+        let readable = std::fs::read_dir(&locked).is_ok();
+        set_permissions(&locked, Permissions::from_mode(0o755)).map_err(|e| e.to_string())?;
+        result?;
+
+        // Permissions aren't enforced for privileged users, so there's nothing to skip.
+        if readable {
+            return Ok(());
+        }
+        assert_eq!(vec!["one", "two"], sorted_aliases(&p));
+        assert_eq!(
+            vec![format!("skipping {}: permission denied", locked.display())],
+            p.warnings
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_glob_skips_unsearchable_directories() -> Result<(), String> {
+        use std::fs::{set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("open")).map_err(|e| e.to_string())?;
+        let private = dir.join("private");
+        create_dir(&private).map_err(|e| e.to_string())?;
+        set_permissions(&private, Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+
+        let input = format!("[*]{}", dir.display());
+        let mut p = Parser::new(input.as_str());
+        let result = p.file();
+        let options = ParserOptions {
+            include_unreadable: true,
+            ..Default::default()
+        };
+        let mut included = Parser::new_with_options(input.as_str(), options);
+        let included_result = included.file();
+        let searchable = std::fs::metadata(private.join(".")).is_ok();
+        set_permissions(&private, Permissions::from_mode(0o755)).map_err(|e| e.to_string())?;
+        result?;
+        included_result?;
+
+        assert_eq!(vec!["open", "private"], sorted_aliases(&included));
+        // Permissions aren't enforced for privileged users, so the directory is searchable.
+        if searchable {
+            return Ok(());
+        }
+        assert_eq!(vec!["open"], sorted_aliases(&p));
+        assert_eq!(
+            vec![format!("skipping {}: permission denied", private.display())],
+            p.warnings
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_drops_equivalent_paths() -> Result<(), String> {
+        let mut p = Parser::new("/some/path\n/some/path/\n[work]/some/work\n[work]/some//work//");
+        p.file()?;
+        assert_eq!(vec!["path", "work"], sorted_aliases(&p));
+        assert!(p.warnings.is_empty());
+
+        assert!(same_directory("/some/path", "/some/path/", false));
+        assert!(same_directory("~/code", "~//code/", false));
+        assert!(same_directory("~/code", "$HOME/code", false));
+        assert!(!same_directory("/some/path", "/some/other", true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_warns_about_home_and_its_expanded_path() -> Result<(), String> {
+        let mut p = Parser::new("[code]~/code\n[code]$HOME/code/\n");
+        p.file()?;
+        assert_eq!("~/code", p.int_rep.get("code").unwrap());
+        assert_eq!(
+            vec![
+                "alias 'code' is configured for both ~/code and $HOME/code, which are the same \
+                  directory; keeping ~/code"
+                    .to_string()
+            ],
+            p.warnings
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_canonicalizes_equivalent_paths() -> Result<(), String> {
+        use std::os::unix::fs::symlink;
+
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("code")).map_err(|e| e.to_string())?;
+        symlink(dir.join("code"), dir.join("src")).map_err(|e| e.to_string())?;
+        let input = format!(
+            "[code]{}\n[code]{}",
+            dir.join("code").display(),
+            dir.join("src").display()
+        );
+
+        let mut p = Parser::new(&input);
+        assert!(matches!(p.file(), Err(DaliaError::ParseError(_))));
+
+        let options = ParserOptions {
+            canonicalize: true,
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options(&input, options);
+        p.file()?;
+        assert_eq!(
+            Some(&dir.join("code").display().to_string()),
+            p.int_rep.get("code")
+        );
+        assert_eq!(
+            vec![format!(
+                "alias 'code' is configured for both {} and {}, which are the same directory; keeping {}",
+                dir.join("code").display(),
+                dir.join("src").display(),
+                dir.join("code").display()
+            )],
+            p.warnings
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_glob_symbolic_links() -> Result<(), String> {
+        use std::os::unix::fs::symlink;
+
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref()).join("code");
+        let elsewhere = PathBuf::from(temp.as_ref()).join("volume");
+        create_dir(&dir).map_err(|e| e.to_string())?;
+        create_dir(dir.join("local")).map_err(|e| e.to_string())?;
+        create_dir(&elsewhere).map_err(|e| e.to_string())?;
+        create_dir(elsewhere.join("nested")).map_err(|e| e.to_string())?;
+        symlink(&elsewhere, dir.join("linked")).map_err(|e| e.to_string())?;
+        symlink(dir.join("missing"), dir.join("dangling")).map_err(|e| e.to_string())?;
+        File::create(elsewhere.join("notes.txt")).map_err(|e| e.to_string())?;
+        symlink(elsewhere.join("notes.txt"), dir.join("notes")).map_err(|e| e.to_string())?;
+        let input = format!("[**]{}", dir.display());
+
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(vec!["linked", "local"], sorted_aliases(&p));
+        assert_eq!(
+            &format!("{}", dir.join("linked").display()),
+            p.int_rep.get("linked").unwrap()
+        );
+        assert_eq!(
+            vec![format!(
+                "skipping {}: it's a broken symbolic link",
+                dir.join("dangling").display()
+            )],
+            p.warnings()
+        );
+
+        let options = ParserOptions {
+            follow_symlinks: false,
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options(input.as_str(), options);
+        p.file()?;
+        assert_eq!(vec!["local"], sorted_aliases(&p));
+        assert!(p.warnings().is_empty());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_glob_skips_non_utf8_names() -> Result<(), String> {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("code")).map_err(|e| e.to_string())?;
+        create_dir(dir.join("docs")).map_err(|e| e.to_string())?;
+        let invalid = dir.join(OsStr::from_bytes(b"bad\xff"));
+        create_dir(&invalid).map_err(|e| e.to_string())?;
+
+        let mut p = Parser::new(format!("[*]{}", dir.display()).as_str());
+        p.file()?;
+        assert_eq!(vec!["code", "docs"], sorted_aliases(&p));
+        assert_eq!(
+            [format!(
+                "skipping {}: its name isn't valid UTF-8",
+                invalid.display()
+            )],
+            p.warnings()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_from_reader() -> Result<(), String> {
+        let input = std::io::Cursor::new(b"[work]/some/work/path\n/yet/another/path\n".to_vec());
+        let mut p = Parser::from_reader(input)?;
+        p.file()?;
+        assert_eq!(2, p.int_rep.len());
+        assert_eq!("/some/work/path", p.int_rep.get("work").unwrap());
+        assert_eq!("/yet/another/path", p.int_rep.get("path").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_from_reader_fails_with_empty_input() {
+        let result = Parser::from_reader(&b"  \n"[..]);
+        assert_eq!(
+            "no config file found to parse",
+            result.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parser_from_path() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join("config");
+        std::fs::write(&file, "[work]/some/work/path").map_err(|e| e.to_string())?;
+
+        let mut p = Parser::from_path(&file)?;
+        p.file()?;
+        assert_eq!("/some/work/path", p.int_rep.get("work").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_from_path_fails_with_missing_file() {
+        let temp = temp_testdir::TempDir::default();
+        let file = PathBuf::from(temp.as_ref()).join("missing");
+        match Parser::from_path(&file) {
+            Err(DaliaError::IoError(path, _)) => assert_eq!(file, path),
+            other => panic!("expected an IoError, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_parse_selects_line_for_target_os() -> Result<(), String> {
+        let input = r#"
+        @macos [music]/Volumes/Media/Music
+        @linux [music]/mnt/media/music
+        /everywhere
+        "#;
+        for (os, expected) in [
+            ("macos", "/Volumes/Media/Music"),
+            ("linux", "/mnt/media/music"),
+        ] {
+            let options = ParserOptions {
+                target_os: os.to_string(),
+                ..Default::default()
+            };
+            let mut p = Parser::new_with_options(input, options);
+            p.file()?;
+            assert_eq!(2, p.int_rep.len());
+            assert_eq!(expected, p.int_rep.get("music").unwrap());
+            assert_eq!("/everywhere", p.int_rep.get("everywhere").unwrap());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_skips_glob_for_other_os() -> Result<(), String> {
+        let options = ParserOptions {
+            target_os: "linux".to_string(),
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options("@windows [*]/path/that/does/not/exist", options);
+        p.file()?;
+        assert!(p.int_rep.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fails_with_unknown_directive() {
+        let mut p = Parser::new("@plan9 /some/path");
+        assert_eq!(
+            "unknown directive '@plan9'",
+            p.file().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_collecting_errors() {
+        let input = r#"[first]/first/path
+        some unfinished line
+        [bad/path
+        /second/path
+        @plan9 /plan9/path
+        [third]/third/path
+        "#;
+        let mut p = Parser::new(input);
+        let (aliases, errors) = p.process_input_collecting_errors();
+
+        assert_eq!(3, errors.len());
+        assert_eq!(
+            vec![
+                DaliaError::ParseError("expecting PATH; found <'some', ALIAS>".into()),
+                DaliaError::ParseError("expecting RBRACK; found <'/path', PATH>".into()),
+                DaliaError::ParseError("unknown directive '@plan9'".into()),
+            ],
+            errors
+        );
+        assert!(p.has_errors());
+        assert_eq!(errors, p.errors());
+
+        assert_eq!(3, aliases.len());
+        assert_eq!("/first/path", aliases.get("first").unwrap());
+        assert_eq!("/second/path", aliases.get("path").unwrap());
+        assert_eq!("/third/path", aliases.get("third").unwrap());
+    }
+
+    #[test]
+    fn test_parse_collecting_errors_without_errors() {
+        let mut p = Parser::new("[first]/first/path\n/second/path");
+        let (aliases, errors) = p.process_input_collecting_errors();
+        assert!(errors.is_empty());
+        assert!(!p.has_errors());
+        assert_eq!(2, aliases.len());
+    }
+
+    #[test]
+    fn test_parse_windows_path_with_explicit_alias() -> Result<(), String> {
+        let mut p = Parser::new("[project]C:\\Users\\me\\project");
+        p.file()?;
+        assert_eq!("C:\\Users\\me\\project", p.int_rep.get("project").unwrap());
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_normalize_path_keeps_backslashes_outside_windows() {
+        assert_eq!(
+            "~/Mobile\\ Documents\\\\",
+            normalize_path("~/Mobile\\ Documents\\\\")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_normalize_windows_path() {
+        assert_eq!("C:\\Users\\me", normalize_path("C:\\Users\\\\me\\"));
+        assert_eq!("C:\\", normalize_path("C:\\"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_parse_windows_path_derives_alias() -> Result<(), String> {
+        let mut p = Parser::new("C:\\Users\\me\\Project\\");
+        p.file()?;
+        assert_eq!("C:\\Users\\me\\Project", p.int_rep.get("project").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_variable_substitution() -> Result<(), String> {
+        let input = r#"
+        $work = /home/me/company/monorepo/
+        $services = $work/services
+        [api]$work/services/api
+        [web]$work/apps/web
+        $services/auth
+        "#;
+        let mut p = Parser::new(input);
+        p.file()?;
+        assert_eq!(3, p.int_rep.len());
+        assert_eq!(
+            "/home/me/company/monorepo/services/api",
+            p.int_rep.get("api").unwrap()
+        );
+        assert_eq!(
+            "/home/me/company/monorepo/apps/web",
+            p.int_rep.get("web").unwrap()
+        );
+        assert_eq!(
+            "/home/me/company/monorepo/services/auth",
+            p.int_rep.get("auth").unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_variable_in_glob_line() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let file_path = PathBuf::from(temp.as_ref());
+        create_dir(file_path.join("apps")).map_err(|e| e.to_string())?;
+        create_dir(file_path.join("apps").join("web")).map_err(|e| e.to_string())?;
 
-    fn consume(&mut self) -> Result<(), String> {
-        self.lookahead = self.input.next_token()?;
+        let input = format!(
+            "$root = {}\n$apps = $root/apps\n[*]$apps",
+            file_path.display()
+        );
+        let mut p = Parser::new(input.as_str());
+        p.file()?;
+        assert_eq!(
+            format!("{}/apps/web", file_path.display()),
+            p.int_rep.get("web").unwrap().to_string()
+        );
         Ok(())
     }
 
-    fn matches(&mut self, k: i32) -> Result<(), String> {
-        if self.lookahead.kind == k {
-            return self.consume();
-        }
-        Err(format!(
-            "expecting {}; found {}",
-            self.input.token_names(k as usize),
-            self.lookahead
-        ))
+    #[test]
+    fn test_parse_fails_with_undefined_variable() {
+        let input = "$work = /home/me/work\n\n[api]$dalia_undefined_var/services/api";
+        let mut p = Parser::new(input);
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "undefined variable 'dalia_undefined_var' on line 3".into()
+            )),
+            p.file()
+        );
     }
 
-    fn file(&mut self) -> Result<(), String> {
-        loop {
-            self.line()?;
-            if self.lookahead.kind == TOKEN_EOF {
-                return self.matches(TOKEN_EOF);
-            }
-        }
+    #[test]
+    fn test_parse_fails_with_variable_used_before_definition() {
+        let input = "[api]$work/services/api\n$work = /home/me/work";
+        let mut p = Parser::new(input);
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "undefined variable 'work' on line 1".into()
+            )),
+            p.file()
+        );
     }
 
-    pub fn process_input(&mut self) -> Result<(), String> {
-        self.file()
+    #[test]
+    fn test_parse_fails_with_redefined_variable() {
+        let input = "$work = /home/me/work\n$work = /home/me/other";
+        let mut p = Parser::new(input);
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "variable 'work' on line 2 is already defined on line 1".into()
+            )),
+            p.file()
+        );
     }
 
-    pub fn line(&mut self) -> Result<(), String> {
-        let mut alias: Option<Cow<String>> = None;
-        let mut is_glob: bool = false;
-        if self.lookahead.kind == TOKEN_LBRACK {
-            self.matches(TOKEN_LBRACK)?;
+    #[test]
+    fn test_parse_name_equals_path() -> Result<(), String> {
+        let input = r#"
+        docs=/home/me/Documents
+        music   =   /home/me/My Music
+        [code]/home/me/code
+        /home/me/Desktop
+        "#;
+        let mut p = Parser::new(input);
+        p.file()?;
+        assert_eq!(4, p.int_rep.len());
+        assert_eq!("/home/me/Documents", p.int_rep.get("docs").unwrap());
+        assert_eq!("/home/me/My Music", p.int_rep.get("music").unwrap());
+        assert_eq!("/home/me/code", p.int_rep.get("code").unwrap());
+        assert_eq!("/home/me/Desktop", p.int_rep.get("desktop").unwrap());
+        assert_eq!(Some(&AliasSource::Explicit), p.sources.get("docs"));
+        Ok(())
+    }
 
-            if self.lookahead.kind == TOKEN_GLOB {
-                is_glob = true;
-                self.glob()?;
-            } else if self.lookahead.kind == TOKEN_ALIAS {
-                alias = Some(self.lookahead.text.to_owned());
-                self.alias()?;
-            }
+    #[test]
+    fn test_parse_name_equals_nothing_fails() {
+        let mut p = Parser::new("/home/me/code\ndocs =  \n/home/me/Desktop");
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "missing path after '=' on line 2".into()
+            )),
+            p.file()
+        );
+    }
 
-            self.matches(TOKEN_RBRACK)?
-        }
-        let path: Option<Cow<String>> = Some(self.lookahead.text.to_owned());
-        self.path()?;
-        if is_glob {
-            self.expand_glob_paths(path);
-        } else {
-            self.add_path_alias(alias, path);
-        }
+    #[test]
+    fn test_parse_equals_inside_bracketed_path() -> Result<(), String> {
+        let mut p = Parser::new("[eq]/home/me/a=b");
+        p.file()?;
+        assert_eq!("/home/me/a=b", p.int_rep.get("eq").unwrap());
         Ok(())
     }
 
-    fn add_path_alias(&mut self, alias: Option<Cow<String>>, path: Option<Cow<String>>) {
-        match alias {
-            Some(a) => {
-                self.int_rep.insert(
-                    a.to_owned().parse().unwrap(),
-                    path.unwrap().to_owned().parse().unwrap(),
-                );
-            }
-            None => {
-                self.insert_alias_from_path(path);
-            }
+    #[test]
+    fn test_parse_tolerates_blank_lines() -> Result<(), String> {
+        let inputs = [
+            "\n\n\n[work]/some/work/path\n/some/other/path",
+            "[work]/some/work/path\n\n  \n\t\n/some/other/path",
+            "[work]/some/work/path\n/some/other/path\n\n   \n\n",
+            "\r\n[work]/some/work/path\r\n\r\n/some/other/path\r\n\r\n",
+        ];
+        for input in inputs.iter() {
+            let mut p = Parser::new(input);
+            p.file()?;
+            assert_eq!(2, p.int_rep.len(), "{:?}", input);
+            assert_eq!("/some/work/path", p.int_rep.get("work").unwrap());
+            assert_eq!("/some/other/path", p.int_rep.get("path").unwrap());
         }
+        Ok(())
     }
 
-    fn expand_glob_paths(&mut self, path: Option<Cow<String>>) {
-        let dir: String = path.unwrap().parse().unwrap();
-        let paths = std::fs::read_dir(dir).unwrap();
-        for path in paths {
-            if let Ok(entry) = path {
-                if entry.metadata().unwrap().is_file() {
-                    continue;
-                }
-                self.insert_alias_from_path(Some(Cow::Owned(
-                    entry.path().to_str().unwrap().to_string(),
-                )));
-            }
-        }
+    #[test]
+    fn test_parse_file_without_aliases() -> Result<(), String> {
+        let mut p = Parser::new("\n$work = /some/work\n\n");
+        p.file()?;
+        assert!(p.int_rep.is_empty());
+        Ok(())
     }
 
-    fn insert_alias_from_path(&mut self, path: Option<Cow<String>>) -> Option<String> {
-        let dir = path?.into_owned();
-        let file_stem = Path::new(&dir).file_stem()?;
-        let alias = file_stem.to_str()?;
-        self.int_rep.insert(alias.to_lowercase(), dir)
-    }
+    #[test]
+    fn test_parse_alias_descriptions() -> Result<(), String> {
+        let input = "[proj]/path/to/proj \"my main project\"\n/some/work \"work\"\n[web]@proj/web \"the site\"\n/some/other";
+        let mut p = Parser::new(input);
+        p.file()?;
+        let described: Vec<(String, Option<String>)> = p
+            .entries()
+            .into_iter()
+            .map(|e| (e.alias, e.description))
+            .collect();
+        assert_eq!(
+            vec![
+                ("other".to_string(), None),
+                ("proj".to_string(), Some("my main project".to_string())),
+                ("web".to_string(), Some("the site".to_string())),
+                ("work".to_string(), Some("work".to_string())),
+            ],
+            described
+        );
+        assert_eq!("cd /path/to/proj", p.entries()[1].body());
 
-    fn alias(&mut self) -> Result<(), String> {
-        self.matches(TOKEN_ALIAS)
+        let mut p = Parser::new("[*]/some/code \"all of it\"");
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "a description on line 1 needs a single alias, not a [*] line".into()
+            )),
+            p.file()
+        );
+        Ok(())
     }
 
-    fn glob(&mut self) -> Result<(), String> {
-        self.matches(TOKEN_GLOB)
+    #[test]
+    fn test_parse_alias_with_command() -> Result<(), String> {
+        let mut p = Parser::new("[mydir:pushd]/some/path\n[work]/some/work");
+        p.file()?;
+        assert_eq!(
+            vec![
+                AliasEntry {
+                    alias: "mydir".into(),
+                    path: "/some/path".into(),
+                    command: "pushd".into(),
+                    kind: EntryKind::Directory,
+                    description: None,
+                    line: 1,
+                },
+                AliasEntry {
+                    alias: "work".into(),
+                    path: "/some/work".into(),
+                    command: DEFAULT_COMMAND.into(),
+                    kind: EntryKind::Directory,
+                    description: None,
+                    line: 2,
+                },
+            ],
+            p.entries()
+        );
+        Ok(())
     }
 
-    fn path(&mut self) -> Result<(), String> {
-        self.matches(TOKEN_PATH)
+    #[test]
+    fn test_parse_alias_with_missing_command_fails() {
+        let mut p = Parser::new("[mydir:]/some/path");
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "expecting ALIAS; found <']', RBRACK>".into()
+            )),
+            p.file()
+        );
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use std::borrow::Cow;
-    use std::fs::create_dir;
-    use std::path::PathBuf;
 
-    use super::*;
+    #[test]
+    fn test_parse_alias_with_template() -> Result<(), String> {
+        let mut p = Parser::new("[gs]{cd %p && git status}/some/path\n[work]/some/work");
+        p.file()?;
+        let entries = p.entries();
+        assert_eq!("cd %p && git status", entries[0].command);
+        assert_eq!("cd /some/path && git status", entries[0].body());
+        assert_eq!("cd /some/work", entries[1].body());
+        Ok(())
+    }
 
     #[test]
-    fn test_create_parser() {
-        let p = Parser::new("/some/absolute/path");
+    fn test_parse_alias_references() -> Result<(), String> {
+        let mut p = Parser::new("[base]/projects\n[work]@base/work");
+        p.file()?;
+        assert_eq!(Some(&"/projects/work".to_string()), p.int_rep.get("work"));
+
+        let input = "[web:pushd]@front/web\nfront = @base/frontend/\n[base]/projects\n[same]@base";
+        let mut p = Parser::new(input);
+        p.file()?;
         assert_eq!(
-            Token::new(TOKEN_PATH, Cow::Owned("/some/absolute/path".into())),
-            p.lookahead
+            vec![
+                ("base", "/projects", "cd"),
+                ("front", "/projects/frontend", "cd"),
+                ("same", "/projects", "cd"),
+                ("web", "/projects/frontend/web", "pushd"),
+            ],
+            p.entries()
+                .iter()
+                .map(|e| (e.alias.as_str(), e.path.as_str(), e.command.as_str()))
+                .collect::<Vec<_>>()
         );
+        Ok(())
     }
 
     #[test]
-    #[should_panic]
-    fn test_create_parser_panics() {
-        Parser::new("");
+    fn test_parse_alias_reference_errors() {
+        let cases = [
+            (
+                "[work]@base/work",
+                DaliaError::ParseError(
+                    "alias 'work' on line 1 refers to '@base', which isn't defined".into(),
+                ),
+            ),
+            (
+                "[a]@b/a\n[b]@c/b\n[c]@b/c",
+                DaliaError::CircularReference(vec!["b".into(), "c".into(), "b".into()]),
+            ),
+            (
+                "[a]@a/x",
+                DaliaError::CircularReference(vec!["a".into(), "a".into()]),
+            ),
+            (
+                "[*]@base/code",
+                DaliaError::ParseError(
+                    "a reference to '@base' on line 1 needs a custom name, like [name]@base/path"
+                        .into(),
+                ),
+            ),
+        ];
+        for (input, expected) in cases.iter() {
+            let mut p = Parser::new(input);
+            assert_eq!(Err(expected.clone()), p.file(), "{}", input);
+        }
     }
 
     #[test]
-    #[should_panic]
-    fn test_create_parser_panics_with_empty_str() {
-        Parser::new("    ");
+    fn test_parse_error_position() {
+        let mut p = Parser::new("/some/path\n  [work /some/work");
+        assert!(p.file().is_err());
+        assert_eq!(Some((2, 9)), p.error_position());
+
+        let mut p = Parser::new("/some/path\n[gs]{cd é %p}[work");
+        assert!(p.file().is_err());
+        assert_eq!(Some((2, 14)), p.error_position());
+
+        let mut p = Parser::new("work = ");
+        assert!(p.file().is_err());
+        assert_eq!(None, p.error_position());
     }
 
     #[test]
-    fn test_parser_consume() {
-        let mut p = Parser::new("[alias]/some/absolute/path");
-        let _ = p.consume();
+    fn test_parse_sections() -> Result<(), String> {
+        let input = "/repos/dotfiles
+[section: work]
+/repos/frontend
+[api:pushd]/repos/api
+[section: home]
+[notes]/home/notes
+[photos]@home-notes/photos
+[section:]
+/repos/scratch";
+        let mut p = Parser::new(input);
+        p.file()?;
         assert_eq!(
-            Token::new(TOKEN_ALIAS, Cow::Owned("alias".into())),
-            p.lookahead
+            vec![
+                ("dotfiles", "/repos/dotfiles", "cd"),
+                ("home-notes", "/home/notes", "cd"),
+                ("home-photos", "/home/notes/photos", "cd"),
+                ("scratch", "/repos/scratch", "cd"),
+                ("work-api", "/repos/api", "pushd"),
+                ("work-frontend", "/repos/frontend", "cd"),
+            ],
+            p.entries()
+                .iter()
+                .map(|e| (e.alias.as_str(), e.path.as_str(), e.command.as_str()))
+                .collect::<Vec<_>>()
+        );
+
+        let options = ParserOptions {
+            section_separator: ".".into(),
+            ..Default::default()
+        };
+        let mut p = Parser::new_with_options("[section: work]\n/repos/frontend", options);
+        p.file()?;
+        assert_eq!(vec!["work.frontend"], sorted_aliases(&p));
+
+        let mut p = Parser::new("[section: -work]\n/repos/frontend");
+        assert!(matches!(p.file(), Err(DaliaError::ParseError(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fallback_paths() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("work")).map_err(|e| e.to_string())?;
+        let work = dir.join("work").display().to_string();
+        let missing = dir.join("opt").join("work").display().to_string();
+
+        let input = format!(
+            "[work:pushd]{} | {}\n{}|{}\n[gone]/does/not/exist | {}",
+            missing, work, missing, work, missing
+        );
+        let mut p = Parser::new(&input);
+        p.file()?;
+        assert_eq!(
+            vec![("work", work.as_str(), "pushd")],
+            p.entries()
+                .iter()
+                .map(|e| (e.alias.as_str(), e.path.as_str(), e.command.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![format!(
+                "skipping the alias on line 3: none of /does/not/exist, {} exist",
+                missing
+            )],
+            p.warnings
+        );
+
+        let mut p = Parser::new("[work]/opt/work | ");
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "missing path around '|' on line 1".into()
+            )),
+            p.file()
         );
+        Ok(())
     }
 
     #[test]
-    fn test_parser_matches() {
-        let mut p = Parser::new("[alias]/some/absolute/path");
-        let _ = p.matches(TOKEN_LBRACK);
+    fn test_parse_escaped_bar_is_part_of_the_path() -> Result<(), String> {
+        let mut p = Parser::new("[music]/some/rock \\| roll\n[both]/some/a\\|b | /some/c\n");
+        p.file()?;
+        assert_eq!("/some/rock | roll", p.int_rep.get("music").unwrap());
         assert_eq!(
-            Token::new(TOKEN_ALIAS, Cow::Owned("alias".into())),
-            p.lookahead
+            vec!["/some/a|b".to_string(), "/some/c".to_string()],
+            p.fallback_paths()
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
         );
+        Ok(())
     }
 
     #[test]
-    fn test_parser_does_not_match() {
-        let mut p = Parser::new("[alias]/some/absolute/path");
-        if let Err(e) = p.matches(TOKEN_RBRACK) {
-            assert_eq!("expecting RBRACK; found <'[', LBRACK>", e);
+    fn test_parse_template_errors() {
+        let cases = [
+            (
+                "[gs]{git status}/some/path",
+                "template 'git status' on line 1 doesn't contain %p",
+            ),
+            (
+                "[gs:pushd]{cd %p}/some/path",
+                "a template on line 1 needs a custom name without a command, like [name]{...}",
+            ),
+            (
+                "[*]{cd %p}/some/path",
+                "a template on line 1 needs a custom name without a command, like [name]{...}",
+            ),
+            (
+                "[gs]{cd %p/some/path",
+                "missing '}' after the template on line 1",
+            ),
+        ];
+        for (input, expected) in cases.iter() {
+            let mut p = Parser::new(input);
+            assert_eq!(
+                Err(DaliaError::ParseError(expected.to_string())),
+                p.file(),
+                "{}",
+                input
+            );
         }
     }
 
     #[test]
-    fn test_parse_file_with_alias_config() -> Result<(), String> {
-        let mut p = Parser::new("[alias]/some/absolute/path");
-        p.file()?;
-        Ok(())
+    fn test_strip_trailing_slash() {
+        assert_eq!("/path/to/dir", strip_trailing_slash("/path/to/dir/"));
+        assert_eq!("/path/to/dir", strip_trailing_slash("/path/to/dir//"));
+        assert_eq!("/path/to/dir", strip_trailing_slash("/path/to/dir"));
+        assert_eq!("/", strip_trailing_slash("/"));
+        assert_eq!("/", strip_trailing_slash("///"));
+        assert_eq!("", strip_trailing_slash(""));
     }
 
     #[test]
-    fn test_parse_file_with_single_path() -> Result<(), String> {
-        let mut p = Parser::new("/some/absolute/path");
-        p.file()?;
+    fn test_parse_trailing_slash_yields_same_alias() -> Result<(), String> {
+        for input in ["/path/to/dir/", "/path/to/dir"].iter() {
+            let mut p = Parser::new(input);
+            p.file()?;
+            assert_eq!(1, p.int_rep.len());
+            assert_eq!("/path/to/dir", p.int_rep.get("dir").unwrap());
+        }
         Ok(())
     }
 
     #[test]
-    fn test_parse_fails_with_invalid_path() {
-        let input = "some/absolute/path";
-        let mut p = Parser::new(input);
-        let result: Result<(), String> = p.file();
-        assert_eq!(result.unwrap_err(), "expecting PATH; found <'some', ALIAS>")
+    fn test_parse_fails_without_derivable_alias_name() {
+        let cases = [("~", "~"), ("~/", "~"), ("/", "/"), ("/some/path\n//", "/")];
+        for (input, path) in cases.iter() {
+            let line = input.lines().count();
+            let mut p = Parser::new(input);
+            assert_eq!(
+                Err(DaliaError::ParseError(format!(
+                    "can't name an alias for '{}' on line {}; give it a custom name like [name]{}",
+                    path, line, path
+                ))),
+                p.file(),
+                "{:?}",
+                input
+            );
+        }
     }
 
     #[test]
-    fn test_parse_complex_file() -> Result<(), String> {
-        let mut p = Parser::new(
-            r#"[alias]/another/absolute/path
-        /yet/another/path
-        "#,
-        );
+    fn test_parse_home_and_root_with_custom_names() -> Result<(), String> {
+        let mut p = Parser::new("[home]~\n[root]/");
         p.file()?;
-        assert!(!p.int_rep.is_empty());
-        assert_eq!(2, p.int_rep.len());
-        assert_eq!("/another/absolute/path", p.int_rep.get("alias").unwrap());
-        assert_eq!("/yet/another/path", p.int_rep.get("path").unwrap());
+        assert_eq!("~", p.int_rep.get("home").unwrap());
+        assert_eq!("/", p.int_rep.get("root").unwrap());
         Ok(())
     }
 
     #[test]
-    fn test_parsed_alias_is_lowercase() -> Result<(), String> {
-        let mut p = Parser::new("/absolute/Path");
+    fn test_parse_format_version() -> Result<(), String> {
+        let mut p = Parser::new("version = 1\n[work]/some/work");
         p.file()?;
-        assert_eq!("/absolute/Path", p.int_rep.get("path").unwrap().as_str());
+        assert_eq!(Some(1), p.format_version());
+        assert!(p.warnings().is_empty());
+        assert!(p.typed_warnings().is_empty());
+        assert_eq!(1, p.int_rep.len());
+
+        let mut p = Parser::new("version = 99\n[work]/some/work");
+        p.file()?;
+        assert_eq!(Some(99), p.format_version());
+        assert_eq!(
+            [DaliaWarning::UnsupportedVersion {
+                found: 99,
+                max_supported: FORMAT_VERSION
+            }],
+            p.typed_warnings()
+        );
+        assert_eq!(
+            ["format version 99 is newer than the supported version 1; some lines may not be understood"],
+            p.warnings()
+        );
+        assert_eq!("/some/work", p.int_rep.get("work").unwrap());
+
+        let mut p = Parser::new("[work]/some/work\nversion = /some/version");
+        p.file()?;
+        assert_eq!(None, p.format_version());
+        assert_eq!(1, p.format_version().unwrap_or(1));
+        assert!(p.warnings().is_empty());
+        assert_eq!("/some/version", p.int_rep.get("version").unwrap());
         Ok(())
     }
 
     #[test]
-    fn test_parsed_alias_with_tilde() -> Result<(), String> {
-        let mut p = Parser::new(
-            r#"
-        ~/absolute/Path
-        [another-path]~/absolute/Path
-        "#,
+    fn test_parse_format_version_zero_fails() {
+        let mut p = Parser::new("version = 0\n");
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "invalid format version '0' on line 1".into()
+            )),
+            p.file()
         );
+    }
+
+    #[test]
+    fn test_parse_exclusions() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        for d in ["api", "archive", "old", "web"].iter() {
+            create_dir(dir.join(d)).map_err(|e| e.to_string())?;
+        }
+        let input = format!(
+            "!archive\n[*]{}\n!{}\n[work]/some/work\n[play]/some/play\n!work",
+            dir.display(),
+            dir.join("old").display()
+        );
+        let mut p = Parser::new(input.as_str());
         p.file()?;
-        assert!(!p.int_rep.is_empty());
-        assert_eq!("~/absolute/Path", p.int_rep.get("path").unwrap().as_str());
+        assert_eq!(vec!["api", "play", "web"], sorted_aliases(&p));
+        assert!(!p.sources.contains_key("work"));
+        assert!(p.warnings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_unknown_exclusion_warns() -> Result<(), String> {
+        let mut p = Parser::new("[work]/some/work\n!play\n!/some/play/");
+        p.file()?;
+        assert_eq!(1, p.int_rep.len());
         assert_eq!(
-            "~/absolute/Path",
-            p.int_rep.get("another-path").unwrap().as_str()
+            [
+                "exclusion '!play' on line 2 doesn't match any alias",
+                "exclusion '!/some/play' on line 3 doesn't match any alias"
+            ],
+            p.warnings()
         );
         Ok(())
     }
 
     #[test]
-    fn test_parse_glob_asterisk() -> Result<(), String> {
-        let temp = temp_testdir::TempDir::default();
-        let file_path = PathBuf::from(temp.as_ref());
-
-        let path1 = format!("{}/one", file_path.to_str().unwrap());
-        if let Err(e) = create_dir(&path1) {
-            return Err(format!("couldn't create temp dir one: {}", e));
+    fn test_is_valid_alias_name() {
+        for name in [
+            "docs",
+            "My_Docs",
+            "proj-api",
+            ".idea",
+            "café",
+            "v1.2",
+            "proj.work.main",
+            "proj.",
+        ]
+        .iter()
+        {
+            assert!(is_valid_alias_name(name), "{}", name);
         }
-
-        let path2 = format!("{}/two", file_path.to_str().unwrap());
-        if let Err(e) = create_dir(&path2) {
-            return Err(format!("couldn't create temp dir two: {}", e));
+        for name in [
+            "", "my alias", "a;b", "rm -rf", "-x", "a'b", "$(x)", "a|b", "a`b",
+        ]
+        .iter()
+        {
+            assert!(!is_valid_alias_name(name), "{}", name);
         }
+    }
 
-        let path3 = format!("{}/three", file_path.to_str().unwrap());
-        if let Err(e) = create_dir(&path3) {
-            return Err(format!("couldn't create temp dir three: {}", e));
-        }
+    #[test]
+    fn test_parse_multi_byte_aliases_and_paths() -> Result<(), String> {
+        let input = "[résumé]/home/me/résumé\n写真 = ~/写真\n/data/🦀/Café\n~/文書";
+        let mut p = Parser::new(input);
+        p.file()?;
+        assert_eq!("/home/me/résumé", p.int_rep.get("résumé").unwrap());
+        assert_eq!("~/写真", p.int_rep.get("写真").unwrap());
+        assert_eq!("/data/🦀/Café", p.int_rep.get("café").unwrap());
+        assert_eq!("~/文書", p.int_rep.get("文書").unwrap());
+        Ok(())
+    }
 
-        let glob_path = format!("[*]{}", file_path.to_str().unwrap());
-        let mut p = Parser::new(glob_path.as_str());
+    #[test]
+    fn test_parse_alias_with_spaces_fails() {
+        let mut p = Parser::new("/some/path\n[my  alias]/path");
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "alias names can't contain spaces: 'my  alias'".into()
+            )),
+            p.file()
+        );
+        assert_eq!(Some((2, 6)), p.error_position());
+
+        let mut p = Parser::new("[my other alias:pushd]/path");
+        assert_eq!(
+            Err(DaliaError::ParseError(
+                "alias names can't contain spaces: 'my other alias'".into()
+            )),
+            p.file()
+        );
+    }
 
+    #[test]
+    fn test_parse_alias_names_with_dots() -> Result<(), String> {
+        let mut p = Parser::new("[proj.work.main]/path/to/repo\n/code/dalia.rs");
         p.file()?;
+        assert_eq!("/path/to/repo", p.int_rep.get("proj.work.main").unwrap());
+        assert_eq!("/code/dalia.rs", p.int_rep.get("dalia").unwrap());
 
-        assert!(!p.int_rep.is_empty());
-        assert_eq!(3, p.int_rep.len());
-        assert_eq!(path1, p.int_rep.get("one").unwrap().to_string());
-        assert_eq!(path2, p.int_rep.get("two").unwrap().to_string());
-        assert_eq!(path3, p.int_rep.get("three").unwrap().to_string());
+        let mut p = Parser::new("[proj.]/path/to/repo");
+        p.file()?;
+        assert_eq!("/path/to/repo", p.int_rep.get("proj.").unwrap());
+        assert_eq!(["alias name 'proj.' on line 1 ends with '.'"], p.warnings());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rejects_unsafe_derived_alias_names() {
+        for (input, alias) in [
+            ("/home/me/My Documents", "my documents"),
+            ("/tmp/a;b", "a;b"),
+        ]
+        .iter()
+        {
+            let mut p = Parser::new(input);
+            assert_eq!(
+                Err(DaliaError::ParseError(format!(
+                    "invalid alias name '{}' on line 1; {}",
+                    alias, ALIAS_NAME_RULE
+                ))),
+                p.file()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_glob_skips_unsafe_alias_names() -> Result<(), String> {
+        let temp = temp_testdir::TempDir::default();
+        let dir = PathBuf::from(temp.as_ref());
+        create_dir(dir.join("code")).map_err(|e| e.to_string())?;
+        create_dir(dir.join("my code")).map_err(|e| e.to_string())?;
 
+        let mut p = Parser::new(format!("[*]{}", dir.display()).as_str());
+        p.file()?;
+        assert_eq!(vec!["code"], sorted_aliases(&p));
+        assert_eq!(
+            [format!(
+                "skipping {}: 'my code' isn't a valid alias name",
+                dir.join("my code").display()
+            )],
+            p.warnings()
+        );
         Ok(())
     }
 }
@@ -0,0 +1,103 @@
+//! Formats errors for the terminal, quoting the configuration line an error was found on.
+
+use std::io::{self, IsTerminal};
+
+use crate::error::DaliaError;
+
+const RED: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// Reports whether errors should be colored: only when stderr is a terminal and `NO_COLOR`
+/// isn't set, as described at https://no-color.org.
+pub fn color_enabled() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && io::stderr().is_terminal()
+}
+
+/// Describes `error` for the dalia command. Errors found at a known position in the
+/// configuration are followed by the line from `source` they were found on, with a caret under
+/// the column, e.g.
+///
+/// ```text
+/// parse error in /home/me/.config/dalia/config at line 2: expecting RBRACK; found <EOF>
+///   |
+/// 2 | [work
+///   |      ^
+/// ```
+pub fn render(error: &DaliaError, source: &str, color: bool) -> String {
+    let paint = |text: &str| {
+        if color {
+            format!("{}{}{}", RED, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+    let (line, column) = match error.location() {
+        Some(location) => location,
+        None => return paint(&error.to_string()),
+    };
+    let text = match source.lines().nth(line.saturating_sub(1)) {
+        Some(text) => text,
+        None => return paint(&error.to_string()),
+    };
+    // Tabs are kept in front of the caret so that it lines up however wide they're shown.
+    let indent: String = text
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let gutter = " ".repeat(line.to_string().len());
+    format!(
+        "{}\n{} |\n{} | {}\n{} | {}{}",
+        paint(&error.to_string()),
+        gutter,
+        line,
+        text,
+        gutter,
+        indent,
+        paint("^")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_render_quotes_the_line_with_a_caret() {
+        let error = DaliaError::InConfig(
+            PathBuf::from("/home/me/.config/dalia/config"),
+            Box::new(DaliaError::Located(
+                2,
+                6,
+                Box::new(DaliaError::ParseError(
+                    "expecting RBRACK; found <EOF>".into(),
+                )),
+            )),
+        );
+        let expected =
+            "parse error in /home/me/.config/dalia/config at line 2: expecting RBRACK; found <EOF>
+  |
+2 | [work
+  |      ^";
+        assert_eq!(expected, render(&error, "/some/path\n[work\n", false));
+
+        let colored = render(&error, "/some/path\n[work\n", true);
+        assert!(colored.starts_with(RED));
+        assert!(colored.ends_with(&format!("{}^{}", RED, RESET)));
+    }
+
+    #[test]
+    fn test_render_without_a_location() {
+        let error = DaliaError::ParseError("missing path after '=' on line 1".into());
+        assert_eq!(
+            "missing path after '=' on line 1",
+            render(&error, "work =", false)
+        );
+        assert_eq!(
+            format!("{}missing path after '=' on line 1{}", RED, RESET),
+            render(&error, "work =", true)
+        );
+    }
+}
@@ -0,0 +1,515 @@
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// ShellFormat identifies a shell whose syntax dalia knows how to generate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ShellFormat {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+    Posix,
+    Powershell,
+    Elvish,
+    Tcsh,
+    Cmd,
+}
+
+impl ShellFormat {
+    /// Every supported shell, in the order they're listed in usage messages.
+    pub const ALL: [ShellFormat; 9] = [
+        ShellFormat::Bash,
+        ShellFormat::Zsh,
+        ShellFormat::Fish,
+        ShellFormat::Nushell,
+        ShellFormat::Posix,
+        ShellFormat::Powershell,
+        ShellFormat::Elvish,
+        ShellFormat::Tcsh,
+        ShellFormat::Cmd,
+    ];
+
+    /// The shells dalia can print a completion script for.
+    pub const COMPLETIONS: [ShellFormat; 3] =
+        [ShellFormat::Bash, ShellFormat::Zsh, ShellFormat::Fish];
+
+    /// The shells that can load dalia's aliases straight from its output when they start.
+    pub const INIT: [ShellFormat; 5] = [
+        ShellFormat::Bash,
+        ShellFormat::Zsh,
+        ShellFormat::Fish,
+        ShellFormat::Posix,
+        ShellFormat::Elvish,
+    ];
+
+    /// The name used to select this shell on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShellFormat::Bash => "bash",
+            ShellFormat::Zsh => "zsh",
+            ShellFormat::Fish => "fish",
+            ShellFormat::Nushell => "nushell",
+            ShellFormat::Posix => "posix",
+            ShellFormat::Powershell => "powershell",
+            ShellFormat::Elvish => "elvish",
+            ShellFormat::Tcsh => "tcsh",
+            ShellFormat::Cmd => "cmd",
+        }
+    }
+
+    /// Quotes a path so that this shell reads it back as a single word, whatever spaces, quotes
+    /// or backticks are in it. A leading `~` or `~user` is left outside the quotes so it still
+    /// expands, and `$` is left alone so that paths like `$HOME/code` keep working. Nushell
+    /// expands a quoted `~` itself, so there the whole path is quoted, and since its plain
+    /// strings don't expand variables, a path that refers to them is written as an interpolated
+    /// string like `$"($env.HOME)/code"`. PowerShell paths are single-quoted with any `'`
+    /// doubled, since it resolves `~` itself, unless they refer to variables, which are
+    /// double-quoted and read from the environment as `${env:HOME}`. Elvish paths are
+    /// single-quoted the same way, but with `~` left outside the quotes and variables like
+    /// `$HOME` read from the environment as `$E:HOME`. A backslash is just another character
+    /// inside tcsh's double quotes, so there a quote, backtick, `!` or `\$` is escaped between
+    /// two quoted strings instead, as in `"say "\""hi"\"""`, which also keeps `!` from starting a
+    /// history substitution. Paths for cmd's doskey macros are
+    /// double-quoted with any `$` doubled, and with `~` and variables written the way cmd
+    /// expands them, like `%USERPROFILE%`.
+    ///
+    /// A backslash before a space, a quote, `$`, a backtick or another backslash escapes it, the
+    /// way configurations wrote such paths before they were quoted, so `Mobile\ Documents` is
+    /// quoted as `"Mobile Documents"` and `\$` stays a literal `$`. Any other backslash, like
+    /// those in `C:\Users`, is kept as it is.
+    pub fn quote_path(&self, path: &str) -> String {
+        let special: &[char] = match self {
+            ShellFormat::Bash | ShellFormat::Zsh | ShellFormat::Posix => &['"', '\\', '`'],
+            ShellFormat::Fish | ShellFormat::Nushell => &['"', '\\'],
+            ShellFormat::Elvish => &[],
+            ShellFormat::Tcsh => &['"', '`', '!'],
+            ShellFormat::Powershell => return quote_powershell(path),
+            ShellFormat::Cmd => return quote_cmd(path),
+        };
+        if *self == ShellFormat::Nushell && has_variables(path) {
+            return quote_nushell_interpolated(path);
+        }
+        let (home, rest) = match path.find('/') {
+            _ if *self == ShellFormat::Nushell || !path.starts_with('~') => ("", path),
+            Some(i) => path.split_at(i + 1),
+            None => (path, ""),
+        };
+        if home.len() == path.len() {
+            return home.to_string();
+        }
+        if *self == ShellFormat::Elvish {
+            return format!("{}{}", home, quote_elvish(rest));
+        }
+        let mut quoted = String::with_capacity(path.len() + 2);
+        quoted.push_str(home);
+        quoted.push('"');
+        for (c, escaped) in unescape(rest) {
+            if special.contains(&c) || (escaped && c == '$' && *self != ShellFormat::Nushell) {
+                if *self == ShellFormat::Tcsh {
+                    quoted.push_str("\"\\");
+                    quoted.push(c);
+                    quoted.push('"');
+                    continue;
+                }
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    }
+}
+
+/// Part is a piece of a configured path: either plain text, or the name in a `$NAME` or
+/// `${NAME}` reference to an environment variable.
+enum Part {
+    Text(String),
+    Variable(String),
+}
+
+/// Splits `path` into its text and the variables it refers to, leaving out the backslashes that
+/// escape characters, so that `\$` is text.
+fn split_variables(path: &str) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = unescape(path).peekable();
+    while let Some((c, escaped)) = chars.next() {
+        if c != '$' || escaped {
+            text.push(c);
+            continue;
+        }
+        let braced = chars.next_if(|&(c, e)| c == '{' && !e).is_some();
+        let mut name = String::new();
+        // Like the shells that expand them, a name can't start with a digit, so `$5` is text.
+        while let Some((c, _)) = chars.next_if(|&(c, e)| {
+            !e && (c.is_ascii_alphabetic() || c == '_' || (c.is_ascii_digit() && !name.is_empty()))
+        }) {
+            name.push(c);
+        }
+        if name.is_empty() || (braced && chars.next_if(|&(c, e)| c == '}' && !e).is_none()) {
+            text.push('$');
+            if braced {
+                text.push('{');
+            }
+            text.push_str(&name);
+            continue;
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(std::mem::take(&mut text)));
+        }
+        parts.push(Part::Variable(name));
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    parts
+}
+
+/// Returns whether `path` refers to any environment variables.
+fn has_variables(path: &str) -> bool {
+    split_variables(path)
+        .iter()
+        .any(|part| matches!(part, Part::Variable(_)))
+}
+
+/// Quotes `path` for PowerShell. A path without variables is single-quoted with any `'`
+/// doubled, so nothing in it is special. Otherwise it's double-quoted, with each `$NAME` or
+/// `${NAME}` read from the environment as `${env:NAME}`, and any backtick, `"` or other `$`
+/// escaped with a backtick.
+fn quote_powershell(path: &str) -> String {
+    if !has_variables(path) {
+        let path: String = unescape(path).map(|(c, _)| c).collect();
+        return format!("'{}'", path.replace('\'', "''"));
+    }
+    let mut quoted = String::from("\"");
+    for part in split_variables(path) {
+        match part {
+            Part::Text(text) => {
+                for c in text.chars() {
+                    if matches!(c, '`' | '"' | '$') {
+                        quoted.push('`');
+                    }
+                    quoted.push(c);
+                }
+            }
+            Part::Variable(name) => quoted.push_str(&format!("${{env:{}}}", name)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes `path` as a Nushell interpolated string, since a plain string doesn't expand
+/// variables. Each `$NAME` or `${NAME}` is read from the environment as `($env.NAME)`, and any
+/// `"`, backslash or parenthesis in the rest of the path is escaped with a backslash.
+fn quote_nushell_interpolated(path: &str) -> String {
+    let mut quoted = String::from("$\"");
+    for part in split_variables(path) {
+        match part {
+            Part::Text(text) => {
+                for c in text.chars() {
+                    if matches!(c, '"' | '\\' | '(' | ')') {
+                        quoted.push('\\');
+                    }
+                    quoted.push(c);
+                }
+            }
+            Part::Variable(name) => quoted.push_str(&format!("($env.{})", name)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Single-quotes `path` for Elvish, whose single-quoted strings treat nothing but a doubled `'`
+/// specially. Each `$NAME` or `${NAME}` is left outside the quotes as `$E:NAME`, since Elvish
+/// only reads environment variables through the `E:` namespace.
+fn quote_elvish(path: &str) -> String {
+    let quoted: String = split_variables(path)
+        .into_iter()
+        .map(|part| match part {
+            Part::Text(text) => format!("'{}'", text.replace('\'', "''")),
+            Part::Variable(name) => format!("$E:{}", name),
+        })
+        .collect();
+    if quoted.is_empty() {
+        return "''".to_string();
+    }
+    quoted
+}
+
+/// Double-quotes `path` for a cmd doskey macro, where `$` starts a macro parameter unless it's
+/// doubled. `$NAME` and `${NAME}` become `%NAME%`, and a leading `~` becomes `%USERPROFILE%`,
+/// since cmd expands neither of the others.
+fn quote_cmd(path: &str) -> String {
+    let home = path == "~" || path.starts_with("~/") || path.starts_with("~\\");
+    let mut quoted = String::from(if home { "\"%USERPROFILE%" } else { "\"" });
+    for part in split_variables(if home { &path[1..] } else { path }) {
+        match part {
+            Part::Text(text) => quoted.push_str(&text.replace('$', "$$")),
+            Part::Variable(name) => quoted.push_str(&format!("%{}%", name)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Yields each character of a configured path along with whether a backslash escaped it, leaving
+/// out the backslashes that do.
+fn unescape(path: &str) -> impl Iterator<Item = (char, bool)> + '_ {
+    let mut chars = path.chars().peekable();
+    std::iter::from_fn(move || {
+        let c = chars.next()?;
+        match chars.peek() {
+            Some(&escaped @ (' ' | '"' | '\'' | '$' | '`' | '\\')) if c == '\\' => {
+                chars.next();
+                Some((escaped, true))
+            }
+            _ => Some((c, false)),
+        }
+    })
+}
+
+impl FromStr for ShellFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Nushell's own command is `nu`, so it's accepted as another name for it, and csh reads
+        // the same aliases as tcsh.
+        match s {
+            "nu" => return Ok(ShellFormat::Nushell),
+            "csh" => return Ok(ShellFormat::Tcsh),
+            _ => {}
+        }
+        ShellFormat::ALL
+            .iter()
+            .find(|shell| shell.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown shell: {}", s))
+    }
+}
+
+impl std::fmt::Display for ShellFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_format_from_str() {
+        assert_eq!(Ok(ShellFormat::Bash), "bash".parse());
+        assert_eq!(Ok(ShellFormat::Zsh), "zsh".parse());
+        assert_eq!(Ok(ShellFormat::Fish), "fish".parse());
+        assert_eq!(Ok(ShellFormat::Nushell), "nushell".parse());
+        assert_eq!(Ok(ShellFormat::Nushell), "nu".parse());
+        assert_eq!(Ok(ShellFormat::Posix), "posix".parse());
+        assert_eq!(Ok(ShellFormat::Powershell), "powershell".parse());
+        assert_eq!(Ok(ShellFormat::Elvish), "elvish".parse());
+        assert_eq!(Ok(ShellFormat::Tcsh), "tcsh".parse());
+        assert_eq!(Ok(ShellFormat::Tcsh), "csh".parse());
+        assert_eq!(Ok(ShellFormat::Cmd), "cmd".parse());
+    }
+
+    #[test]
+    fn test_shell_format_from_str_fails() {
+        assert_eq!(
+            Err("unknown shell: xonsh".to_string()),
+            "xonsh".parse::<ShellFormat>()
+        );
+    }
+
+    #[test]
+    fn test_quote_path_for_powershell() {
+        let shell = ShellFormat::Powershell;
+        assert_eq!(
+            "'C:\\Users\\me\\My Documents'",
+            shell.quote_path("C:\\Users\\me\\My Documents")
+        );
+        assert_eq!(
+            "'/home/me/it''s-here'",
+            shell.quote_path("/home/me/it's-here")
+        );
+        assert_eq!(
+            "'~/Mobile Documents'",
+            shell.quote_path("~/Mobile\\ Documents")
+        );
+        assert_eq!(
+            "'/say \"hi\"/`date`/$5'",
+            shell.quote_path("/say \"hi\"/`date`/$5")
+        );
+    }
+
+    #[test]
+    fn test_quote_path_reads_variables_from_the_environment_in_powershell() {
+        let shell = ShellFormat::Powershell;
+        assert_eq!("\"${env:HOME}/code\"", shell.quote_path("$HOME/code"));
+        assert_eq!(
+            "\"C:/${env:PROJECT_DIR}-v2/it's `\"here`\"/`$5\"",
+            shell.quote_path("C:/${PROJECT_DIR}-v2/it's \"here\"/\\$5")
+        );
+    }
+
+    #[test]
+    fn test_quote_path_reads_variables_from_the_environment_in_nushell() {
+        let shell = ShellFormat::Nushell;
+        assert_eq!("$\"($env.HOME)/code\"", shell.quote_path("$HOME/code"));
+        assert_eq!(
+            "$\"~/($env.PROJECT_DIR)/a \\(b\\)/say \\\"hi\\\"\"",
+            shell.quote_path("~/${PROJECT_DIR}/a (b)/say \"hi\"")
+        );
+        assert_eq!("\"/costs/$5\"", shell.quote_path("/costs/$5"));
+    }
+
+    #[test]
+    fn test_quote_path_for_elvish() {
+        let shell = ShellFormat::Elvish;
+        assert_eq!("'/some/work/path'", shell.quote_path("/some/work/path"));
+        assert_eq!("'/my docs'", shell.quote_path("/my\\ docs"));
+        assert_eq!(
+            "'/home/me/it''s-here'",
+            shell.quote_path("/home/me/it's-here")
+        );
+        assert_eq!(
+            "'/say \"hi\"/`date`/a\\b'",
+            shell.quote_path("/say \"hi\"/`date`/a\\b")
+        );
+        assert_eq!("~/'my code'", shell.quote_path("~/my code"));
+        assert_eq!("~", shell.quote_path("~"));
+    }
+
+    #[test]
+    fn test_quote_path_reads_variables_from_the_environment_in_elvish() {
+        let shell = ShellFormat::Elvish;
+        assert_eq!("$E:HOME'/code'", shell.quote_path("$HOME/code"));
+        assert_eq!(
+            "'/a/'$E:PROJECT_DIR'-v2'",
+            shell.quote_path("/a/${PROJECT_DIR}-v2")
+        );
+        assert_eq!("'/costs/$5'", shell.quote_path("/costs/\\$5"));
+        assert_eq!("'/$/${x'", shell.quote_path("/$/${x"));
+    }
+
+    #[test]
+    fn test_quote_path_for_cmd() {
+        let shell = ShellFormat::Cmd;
+        assert_eq!(r#""C:\Projects""#, shell.quote_path(r"C:\Projects"));
+        assert_eq!(
+            r#""C:\Users\me\My Documents""#,
+            shell.quote_path(r"C:\Users\me\My Documents")
+        );
+        assert_eq!(r#""%USERPROFILE%/my code""#, shell.quote_path("~/my code"));
+        assert_eq!(r#""%USERPROFILE%""#, shell.quote_path("~"));
+        assert_eq!(
+            r#""%APPDATA%\dalia""#,
+            shell.quote_path(r"${APPDATA}\dalia")
+        );
+        assert_eq!(r#""D:\costs\$$5""#, shell.quote_path(r"D:\costs\\\$5"));
+    }
+
+    #[test]
+    fn test_quote_path_for_tcsh() {
+        let shell = ShellFormat::Tcsh;
+        assert_eq!(r#""/my docs""#, shell.quote_path(r"/my\ docs"));
+        assert_eq!(r#""/say "\""hi"\""""#, shell.quote_path(r#"/say "hi""#));
+        assert_eq!(r#""/"\`"date"\`"""#, shell.quote_path("/`date`"));
+        assert_eq!(r#""/costs/"\$"5""#, shell.quote_path(r"/costs/\$5"));
+        assert_eq!(r#""/hi"\!"there""#, shell.quote_path("/hi!there"));
+        assert_eq!(r#""C:\Users""#, shell.quote_path(r"C:\Users"));
+        assert_eq!(r#""$HOME/code""#, shell.quote_path("$HOME/code"));
+        assert_eq!(r#"~/"my code""#, shell.quote_path("~/my code"));
+    }
+
+    #[test]
+    fn test_quote_path_leaves_a_plain_path_alone() {
+        for shell in ShellFormat::ALL
+            .iter()
+            .filter(|s| !matches!(s, ShellFormat::Powershell | ShellFormat::Elvish))
+        {
+            assert_eq!("\"/some/work/path\"", shell.quote_path("/some/work/path"));
+        }
+    }
+
+    #[test]
+    fn test_quote_path_with_spaces_and_apostrophes() {
+        for shell in ShellFormat::ALL
+            .iter()
+            .filter(|s| !matches!(s, ShellFormat::Powershell | ShellFormat::Elvish))
+        {
+            assert_eq!("\"/my docs\"", shell.quote_path("/my docs"));
+            assert_eq!(
+                "\"/home/me/it's-here\"",
+                shell.quote_path("/home/me/it's-here")
+            );
+        }
+    }
+
+    #[test]
+    fn test_quote_path_escapes_quotes_and_backslashes() {
+        for shell in ShellFormat::ALL.iter().filter(|s| {
+            !matches!(
+                s,
+                ShellFormat::Powershell
+                    | ShellFormat::Elvish
+                    | ShellFormat::Tcsh
+                    | ShellFormat::Cmd
+            )
+        }) {
+            assert_eq!(
+                "\"/say \\\"hi\\\"/a\\\\b\"",
+                shell.quote_path("/say \"hi\"/a\\b")
+            );
+        }
+    }
+
+    #[test]
+    fn test_quote_path_with_escaped_characters() {
+        assert_eq!(
+            "~/\"Library/Mobile Documents\"",
+            ShellFormat::Bash.quote_path("~/Library/Mobile\\ Documents")
+        );
+        assert_eq!(
+            "\"/costs/\\$5\"",
+            ShellFormat::Zsh.quote_path("/costs/\\$5")
+        );
+        assert_eq!(
+            "\"/costs/\\$5\"",
+            ShellFormat::Fish.quote_path("/costs/\\$5")
+        );
+        assert_eq!(
+            "\"/costs/$5\"",
+            ShellFormat::Nushell.quote_path("/costs/\\$5")
+        );
+        assert_eq!("\"/it's\"", ShellFormat::Posix.quote_path("/it\\'s"));
+        assert_eq!("\"C:\\\\Users\"", ShellFormat::Bash.quote_path("C:\\Users"));
+    }
+
+    #[test]
+    fn test_quote_path_with_backticks() {
+        assert_eq!("\"/\\`date\\`\"", ShellFormat::Bash.quote_path("/`date`"));
+        assert_eq!("\"/\\`date\\`\"", ShellFormat::Posix.quote_path("/`date`"));
+        assert_eq!("\"/`date`\"", ShellFormat::Fish.quote_path("/`date`"));
+        assert_eq!("\"/`date`\"", ShellFormat::Nushell.quote_path("/`date`"));
+    }
+
+    #[test]
+    fn test_quote_path_leaves_variables_to_expand() {
+        assert_eq!("\"$HOME/code\"", ShellFormat::Zsh.quote_path("$HOME/code"));
+        assert_eq!("\"$HOME/code\"", ShellFormat::Fish.quote_path("$HOME/code"));
+    }
+
+    #[test]
+    fn test_quote_path_leaves_the_home_directory_unquoted() {
+        assert_eq!("~/\"my code\"", ShellFormat::Bash.quote_path("~/my code"));
+        assert_eq!("~me/\"code\"", ShellFormat::Fish.quote_path("~me/code"));
+        assert_eq!("~", ShellFormat::Posix.quote_path("~"));
+        assert_eq!("~/", ShellFormat::Bash.quote_path("~/"));
+        assert_eq!(
+            "\"~/my code\"",
+            ShellFormat::Nushell.quote_path("~/my code")
+        );
+    }
+}